@@ -17,6 +17,16 @@ fn foo_with_ps(x: i64) -> i64 {
     }
 }
 
+#[inline(never)]
+fn foo_with_empty_scope(x: i64) -> i64 {
+    with_params! {
+        params ParamScope::default();
+        get y = y or 0;
+
+        x+y
+    }
+}
+
 #[inline(never)]
 fn foo_with_config(x: i64, cfg: &Config) -> i64 {
     let y = cfg.get_int("y").unwrap();
@@ -59,6 +69,20 @@ fn call_foo_with_ps_optimized(nloop: i64) -> i64 {
     sum
 }
 
+#[inline(never)]
+fn call_foo_with_empty_scope(nloop: i64) -> i64 {
+    let mut sum = 0;
+
+    with_params! {
+        set y = 42;
+
+        for i in 0..nloop {
+            sum += foo_with_empty_scope(i);
+        }
+    }
+    sum
+}
+
 #[inline(never)]
 fn call_foo_with_ps_and_raw_btree(nloop: i64) -> i64 {
     let mut sum = 0;
@@ -73,6 +97,20 @@ fn call_foo_with_ps_and_raw_btree(nloop: i64) -> i64 {
     sum
 }
 
+#[inline(never)]
+fn call_foo_with_dynamic_key(nloop: i64, line: &str) -> i64 {
+    let mut sum = 0;
+    let (key, _) = line.split_once('=').unwrap();
+    with_params! {
+        set y = 42;
+
+        for i in 0..nloop {
+            sum += THREAD_STORAGE.with(|ts| ts.borrow().get_or_else(key, i));
+        }
+    }
+    sum
+}
+
 #[inline(never)]
 fn call_foo_with_config_rs(nloop: i64, cfg: &Config) -> i64 {
     let mut sum = 0;
@@ -98,12 +136,24 @@ pub fn bench_apis_with_ps_optimized(c: &mut Criterion) {
     });
 }
 
+pub fn bench_apis_with_empty_scope(c: &mut Criterion) {
+    c.bench_function("raw api with empty scope", |b| {
+        b.iter(|| call_foo_with_empty_scope(black_box(10000)))
+    });
+}
+
 pub fn bench_apis_with_ps(c: &mut Criterion) {
     c.bench_function("raw api with ps", |b| {
         b.iter(|| call_foo_with_ps(black_box(10000)))
     });
 }
 
+pub fn bench_apis_with_dynamic_key(c: &mut Criterion) {
+    c.bench_function("raw api with dynamic key", |b| {
+        b.iter(|| call_foo_with_dynamic_key(black_box(10000), black_box("y=1")))
+    });
+}
+
 pub fn bench_config_rs(c: &mut Criterion) {
     let cfg = config::Config::builder()
         .add_source(config::File::from_str(
@@ -122,7 +172,9 @@ criterion_group!(
     bench_apis,
     bench_apis_with_ps_and_raw_btree,
     bench_apis_with_ps_optimized,
+    bench_apis_with_empty_scope,
     bench_apis_with_ps,
+    bench_apis_with_dynamic_key,
     bench_config_rs,
 );
 criterion_main!(benches);
@@ -0,0 +1,126 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use hyperparameter::*;
+
+const NPARAMS: usize = 200;
+const KEY: u64 = xxhash("bench.param.100".as_bytes());
+
+/// Fill the thread storage with `NPARAMS` parameters, as a training script would.
+fn fill_storage() -> ParamScope {
+    let mut ps = ParamScope::default();
+    for i in 0..NPARAMS {
+        ps.put(format!("bench.param.{}", i), i as i64);
+    }
+    ps
+}
+
+#[inline(never)]
+fn storage_get(nloop: i64) -> i64 {
+    let mut sum = 0;
+    for _ in 0..nloop {
+        sum += THREAD_STORAGE.with(|ts| ts.borrow().get_or_else(KEY, 0i64));
+    }
+    sum
+}
+
+#[inline(never)]
+fn storage_get_missing(nloop: i64) -> i64 {
+    let mut sum = 0;
+    for i in 0..nloop {
+        sum += THREAD_STORAGE.with(|ts| ts.borrow().get_or_else("bench.missing", i));
+    }
+    sum
+}
+
+#[inline(never)]
+fn storage_put(nloop: i64) {
+    THREAD_STORAGE.with(|ts| {
+        let mut ts = ts.borrow_mut();
+        ts.enter();
+        for i in 0..nloop {
+            ts.put("bench.param.100", i);
+        }
+        ts.exit();
+    });
+}
+
+#[inline(never)]
+fn storage_enter_exit(nloop: i64) {
+    for _ in 0..nloop {
+        THREAD_STORAGE.with(|ts| {
+            let mut ts = ts.borrow_mut();
+            ts.enter();
+            ts.exit();
+        });
+    }
+}
+
+#[inline(never)]
+fn storage_enter_put_exit(nloop: i64) {
+    for i in 0..nloop {
+        THREAD_STORAGE.with(|ts| {
+            let mut ts = ts.borrow_mut();
+            ts.enter();
+            ts.put("bench.param.100", i);
+            ts.exit();
+        });
+    }
+}
+
+#[inline(never)]
+fn storage_capture(nloop: i64) {
+    for _ in 0..nloop {
+        black_box(ParamScope::capture());
+    }
+}
+
+pub fn bench_storage_get(c: &mut Criterion) {
+    with_params! {
+        params fill_storage();
+
+        c.bench_function("storage get", |b| b.iter(|| storage_get(black_box(10000))));
+        c.bench_function("storage get missing", |b| {
+            b.iter(|| storage_get_missing(black_box(10000)))
+        });
+    }
+}
+
+pub fn bench_storage_put(c: &mut Criterion) {
+    with_params! {
+        params fill_storage();
+
+        c.bench_function("storage put", |b| b.iter(|| storage_put(black_box(10000))));
+    }
+}
+
+pub fn bench_storage_enter_exit(c: &mut Criterion) {
+    with_params! {
+        params fill_storage();
+
+        c.bench_function("storage enter exit", |b| {
+            b.iter(|| storage_enter_exit(black_box(10000)))
+        });
+        c.bench_function("storage enter put exit", |b| {
+            b.iter(|| storage_enter_put_exit(black_box(10000)))
+        });
+    }
+}
+
+pub fn bench_storage_capture(c: &mut Criterion) {
+    with_params! {
+        params fill_storage();
+
+        c.bench_function("storage capture", |b| {
+            b.iter(|| storage_capture(black_box(1000)))
+        });
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_storage_get,
+    bench_storage_put,
+    bench_storage_enter_exit,
+    bench_storage_capture,
+);
+criterion_main!(benches);
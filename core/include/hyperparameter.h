@@ -0,0 +1,169 @@
+/* C API of the hyperparameter crate. */
+
+#ifndef HYPERPARAMETER_H
+#define HYPERPARAMETER_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+typedef struct ParamScope ParamScope;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a new ParamScope object and returns a pointer to it.
+ */
+ParamScope *param_scope_create(void);
+
+/**
+ * Destroys the ParamScope object at the given address.
+ */
+void param_scope_destroy(ParamScope *this_);
+
+/**
+ * Enters the given ParamScope object.
+ */
+void param_scope_enter(ParamScope *this_);
+
+/**
+ * Exits the given ParamScope object.
+ */
+void param_scope_exit(ParamScope *this_);
+
+/**
+ * Captures the parameters visible on the current thread into an opaque token.
+ *
+ * The token can be handed to another thread and installed there with
+ * `param_scope_install_token`. Release it with `param_scope_destroy`.
+ */
+ParamScope *param_scope_capture_token(void);
+
+/**
+ * Installs a captured token on the current thread.
+ *
+ * Returns an entered ParamScope object; call `param_scope_exit` and
+ * `param_scope_destroy` on it once the callback is done. The token itself
+ * is left untouched and can be installed on several threads.
+ */
+ParamScope *param_scope_install_token(const ParamScope *token);
+
+/**
+ * Hashes a string key into the hashed key taken by the `hget` functions.
+ */
+uint64_t param_scope_hash(const char *key);
+
+/**
+ * Gets an integer value from the given ParamScope object by hashed key.
+ */
+int64_t param_scope_hget_or_i64(ParamScope *this_, uint64_t hkey, int64_t def);
+
+/**
+ * Gets an integer value by hashed key; the former name of
+ * `param_scope_hget_or_i64`, kept for existing callers.
+ */
+int64_t param_scope_hget_i64(ParamScope *this_, uint64_t hkey, int64_t def);
+
+/**
+ * Gets a float value from the given ParamScope object by hashed key.
+ */
+double param_scope_hget_or_f64(ParamScope *this_, uint64_t hkey, double def);
+
+/**
+ * Gets a string value from the given ParamScope object by hashed key.
+ *
+ * The returned string is owned by the caller; release it with
+ * `param_scope_free_str`.
+ */
+char *param_scope_hget_or_str(ParamScope *this_, uint64_t hkey, const char *def);
+
+/**
+ * Releases a string returned by `param_scope_hget_or_str`.
+ */
+void param_scope_free_str(char *s);
+
+/**
+ * Gets a boolean value from the given ParamScope object by hashed key.
+ */
+bool param_scope_hget_or_bool(ParamScope *this_, uint64_t hkey, bool def);
+
+/**
+ * Sets an integer value in the given ParamScope object by string key.
+ */
+void param_scope_put_i64(ParamScope *this_, const char *key, int64_t val);
+
+/**
+ * Sets a float value in the given ParamScope object by string key.
+ */
+void param_scope_put_f64(ParamScope *this_, const char *key, double val);
+
+/**
+ * Sets a boolean value in the given ParamScope object by string key.
+ */
+void param_scope_put_bool(ParamScope *this_, const char *key, bool val);
+
+/**
+ * Sets a string value in the given ParamScope object by string key.
+ */
+void param_scope_put_str(ParamScope *this_, const char *key, const char *val);
+
+/**
+ * Gets an integer list from the given ParamScope object by hashed key.
+ *
+ * Pass `cap` 0 to query the length before allocating `out`.
+ */
+int64_t param_scope_hget_i64_list(ParamScope *this_, uint64_t hkey, int64_t *out, size_t cap);
+
+/**
+ * Gets a float list from the given ParamScope object by hashed key.
+ *
+ * Pass `cap` 0 to query the length before allocating `out`.
+ */
+int64_t param_scope_hget_f64_list(ParamScope *this_, uint64_t hkey, double *out, size_t cap);
+
+/**
+ * Sets an integer list in the given ParamScope object by string key.
+ */
+void param_scope_put_i64_list(ParamScope *this_, const char *key, const int64_t *vals, size_t len);
+
+/**
+ * Sets a float list in the given ParamScope object by string key.
+ */
+void param_scope_put_f64_list(ParamScope *this_, const char *key, const double *vals, size_t len);
+
+/**
+ * Deletes a parameter from the given ParamScope object by string key.
+ *
+ * On an entered scope the key stays deleted from the thread until the scope
+ * exits; see `ParamScope::del`.
+ */
+void param_scope_del(ParamScope *this_, const char *key);
+
+/**
+ * Lists the keys visible from the given ParamScope object, sorted.
+ *
+ * The number of keys is written to `len`. Release the array and its strings
+ * with `param_scope_free_keys`.
+ */
+char **param_scope_keys(ParamScope *this_, size_t *len);
+
+/**
+ * Releases an array returned by `param_scope_keys`.
+ */
+void param_scope_free_keys(char **keys, size_t len);
+
+/**
+ * Gets the type of a parameter from the given ParamScope object by hashed key.
+ *
+ * Returns 0 if the key is not set, then 1 for integers, 2 for floats,
+ * 3 for strings, 4 for booleans, 5 for lists and 6 for other values.
+ */
+int32_t param_scope_hget_type(ParamScope *this_, uint64_t hkey);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif // __cplusplus
+
+#endif /* HYPERPARAMETER_H */
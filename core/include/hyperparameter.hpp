@@ -0,0 +1,234 @@
+// Header-only C++ wrapper of the C API in hyperparameter.h.
+//
+//   #include "hyperparameter.hpp"
+//   using namespace hyperparameter::literals;
+//
+//   hyperparameter::ParamScope ps;
+//   ps.put("train.lr", 0.01);
+//   auto guard = ps.enter();  // exits the scope when destroyed
+//   double lr = hyperparameter::get("train.lr"_key, 0.1);
+//
+// Keys are hashed at compile time with the same xxh64 seed as the Rust
+// macros, so C++ and Rust code in one process read the same parameters.
+
+#ifndef HYPERPARAMETER_HPP
+#define HYPERPARAMETER_HPP
+
+#include <cstddef>
+#include <cstdint>
+#include <string>
+#include <utility>
+#include <vector>
+
+#include "hyperparameter.h"
+#include "hyperparameter_xxh64.hpp"
+
+namespace hyperparameter {
+
+/// Hash a key the way `get_param!` does.
+constexpr uint64_t xxhash(const char *key, size_t len) {
+  return detail::xxh64::hash(key, len, HASH_SEED);
+}
+
+namespace literals {
+/// `"train.lr"_key` is the hash of the key `train.lr`, computed at compile time.
+constexpr uint64_t operator""_key(const char *key, size_t len) {
+  return xxhash(key, len);
+}
+} // namespace literals
+
+/// The type of a parameter, as returned by `param_scope_hget_type`.
+enum class ParamType : int32_t {
+  Unset = 0,
+  Int = 1,
+  Float = 2,
+  Str = 3,
+  Bool = 4,
+  List = 5,
+  Other = 6,
+};
+
+class ParamScope;
+
+/// Keeps a ParamScope entered, and exits it when destroyed.
+class [[nodiscard]] ScopeGuard {
+public:
+  ScopeGuard(const ScopeGuard &) = delete;
+  ScopeGuard &operator=(const ScopeGuard &) = delete;
+  ScopeGuard(ScopeGuard &&other) noexcept
+      : scope_(std::exchange(other.scope_, nullptr)) {}
+  ScopeGuard &operator=(ScopeGuard &&) = delete;
+  ~ScopeGuard() {
+    if (scope_ != nullptr)
+      param_scope_exit(scope_);
+  }
+
+private:
+  friend class ParamScope;
+  explicit ScopeGuard(::ParamScope *scope) : scope_(scope) {}
+  ::ParamScope *scope_;
+};
+
+/// Owns a parameter scope of the Rust library.
+///
+/// Parameters put into the scope are visible to the current thread, from
+/// C++ and Rust alike, while a guard returned by `enter()` is alive.
+class ParamScope {
+public:
+  ParamScope() : scope_(param_scope_create()) {}
+  ParamScope(const ParamScope &) = delete;
+  ParamScope &operator=(const ParamScope &) = delete;
+  ParamScope(ParamScope &&other) noexcept
+      : scope_(std::exchange(other.scope_, nullptr)) {}
+  ParamScope &operator=(ParamScope &&other) noexcept {
+    std::swap(scope_, other.scope_);
+    return *this;
+  }
+  ~ParamScope() {
+    if (scope_ != nullptr)
+      param_scope_destroy(scope_);
+  }
+
+  /// Capture the parameters visible on the current thread, to enter them on
+  /// another thread.
+  static ParamScope capture() { return ParamScope(param_scope_capture_token()); }
+
+  /// Enter the scope until the returned guard is destroyed.
+  ///
+  /// The guard refers to this scope, which must outlive it, so temporaries
+  /// such as `ParamScope::capture()` have to be stored before entering.
+  ScopeGuard enter() & {
+    param_scope_enter(scope_);
+    return ScopeGuard(scope_);
+  }
+  ScopeGuard enter() && = delete;
+
+  ParamScope &put(const char *key, int64_t val) {
+    param_scope_put_i64(scope_, key, val);
+    return *this;
+  }
+  ParamScope &put(const char *key, int32_t val) {
+    return put(key, static_cast<int64_t>(val));
+  }
+  ParamScope &put(const char *key, double val) {
+    param_scope_put_f64(scope_, key, val);
+    return *this;
+  }
+  ParamScope &put(const char *key, bool val) {
+    param_scope_put_bool(scope_, key, val);
+    return *this;
+  }
+  ParamScope &put(const char *key, const char *val) {
+    param_scope_put_str(scope_, key, val);
+    return *this;
+  }
+  ParamScope &put(const char *key, const std::string &val) {
+    return put(key, val.c_str());
+  }
+  ParamScope &put(const char *key, const std::vector<int64_t> &val) {
+    param_scope_put_i64_list(scope_, key, val.data(), val.size());
+    return *this;
+  }
+  ParamScope &put(const char *key, const std::vector<double> &val) {
+    param_scope_put_f64_list(scope_, key, val.data(), val.size());
+    return *this;
+  }
+
+  /// Get a parameter by hashed key, or `def` if it is unset or does not
+  /// convert to `T`.
+  template <typename T> T get(uint64_t hkey, T def) const;
+
+  template <typename T> T get(const std::string &key, T def) const {
+    return get<T>(xxhash(key.data(), key.size()), std::move(def));
+  }
+
+  ParamScope &del(const char *key) {
+    param_scope_del(scope_, key);
+    return *this;
+  }
+
+  /// The keys visible from this scope, sorted.
+  std::vector<std::string> keys() const {
+    size_t len = 0;
+    char **raw = param_scope_keys(scope_, &len);
+    std::vector<std::string> keys(raw, raw + len);
+    param_scope_free_keys(raw, len);
+    return keys;
+  }
+
+  ParamType type(uint64_t hkey) const {
+    return static_cast<ParamType>(param_scope_hget_type(scope_, hkey));
+  }
+
+private:
+  explicit ParamScope(::ParamScope *scope) : scope_(scope) {}
+
+  template <typename T>
+  std::vector<T> get_list(int64_t (*hget)(::ParamScope *, uint64_t, T *, size_t),
+                          uint64_t hkey, std::vector<T> def) const {
+    int64_t len = hget(scope_, hkey, nullptr, 0);
+    if (len < 0)
+      return def;
+    std::vector<T> vals(static_cast<size_t>(len));
+    hget(scope_, hkey, vals.data(), vals.size());
+    return vals;
+  }
+
+  ::ParamScope *scope_;
+};
+
+template <>
+inline int64_t ParamScope::get<int64_t>(uint64_t hkey, int64_t def) const {
+  return param_scope_hget_or_i64(scope_, hkey, def);
+}
+
+template <>
+inline int32_t ParamScope::get<int32_t>(uint64_t hkey, int32_t def) const {
+  return static_cast<int32_t>(param_scope_hget_or_i64(scope_, hkey, def));
+}
+
+template <>
+inline double ParamScope::get<double>(uint64_t hkey, double def) const {
+  return param_scope_hget_or_f64(scope_, hkey, def);
+}
+
+template <>
+inline bool ParamScope::get<bool>(uint64_t hkey, bool def) const {
+  return param_scope_hget_or_bool(scope_, hkey, def);
+}
+
+template <>
+inline std::string ParamScope::get<std::string>(uint64_t hkey,
+                                                std::string def) const {
+  char *raw = param_scope_hget_or_str(scope_, hkey, &def[0]);
+  std::string val(raw);
+  param_scope_free_str(raw);
+  return val;
+}
+
+template <>
+inline std::vector<int64_t>
+ParamScope::get<std::vector<int64_t>>(uint64_t hkey,
+                                      std::vector<int64_t> def) const {
+  return get_list(param_scope_hget_i64_list, hkey, std::move(def));
+}
+
+template <>
+inline std::vector<double>
+ParamScope::get<std::vector<double>>(uint64_t hkey,
+                                     std::vector<double> def) const {
+  return get_list(param_scope_hget_f64_list, hkey, std::move(def));
+}
+
+/// Get a parameter of the current thread, like `get_param!` in Rust.
+template <typename T> T get(uint64_t hkey, T def) {
+  return ParamScope().get<T>(hkey, std::move(def));
+}
+
+inline std::string get(uint64_t hkey, const char *def) {
+  return get<std::string>(hkey, def);
+}
+
+} // namespace hyperparameter
+
+#endif // HYPERPARAMETER_HPP
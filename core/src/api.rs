@@ -1,13 +1,21 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Debug;
+use std::hash::BuildHasher;
 
 use const_str;
 use xxhash_rust;
 
+use crate::mode::defaults_only;
 use crate::storage::{
-    frozen_global_storage, Entry, GetOrElse, MultipleVersion, Params, THREAD_STORAGE,
+    check_text_limit, frozen_global_prefix, frozen_global_storage, frozen_global_storage_hard,
+    key_matches, strip_namespace, unfrozen_global_prefix, Entry, GetOrElse, MissingParamError,
+    MultipleVersion, Params, THREAD_STORAGE,
 };
-use crate::value::{Value, EMPTY};
+use crate::subscribe::notify_subscribers;
+use crate::ticket::wait_for_tickets;
+use crate::value::{Value, ValueError, EMPTY};
 use crate::xxh::XXHashable;
 
 /// ParameterScope
@@ -29,7 +37,7 @@ pub enum ParamScope {
 
 impl Default for ParamScope {
     fn default() -> Self {
-        ParamScope::Just(Params::new())
+        ParamScope::Just(Params::default())
     }
 }
 
@@ -44,27 +52,189 @@ impl<T: Into<String> + Clone> From<&Vec<T>> for ParamScope {
 impl ParamScope {
     /// Get a parameter with a given hash key.
     pub fn get_with_hash(&self, key: u64) -> Value {
-        if let ParamScope::Just(changes) = self {
-            if let Some(e) = changes.get(&key) {
-                match e.value() {
-                    Value::Empty => {}
-                    v => return v.clone(),
-                }
-            }
+        if let Some(v) = self.local(key) {
+            return v.clone();
         }
         THREAD_STORAGE.with(|ts| {
             let ts = ts.borrow();
-            ts.get_entry(key).map(|e| e.clone_value()).unwrap_or(EMPTY)
+            ts.get_entry(key)
+                .filter(|e| !defaults_only(&e.key))
+                .map(|e| e.clone_value())
+                .unwrap_or(EMPTY)
         })
     }
 
     /// Get a parameter with a given key.
-    pub fn get<K>(&self, key: K) -> Value
+    pub fn get<K: XXHashable>(&self, key: K) -> Value {
+        let hkey = key.xxh();
+        self.get_with_hash(hkey)
+    }
+
+    /// Get a parameter defined in this scope object, ignoring the thread storage.
+    fn local(&self, key: u64) -> Option<&Value> {
+        match self {
+            ParamScope::Just(changes) => changes
+                .get(&key)
+                .filter(|e| !defaults_only(&e.key))
+                .map(|e| e.value())
+                .filter(|v| !matches!(v, Value::Empty)),
+            ParamScope::Nothing => None,
+        }
+    }
+
+    /// Create a read-only view that resolves misses against `other` before the thread storage.
+    ///
+    /// Neither scope is copied, so precomputed scopes such as model defaults
+    /// and experiment overrides can be composed at read time.
+    pub fn with_fallback<'a>(&'a self, other: &'a ParamScope) -> ScopeView<'a> {
+        ScopeView {
+            layers: vec![self, other],
+        }
+    }
+
+    /// Get a parameter with a given key, or `None` if it is not set.
+    pub fn try_get<K: XXHashable>(&self, key: K) -> Option<Value> {
+        let hkey = key.xxh();
+        if let Some(v) = self.local(hkey) {
+            return Some(v.clone());
+        }
+        THREAD_STORAGE.with(|ts| {
+            ts.borrow()
+                .get_entry(hkey)
+                .filter(|e| !defaults_only(&e.key))
+                .map(|e| e.clone_value())
+                .filter(|v| !matches!(v, Value::Empty))
+        })
+    }
+
+    /// Get a parameter, or `None` if it is not set, see [`Storage::get_opt`](crate::Storage::get_opt).
+    ///
+    /// Unlike [`ParamScope::try_get`], a key hidden by this scope object
+    /// reads as `None`.
+    pub fn get_opt<K: XXHashable, V>(&self, key: K) -> Option<V>
     where
-        K: Into<String> + Clone + XXHashable,
+        V: for<'a> TryFrom<&'a Value, Error = ValueError>,
     {
         let hkey = key.xxh();
-        self.get_with_hash(hkey)
+        if let ParamScope::Just(changes) = self {
+            if let Some(e) = changes.get(&hkey).filter(|e| !defaults_only(&e.key)) {
+                if matches!(e.value(), Value::Empty) {
+                    return None;
+                }
+                return crate::storage::convert_opt(|| e.key.to_string(), e.value());
+            }
+        }
+        THREAD_STORAGE.with(|ts| ts.borrow().get_opt_with_hash(hkey))
+    }
+
+    /// Get a parameter that has no sensible default, see [`Storage::get_required`](crate::Storage::get_required).
+    pub fn get_required<V>(&self, key: &str) -> Result<V, MissingParamError>
+    where
+        V: for<'a> TryFrom<&'a Value, Error = ValueError>,
+    {
+        if let ParamScope::Just(changes) = self {
+            if let Some(e) = changes.get(&key.xxh()).filter(|e| !defaults_only(&e.key)) {
+                if matches!(e.value(), Value::Empty) {
+                    return Err(MissingParamError::missing(key));
+                }
+                return V::try_from(e.value())
+                    .map_err(|err| MissingParamError::mismatch(key, e.value(), err));
+            }
+        }
+        THREAD_STORAGE.with(|ts| ts.borrow().get_required(key))
+    }
+
+    /// Put a parameter, failing if the value exceeds the configured size limits.
+    ///
+    /// See [`set_text_limit`](crate::set_text_limit) and
+    /// [`set_user_defined_limit`](crate::set_user_defined_limit).
+    pub fn try_put<K, V>(&mut self, key: K, val: V) -> Result<(), String>
+    where
+        K: Into<String> + XXHashable,
+        V: Into<Value> + Clone,
+    {
+        let changes = match self {
+            ParamScope::Just(changes) => changes,
+            ParamScope::Nothing => {
+                let res = THREAD_STORAGE.with(|ts| ts.borrow_mut().try_put(key, val));
+                notify_subscribers();
+                return res;
+            }
+        };
+        let hkey = key.xxh();
+        let val: Value = val.into();
+        if matches!(val, Value::Empty) {
+            changes.remove(&hkey);
+            return Ok(());
+        }
+        check_text_limit(&val)?;
+        if let std::collections::hash_map::Entry::Vacant(e) = changes.entry(hkey) {
+            let key: String = key.into();
+            e.insert(Entry::new(key, val));
+        } else {
+            changes.update(hkey, val);
+        }
+        Ok(())
+    }
+
+    /// Put a parameter and attach `tags` to its key, see [`tag_key`](crate::tag_key).
+    pub fn put_tagged<K, V>(&mut self, key: K, val: V, tags: &[&str])
+    where
+        K: Into<String> + Clone + XXHashable + Debug,
+        V: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value, Error = ValueError> + Clone,
+    {
+        crate::tags::tag_key(key.clone(), tags);
+        self.put(key, val);
+    }
+
+    /// Delete a parameter.
+    ///
+    /// On a scope object this drops its own value for the key; once entered,
+    /// the key is deleted from the thread storage until the scope exits.
+    pub fn del<K: XXHashable>(&mut self, key: K) {
+        match self {
+            ParamScope::Just(changes) => {
+                changes.remove(&key.xxh());
+            }
+            ParamScope::Nothing => {
+                THREAD_STORAGE.with(|ts| ts.borrow_mut().del(key));
+                notify_subscribers();
+            }
+        }
+    }
+
+    /// Hide a parameter while this scope is entered.
+    ///
+    /// Unlike [`ParamScope::del`], the deletion is kept by the scope object:
+    /// once entered, reads of the key fall back to their defaults, and the
+    /// shadowed value is back when the scope exits. `ParamScope::Nothing`
+    /// becomes a scope object holding the hidden key, the current thread is
+    /// left untouched until it is entered.
+    pub fn hide<K: Into<String> + XXHashable>(&mut self, key: K) {
+        if let ParamScope::Nothing = self {
+            *self = ParamScope::default();
+        }
+        if let ParamScope::Just(changes) = self {
+            let hkey = key.xxh();
+            let key: String = key.into();
+            changes.insert(hkey, Entry::new(key, Value::Empty));
+        }
+    }
+
+    /// Delete all parameters, with the same rules as [`ParamScope::del`].
+    pub fn clear(&mut self) {
+        match self {
+            ParamScope::Just(changes) => changes.clear(),
+            ParamScope::Nothing => {
+                THREAD_STORAGE.with(|ts| {
+                    let mut ts = ts.borrow_mut();
+                    for key in ts.keys() {
+                        ts.del(key);
+                    }
+                });
+                notify_subscribers();
+            }
+        }
     }
 
     pub fn add<T: Into<String>>(&mut self, expr: T) {
@@ -81,31 +251,214 @@ impl ParamScope {
             ts.keys().iter().cloned().collect()
         });
         if let ParamScope::Just(changes) = self {
-            retval.extend(changes.values().map(|e| e.key.clone()));
+            for e in changes.values() {
+                if matches!(e.value(), Value::Empty) {
+                    retval.remove(&*e.key);
+                } else {
+                    retval.insert(e.key.to_string());
+                }
+            }
         }
         retval.iter().cloned().collect()
     }
 
+    /// Get all parameters under a dotted prefix, keyed by the rest of their key.
+    ///
+    /// This lets a subsystem read its whole config block at once. Parameters
+    /// of this scope override those visible on the current thread:
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// with_params! {
+    ///     set train.lr = 0.01;
+    ///     set train.optim.name = "adam".to_string();
+    ///     set trainer.name = "gpu".to_string();
+    ///
+    ///     let mut ps = ParamScope::default();
+    ///     ps.put("train.epochs", 10);
+    ///     let train = ps.namespace("train");
+    ///     assert_eq!(
+    ///         vec!["epochs", "lr", "optim.name"],
+    ///         train.keys().collect::<Vec<_>>()
+    ///     );
+    ///     assert_eq!(Value::from(0.01), train["lr"]);
+    /// }
+    /// ```
+    pub fn namespace(&self, prefix: &str) -> BTreeMap<String, Value> {
+        let visible = THREAD_STORAGE.with(|ts| ts.borrow().namespace(prefix));
+        self.overlay(visible, |key| strip_namespace(key, prefix))
+    }
+
+    /// Get all parameters whose key matches a glob pattern, keyed by their key.
+    ///
+    /// `*` matches within one segment of the dotted key and `**` matches any
+    /// number of segments, which helps sweeping over per-layer parameters:
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// with_params! {
+    ///     set model.layer1.dropout = 0.1;
+    ///     set model.layer2.dropout = 0.2;
+    ///     set model.layer2.width = 128;
+    ///
+    ///     let dropouts = ParamScope::Nothing.query("model.*.dropout");
+    ///     assert_eq!(
+    ///         vec!["model.layer1.dropout", "model.layer2.dropout"],
+    ///         dropouts.keys().collect::<Vec<_>>()
+    ///     );
+    ///     assert_eq!(3, ParamScope::Nothing.query("model.**").len());
+    /// }
+    /// ```
+    pub fn query(&self, pattern: &str) -> BTreeMap<String, Value> {
+        let visible = THREAD_STORAGE.with(|ts| ts.borrow().query(pattern));
+        self.overlay(visible, |key| key_matches(pattern, key).then_some(key))
+    }
+
+    /// Apply the parameters of this scope for which `name` gives a name to `visible`.
+    fn overlay(
+        &self,
+        mut visible: BTreeMap<String, Value>,
+        name: impl Fn(&str) -> Option<&str>,
+    ) -> BTreeMap<String, Value> {
+        if let ParamScope::Just(changes) = self {
+            for e in changes.values() {
+                let name = match name(&e.key) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                if matches!(e.value(), Value::Empty) {
+                    visible.remove(name);
+                } else {
+                    visible.insert(name.to_string(), e.value().clone());
+                }
+            }
+        }
+        visible
+    }
+
+    /// Capture the parameters visible on the current thread.
+    ///
+    /// The returned scope can be moved to another thread and entered there,
+    /// which makes the captured parameters visible on that thread too.
+    pub fn capture() -> ParamScope {
+        THREAD_STORAGE.with(|ts| ParamScope::Just(ts.borrow().capture()))
+    }
+
+    /// Put a parameter only if it is unset, both in this scope and on the current thread.
+    ///
+    /// This lets library code contribute defaults without clobbering values
+    /// a caller already configured in an outer scope. The check happens at
+    /// call time, so build the scope right before entering it.
+    pub fn put_default<K, V>(&mut self, key: K, val: V)
+    where
+        K: Into<String> + Clone + XXHashable + Debug,
+        V: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value, Error = ValueError> + Clone,
+    {
+        if matches!(self.get_with_hash(key.xxh()), Value::Empty) {
+            self.put(key, val);
+        }
+    }
+
     /// Enter a new parameter scope.
     pub fn enter(&mut self) {
         THREAD_STORAGE.with(|ts| {
             let mut ts = ts.borrow_mut();
             ts.enter();
             if let ParamScope::Just(changes) = self {
-                for v in changes.values() {
-                    ts.put(v.key.clone(), v.value().clone());
+                for (hkey, v) in changes.iter() {
+                    #[cfg(feature = "provenance")]
+                    let put = crate::provenance::with_source(v.source().clone(), || {
+                        ts.try_put_with_hash(*hkey, v.key.clone(), v.clone_value())
+                    });
+                    #[cfg(not(feature = "provenance"))]
+                    let put = ts.try_put_with_hash(*hkey, v.key.clone(), v.clone_value());
+                    if let Err(e) = put {
+                        println!("hyperparameter warning: {}", e);
+                    }
                 }
             }
         });
+        // after the storage is released, as subscribers may read parameters
+        #[cfg(feature = "tracing")]
+        if let ParamScope::Just(changes) = self {
+            crate::logging::trace_scope_enter(changes);
+        }
         *self = ParamScope::Nothing;
+        notify_subscribers();
     }
 
     /// Exit the current parameter scope.
+    ///
+    /// Blocks until every [`ScopeTicket`](crate::ScopeTicket) issued inside the scope is dropped.
+    /// The scope object keeps the parameters deleted in the scope as hidden,
+    /// so they are deleted again if it is re-entered.
     pub fn exit(&mut self) {
         THREAD_STORAGE.with(|ts| {
+            wait_for_tickets(ts.borrow().depth());
             let tree = ts.borrow_mut().exit();
             *self = ParamScope::Just(tree);
-        })
+        });
+        notify_subscribers();
+    }
+}
+
+/// A read-only stack of scope objects, created by [`ParamScope::with_fallback`].
+///
+/// Lookups try each scope in order and then the thread storage.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let mut defaults = ParamScope::default();
+/// defaults.put("model.layers", 12);
+/// defaults.put("model.dropout", 0.1);
+/// let mut overrides = ParamScope::default();
+/// overrides.put("model.layers", 24);
+///
+/// let view = overrides.with_fallback(&defaults);
+/// assert_eq!(24, view.get_or_else("model.layers", 0));
+/// assert_eq!(0.1, view.get_or_else("model.dropout", 0.0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScopeView<'a> {
+    layers: Vec<&'a ParamScope>,
+}
+
+impl<'a> ScopeView<'a> {
+    /// Add another scope to resolve misses against, after the existing ones.
+    pub fn with_fallback(mut self, other: &'a ParamScope) -> ScopeView<'a> {
+        self.layers.push(other);
+        self
+    }
+
+    /// Get a parameter with a given hash key.
+    pub fn get_with_hash(&self, key: u64) -> Value {
+        match self.layers.iter().find_map(|ps| ps.local(key)) {
+            Some(v) => v.clone(),
+            None => ParamScope::Nothing.get_with_hash(key),
+        }
+    }
+
+    /// Get a parameter with a given key.
+    pub fn get<K: XXHashable>(&self, key: K) -> Value {
+        self.get_with_hash(key.xxh())
+    }
+
+    /// Get a parameter or the default value if it doesn't exist.
+    pub fn get_or_else<K, V>(&self, key: K, default: V) -> V
+    where
+        K: XXHashable,
+        V: Into<Value> + TryFrom<Value> + for<'b> TryFrom<&'b Value, Error = ValueError>,
+    {
+        let hkey = key.xxh();
+        for ps in self.layers.iter() {
+            if let Some(Ok(v)) = ps.local(hkey).map(V::try_from) {
+                return v;
+            }
+        }
+        THREAD_STORAGE.with(|ts| ts.borrow().get_or_else(hkey, default))
     }
 }
 
@@ -117,28 +470,32 @@ pub trait ParamScopeOps<K, V> {
 
 impl<V> ParamScopeOps<u64, V> for ParamScope
 where
-    V: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value>,
+    V: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value, Error = ValueError>,
 {
     fn get_or_else(&self, key: u64, default: V) -> V {
         if let ParamScope::Just(changes) = self {
-            if let Some(val) = changes.get(&key) {
-                let r = val.value().clone().try_into();
-                if r.is_ok() {
-                    return r.ok().unwrap();
+            if let Some(val) = changes.get(&key).filter(|e| !defaults_only(&e.key)) {
+                if let Ok(v) = val.value().try_into() {
+                    return v;
                 }
             }
         }
-        THREAD_STORAGE.with(|ts| ts.borrow_mut().get_or_else(key, default))
+        THREAD_STORAGE.with(|ts| ts.borrow().get_or_else(key, default))
     }
 
     /// Put a parameter.
     fn put(&mut self, key: u64, val: V) {
         println!(
             "hyperparameter warning: put parameter with hashed key {}",
-            key
+            crate::xxh::describe_key(key)
         );
         if let ParamScope::Just(changes) = self {
-            if let std::collections::btree_map::Entry::Vacant(e) = changes.entry(key) {
+            let val: Value = val.into();
+            if matches!(val, Value::Empty) {
+                changes.remove(&key);
+                return;
+            }
+            if let std::collections::hash_map::Entry::Vacant(e) = changes.entry(key) {
                 e.insert(Entry::new("", val));
             } else {
                 changes.update(key, val);
@@ -150,7 +507,7 @@ where
 impl<K, V> ParamScopeOps<K, V> for ParamScope
 where
     K: Into<String> + Clone + XXHashable + Debug,
-    V: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value> + Clone,
+    V: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value, Error = ValueError> + Clone,
 {
     /// Get a parameter or the default value if it doesn't exist.
     fn get_or_else(&self, key: K, default: V) -> V {
@@ -160,22 +517,8 @@ where
 
     /// Put a parameter.
     fn put(&mut self, key: K, val: V) {
-        let hkey = key.xxh();
-        if let ParamScope::Just(changes) = self {
-            // if changes.contains_key(&hkey) {
-            //     changes.update(hkey, val);
-            // } else {
-            //     let key: String = key.into();
-            //     changes.insert(hkey, Entry::new(key, val));
-            // }
-            if let std::collections::btree_map::Entry::Vacant(e) = changes.entry(hkey) {
-                let key: String = key.into();
-                e.insert(Entry::new(key, val));
-            } else {
-                changes.update(hkey, val);
-            }
-        } else {
-            THREAD_STORAGE.with(|ts| ts.borrow_mut().put(key, val))
+        if let Err(e) = self.try_put(key, val) {
+            println!("hyperparameter warning: {}", e);
         }
     }
 }
@@ -184,28 +527,250 @@ pub fn frozen() {
     frozen_global_storage();
 }
 
+/// Like [`frozen`], but parameters can no longer be written afterwards.
+///
+/// Any later write, in any scope and on any thread, panics in debug builds;
+/// release builds print a warning and ignore it. The same goes for later
+/// calls to [`frozen`], [`freeze_prefix`] and [`unfreeze_prefix`]. Reads are unaffected. Use it
+/// once startup has set every parameter, to guarantee they stay fixed.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set server.workers = 8;
+///
+///     freeze_hard();
+/// }
+///
+/// // Panics in debug builds, ignored in release builds.
+/// let _ = std::panic::catch_unwind(|| {
+///     with_params! {
+///         set server.workers = 16;
+///     }
+/// });
+/// std::thread::spawn(|| assert_eq!(8, get_param!(server.workers, 1)))
+///     .join()
+///     .unwrap();
+/// ```
+pub fn freeze_hard() {
+    frozen_global_storage_hard();
+}
+
+/// Like [`frozen`], but only publish the parameters whose key starts with `prefix`.
+///
+/// The frozen values of other prefixes are left untouched, so components can
+/// refresh their own defaults without a process-wide freeze. As with
+/// `frozen`, only threads created afterwards see the new values.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set model.layers = 12;
+///     set data.path = "/tmp".to_string();
+///
+///     freeze_prefix("model.");
+/// }
+/// std::thread::spawn(|| {
+///     assert_eq!(12, get_param!(model.layers, 0));
+///     assert_eq!("", get_param!(data.path, "".to_string()));
+/// })
+/// .join()
+/// .unwrap();
+///
+/// unfreeze_prefix("model.");
+/// std::thread::spawn(|| assert_eq!(0, get_param!(model.layers, 0)))
+///     .join()
+///     .unwrap();
+/// ```
+pub fn freeze_prefix(prefix: &str) {
+    frozen_global_prefix(prefix);
+}
+
+/// Drop the frozen parameters whose key starts with `prefix`.
+///
+/// Library defaults registered for those keys are restored.
+pub fn unfreeze_prefix(prefix: &str) {
+    unfrozen_global_prefix(prefix);
+}
+
+/// Call `f` with a text parameter of the current thread, without copying it.
+///
+/// `f` gets `default` if the parameter is not set or is not text. Reading
+/// parameters from `f` is fine, but setting them panics since the thread
+/// storage is borrowed for the call.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set model.optimizer = "adam".to_string();
+///
+///     assert!(with_param_str("model.optimizer", "sgd", |s| s.starts_with("ad")));
+///     assert_eq!(3, with_param_str("model.scheduler", "cos", str::len));
+/// }
+/// ```
+pub fn with_param_str<K: XXHashable, R>(key: K, default: &str, f: impl FnOnce(&str) -> R) -> R {
+    THREAD_STORAGE.with(|ts| ts.borrow().with_str(key, default, f))
+}
+
 #[macro_export]
 macro_rules! get_param {
     ($name:expr, $default:expr) => {{
-        const CONST_KEY: &str = const_str::replace!(stringify!($name), ";", "");
-        const CONST_HASH: u64 = xxhash_rust::const_xxh64::xxh64(CONST_KEY.as_bytes(), 42);
-        THREAD_STORAGE.with(|ts| ts.borrow_mut().get_or_else(CONST_HASH, $default))
+        // The key constants live in their own block so that they do not
+        // shadow names used by the caller's `$default`.
+        let hash: u64 = {
+            const CONST_KEY: &str = $crate::const_str::replace!(stringify!($name), ";", "");
+            const CONST_HASH: u64 = $crate::__key_hash!(CONST_KEY);
+            $crate::__register_param!(CONST_KEY, $default, "");
+            CONST_HASH
+        };
+        $crate::THREAD_STORAGE
+            .with(|ts| $crate::GetOrElse::get_or_else(&*ts.borrow(), hash, $default))
         // ParamScope::default().get_or_else(CONST_HASH, $default)
     }};
 
     ($name:expr, $default:expr, $help: expr) => {{
-        const CONST_KEY: &str = const_str::replace!(stringify!($name), ";", "");
-        const CONST_HASH: u64 = xxhash_rust::const_xxh64::xxh64(CONST_KEY.as_bytes(), 42);
+        let hash: u64 = {
+            const CONST_KEY: &str = $crate::const_str::replace!(stringify!($name), ";", "");
+            const CONST_HASH: u64 = $crate::__key_hash!(CONST_KEY);
+            CONST_HASH
+        };
         // ParamScope::default().get_or_else(CONST_HASH, $default)
         {
             const CONST_HELP: &str = $help;
-            #[::linkme::distributed_slice(PARAMS)]
-            static help: (&str, &str) = (CONST_KEY, CONST_HELP);
+            #[$crate::linkme::distributed_slice($crate::PARAMS)]
+            #[linkme(crate = $crate::linkme)]
+            #[allow(non_upper_case_globals)]
+            static help: (&str, &str) = (
+                $crate::const_str::replace!(stringify!($name), ";", ""),
+                CONST_HELP,
+            );
+            $crate::__register_param!(
+                $crate::const_str::replace!(stringify!($name), ";", ""),
+                $default,
+                CONST_HELP
+            );
         }
-        THREAD_STORAGE.with(|ts| ts.borrow_mut().get_or_else(CONST_HASH, $default))
+        $crate::THREAD_STORAGE
+            .with(|ts| $crate::GetOrElse::get_or_else(&*ts.borrow(), hash, $default))
     }};
 }
 
+/// Get an optional parameter: `None` if it is not set.
+///
+/// As with [`Storage::get_opt`](crate::Storage::get_opt), a value that does
+/// not convert to the expected type panics in debug builds.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let unset: Option<i64> = get_param_opt!(train.seed);
+/// assert_eq!(None, unset);
+/// with_params! {
+///     set train.seed = 42;
+///
+///     assert_eq!(Some(42), get_param_opt!(train.seed));
+/// }
+/// ```
+#[macro_export]
+macro_rules! get_param_opt {
+    ($name:expr) => {{
+        const CONST_KEY: &str = $crate::const_str::replace!(stringify!($name), ";", "");
+        const CONST_HASH: u64 = $crate::__key_hash!(CONST_KEY);
+        $crate::__register_param!(CONST_KEY, None, "");
+        $crate::THREAD_STORAGE.with(|ts| ts.borrow().get_opt_with_hash(CONST_HASH))
+    }};
+}
+
+/// Get a parameter, falling back to its parent keys when it is not set.
+///
+/// `get_param_hier!(a.b.c, default)` reads `a.b.c`, then `a.b`, then `a`,
+/// and gives `default` if none is set, so a value set for a whole group is
+/// inherited by its members unless they override it. The hashes of the
+/// parent keys are computed at compile time.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set train.lr = 0.1;
+///     set train.lr.decoder = 0.01;
+///
+///     assert_eq!(0.1, get_param_hier!(train.lr.encoder, 0.0));
+///     assert_eq!(0.01, get_param_hier!(train.lr.decoder, 0.0));
+///     assert_eq!(0.0, get_param_hier!(eval.lr.encoder, 0.0));
+/// }
+/// ```
+#[macro_export]
+macro_rules! get_param_hier {
+    ($name:expr, $default:expr) => {{
+        let hashes = {
+            const CONST_KEY: &str = $crate::const_str::replace!(stringify!($name), ";", "");
+            const CONST_HASHES: [u64; $crate::key_depth(CONST_KEY)] =
+                $crate::key_hierarchy_hashes(CONST_KEY);
+            $crate::__register_param!(CONST_KEY, $default, "");
+            CONST_HASHES
+        };
+        $crate::THREAD_STORAGE
+            .with(|ts| ts.borrow().get_hier_with_hashes(&hashes))
+            .unwrap_or($default)
+    }};
+}
+
+/// Add a `get_param!` call site to the [`PARAM_REGISTRY`](crate::PARAM_REGISTRY).
+#[cfg(feature = "registry")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __register_param {
+    ($key:expr, $default:expr, $help:expr) => {
+        #[$crate::linkme::distributed_slice($crate::PARAM_REGISTRY)]
+        #[linkme(crate = $crate::linkme)]
+        static PARAM: $crate::ParamInfo = $crate::ParamInfo {
+            key: $key,
+            default: stringify!($default),
+            help: $help,
+            module: module_path!(),
+            file: file!(),
+            line: line!(),
+        };
+    };
+}
+
+#[cfg(not(feature = "registry"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __register_param {
+    ($key:expr, $default:expr, $help:expr) => {};
+}
+
+/// Add a `with_params!` `set` statement to the [`SET_PARAM_REGISTRY`](crate::SET_PARAM_REGISTRY).
+#[cfg(feature = "registry")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __register_set {
+    ($key:expr, $val:expr) => {
+        #[$crate::linkme::distributed_slice($crate::SET_PARAM_REGISTRY)]
+        #[linkme(crate = $crate::linkme)]
+        static PARAM: $crate::ParamInfo = $crate::ParamInfo {
+            key: $key,
+            default: stringify!($val),
+            help: "",
+            module: module_path!(),
+            file: file!(),
+            line: line!(),
+        };
+    };
+}
+
+#[cfg(not(feature = "registry"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __register_set {
+    ($key:expr, $val:expr) => {};
+}
+
 /// Define or use `hyperparameters` in a code block.
 ///
 /// Hyperparameters are named parameters whose values control the learning process of
@@ -232,6 +797,87 @@ macro_rules! get_param {
 ///     }
 /// }
 /// ```
+///
+/// # Loops
+///
+/// `@foreach` runs its body once per item, each iteration in its own child
+/// scope, so parameters set in one iteration never leak into the next.
+/// ```
+/// use hyperparameter::*;
+///
+/// let mut seen = vec![];
+/// with_params! {
+///     set grid.lr = 0.1;
+///
+///     @foreach (i, lr) in [0.1, 0.01].into_iter().enumerate() => {
+///         set grid.index = i as i64;
+///         set grid.lr = lr;
+///
+///         seen.push((get_param!(grid.index, -1), get_param!(grid.lr, 0.0)));
+///     }
+///
+///     assert_eq!(0.1, get_param!(grid.lr, 0.0));
+///     assert_eq!(-1, get_param!(grid.index, -1));
+/// }
+/// assert_eq!(vec![(0, 0.1), (1, 0.01)], seen);
+/// ```
+///
+/// # Required parameters
+///
+/// `@get` reads a parameter that has no sensible default, and panics with
+/// the key name if it is not set or does not convert to the expected type.
+/// The type can be given after the name, as in a `let` binding.
+/// Use [`ParamScope::get_required`] to handle the error instead.
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set data.path = "/data/train".to_string();
+///
+///     @get path: String = data.path;
+///     assert_eq!("/data/train", path);
+/// }
+///
+/// let err = ParamScope::Nothing.get_required::<i64>("data.path").unwrap_err();
+/// assert_eq!("required parameter `data.path` is not set", err.to_string());
+/// ```
+///
+/// # Deletion
+///
+/// `@del` hides a parameter until the scope exits: reads fall back to their
+/// defaults, and the outer value is restored afterwards.
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set train.dropout = 0.5;
+///
+///     with_params! {
+///         @del train.dropout;
+///
+///         assert_eq!(0.0, get_param!(train.dropout, 0.0));
+///     }
+///     assert_eq!(0.5, get_param!(train.dropout, 0.0));
+/// }
+/// ```
+///
+/// # Setting many parameters
+///
+/// `@set_all` puts every parameter of a value implementing [`IntoParams`],
+/// such as a map or a list of `(key, value)` pairs loaded at runtime.
+/// ```
+/// use std::collections::HashMap;
+/// use hyperparameter::*;
+///
+/// let overrides = HashMap::from([("train.lr", 0.01), ("train.dropout", 0.2)]);
+/// with_params! {
+///     @set_all overrides;
+///     set train.epochs = 10;
+///
+///     assert_eq!(0.01, get_param!(train.lr, 0.1));
+///     assert_eq!(0.2, get_param!(train.dropout, 0.0));
+/// }
+/// ```
 #[macro_export]
 macro_rules! with_params {
     (
@@ -239,12 +885,16 @@ macro_rules! with_params {
 
         $($body:tt)*
     ) =>{
-        let mut ps = ParamScope::default();
+        let mut ps = $crate::ParamScope::default();
         {
-            const CONST_KEY: &str = const_str::replace!(stringify!($($key).+), ";", "");
-            ps.put(CONST_KEY, $val);
+            let key: &str = {
+                const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+                $crate::__register_set!(CONST_KEY, $val);
+                CONST_KEY
+            };
+            $crate::ParamScopeOps::put(&mut ps, key, $val);
         }
-        with_params!(params ps; $($body)*)
+        $crate::with_params!(params ps; $($body)*)
     };
 
     (
@@ -254,110 +904,565 @@ macro_rules! with_params {
         $($body:tt)*
     ) => {
         {
-            const CONST_KEY: &str = const_str::replace!(stringify!($($key).+), ";", "");
-            $ps.put(CONST_KEY, $val);
+            let key: &str = {
+                const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+                $crate::__register_set!(CONST_KEY, $val);
+                CONST_KEY
+            };
+            $crate::ParamScopeOps::put(&mut $ps, key, $val);
+        }
+        $crate::with_params!(params $ps; $($body)*)
+    };
+
+    (
+        @setdefault $($key:ident).+ = $val:expr;
+
+        $($body:tt)*
+    ) =>{
+        let mut ps = $crate::ParamScope::default();
+        {
+            let key: &str = {
+                const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+                $crate::__register_set!(CONST_KEY, $val);
+                CONST_KEY
+            };
+            ps.put_default(key, $val);
+        }
+        $crate::with_params!(params ps; $($body)*)
+    };
+
+    (
+        params $ps:expr;
+        @setdefault $($key:ident).+ = $val:expr;
+
+        $($body:tt)*
+    ) => {
+        {
+            let key: &str = {
+                const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+                $crate::__register_set!(CONST_KEY, $val);
+                CONST_KEY
+            };
+            $ps.put_default(key, $val);
         }
-        with_params!(params $ps; $($body)*)
+        $crate::with_params!(params $ps; $($body)*)
     };
 
+    (
+        @del $($key:ident).+;
+
+        $($body:tt)*
+    ) =>{
+        let mut ps = $crate::ParamScope::default();
+        {
+            const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+            ps.hide(CONST_KEY);
+        }
+        $crate::with_params!(params ps; $($body)*)
+    };
+
+    (
+        params $ps:expr;
+        @del $($key:ident).+;
+
+        $($body:tt)*
+    ) => {
+        {
+            const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+            $ps.hide(CONST_KEY);
+        }
+        $crate::with_params!(params $ps; $($body)*)
+    };
+
+    (
+        @set_all $vals:expr;
+
+        $($body:tt)*
+    ) =>{
+        let mut ps = $crate::ParamScope::default();
+        $crate::IntoParams::put_into($vals, &mut ps);
+        $crate::with_params!(params ps; $($body)*)
+    };
+
+    (
+        params $ps:expr;
+        @set_all $vals:expr;
+
+        $($body:tt)*
+    ) => {
+        $crate::IntoParams::put_into($vals, &mut $ps);
+        $crate::with_params!(params $ps; $($body)*)
+    };
+
+    (
+        @foreach $item:pat in $iter:expr => { $($each:tt)* }
+
+        $($body:tt)*
+    ) => {{
+        for $item in $iter {
+            let _iteration = $crate::EnteredScope::mark();
+            let mut ps = $crate::ParamScope::default();
+            let _ = { $crate::with_params! { params ps; $($each)* } };
+        }
+        $crate::with_params! { $($body)* }
+    }};
+
+    (
+        params $ps:expr;
+        @foreach $item:pat in $iter:expr => { $($each:tt)* }
+
+        $($body:tt)*
+    ) => {{
+        $ps.enter();
+        let ret = {
+            for $item in $iter {
+                let _iteration = $crate::EnteredScope::mark();
+                let mut ps = $crate::ParamScope::default();
+                let _ = { $crate::with_params! { params ps; $($each)* } };
+            }
+            $crate::with_params_readonly! { $($body)* }
+        };
+        $ps.exit();
+        ret
+    }};
+
     (
         params $ps:expr;
         params $nested:expr;
 
-        $($body:tt)*
-    ) => {
-        $ps.enter();
-        let ret = with_params!(params $nested; $($body)*);
-        $ps.exit();
-        ret
+        $($body:tt)*
+    ) => {
+        $ps.enter();
+        let ret = $crate::with_params!(params $nested; $($body)*);
+        $ps.exit();
+        ret
+    };
+
+    (
+        get $name:ident = $($key:ident).+ or $default:expr;
+
+        $($body:tt)*
+    ) => {
+        let $name = $crate::get_param!($($key).+, $default);
+        $crate::with_params_readonly!($($body)*)
+    };
+
+    (
+        $(#[doc = $doc:expr])*
+        get $name:ident = $($key:ident).+ or $default:expr;
+
+        $($body:tt)*
+    ) => {
+        let $name = $crate::get_param!($($key).+, $default, $($doc)*);
+        $crate::with_params_readonly!($($body)*)
+    };
+
+    (
+        params $ps:expr;
+        get $name:ident = $($key:ident).+ or $default:expr;
+
+        $($body:tt)*
+    ) => {
+        $ps.enter();
+        let ret = {
+            let $name = $crate::get_param!($($key).+, $default);
+
+            $crate::with_params_readonly!($($body)*)
+        };
+        $ps.exit();
+        ret
+    };
+
+    (
+        @get $name:ident $(: $ty:ty)? = $($key:ident).+;
+
+        $($body:tt)*
+    ) => {
+        let $name $(: $ty)? = {
+            const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+            match $crate::ParamScope::Nothing.get_required(CONST_KEY) {
+                Ok(val) => val,
+                Err(e) => panic!("{}", e),
+            }
+        };
+        $crate::with_params_readonly!($($body)*)
+    };
+
+    (
+        params $ps:expr;
+        @get $name:ident $(: $ty:ty)? = $($key:ident).+;
+
+        $($body:tt)*
+    ) => {
+        $ps.enter();
+        let ret = {
+            let $name $(: $ty)? = {
+                const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+                match $crate::ParamScope::Nothing.get_required(CONST_KEY) {
+                    Ok(val) => val,
+                    Err(e) => panic!("{}", e),
+                }
+            };
+
+            $crate::with_params_readonly!($($body)*)
+        };
+        $ps.exit();
+        ret
+    };
+
+    (
+        params $ps:expr;
+
+        $($body:tt)*
+    ) => {{
+            $ps.enter();
+            let ret = {$($body)*};
+            $ps.exit();
+            ret
+    }};
+
+    ($($body:tt)*) => {{
+        let ret = {$($body)*};
+        ret
+    }};
+}
+
+#[macro_export]
+macro_rules! with_params_readonly {
+    (
+        get $name:ident = $($key:ident).+ or $default:expr;
+
+        $($body:tt)*
+    ) => {
+        let $name = $crate::get_param!($($key).+, $default);
+        $crate::with_params_readonly!($($body)*)
+    };
+
+    (
+        set $($key:ident).+ = $val:expr;
+
+        $($body:tt)*
+    ) =>{
+        let mut ps = $crate::ParamScope::default();
+        {
+            let key: &str = {
+                const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+                $crate::__register_set!(CONST_KEY, $val);
+                CONST_KEY
+            };
+            $crate::ParamScopeOps::put(&mut ps, key, $val);
+        }
+        $crate::with_params!(params ps; $($body)*)
+    };
+
+    (
+        @setdefault $($key:ident).+ = $val:expr;
+
+        $($body:tt)*
+    ) =>{
+        let mut ps = $crate::ParamScope::default();
+        {
+            let key: &str = {
+                const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+                $crate::__register_set!(CONST_KEY, $val);
+                CONST_KEY
+            };
+            ps.put_default(key, $val);
+        }
+        $crate::with_params!(params ps; $($body)*)
+    };
+
+    (
+        @del $($key:ident).+;
+
+        $($body:tt)*
+    ) =>{
+        let mut ps = $crate::ParamScope::default();
+        {
+            const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+            ps.hide(CONST_KEY);
+        }
+        $crate::with_params!(params ps; $($body)*)
+    };
+
+    (
+        @set_all $vals:expr;
+
+        $($body:tt)*
+    ) =>{
+        let mut ps = $crate::ParamScope::default();
+        $crate::IntoParams::put_into($vals, &mut ps);
+        $crate::with_params!(params ps; $($body)*)
+    };
+
+    (
+        @foreach $item:pat in $iter:expr => { $($each:tt)* }
+
+        $($body:tt)*
+    ) => {{
+        for $item in $iter {
+            let _iteration = $crate::EnteredScope::mark();
+            let mut ps = $crate::ParamScope::default();
+            let _ = { $crate::with_params! { params ps; $($each)* } };
+        }
+        $crate::with_params_readonly! { $($body)* }
+    }};
+
+    (
+        @get $name:ident $(: $ty:ty)? = $($key:ident).+;
+
+        $($body:tt)*
+    ) => {
+        let $name $(: $ty)? = {
+            const CONST_KEY: &str = $crate::const_str::replace!(stringify!($($key).+), ";", "");
+            match $crate::ParamScope::Nothing.get_required(CONST_KEY) {
+                Ok(val) => val,
+                Err(e) => panic!("{}", e),
+            }
+        };
+        $crate::with_params_readonly!($($body)*)
+    };
+
+    ($($body:tt)*) => {{
+            let ret = {$($body)*};
+            ret
+    }};
+}
+
+/// Declare typed accessors for a group of parameters.
+///
+/// Each parameter `name: type = default` becomes a function `name()`
+/// returning its current value, and a module `name` holding the parameter's
+/// `KEY`, its compile-time `HASH` and a `set(value)` function that writes into
+/// the innermost entered scope. Nested groups become nested modules.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// mod params {
+///     hyperparameter::declare_params! {
+///         train {
+///             /// learning rate
+///             lr: f64 = 0.1,
+///             epochs: i64 = 10,
+///             optim { kind: String = "adam".to_string() },
+///         }
+///     }
+/// }
+///
+/// assert_eq!(0.1, params::train::lr());
+/// assert_eq!("train.optim.kind", params::train::optim::kind::KEY);
+/// with_params! {
+///     set train.epochs = 20;
+///
+///     assert_eq!(20, params::train::epochs());
+///     params::train::lr::set(0.01);
+///     assert_eq!(0.01, get_param!(train.lr, 0.0));
+/// }
+/// assert_eq!(0.1, params::train::lr());
+/// ```
+#[macro_export]
+macro_rules! declare_params {
+    ($($body:tt)*) => {
+        $crate::__declare_params!([] $($body)*);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_params {
+    (@param [$($prefix:ident)*] [$($doc:expr),*] $name:ident : $ty:ty = $default:expr) => {
+        $(#[doc = $doc])*
+        #[allow(dead_code)]
+        pub fn $name() -> $ty {
+            let default: $ty = $default;
+            $crate::THREAD_STORAGE
+                .with(|ts| $crate::GetOrElse::get_or_else(&*ts.borrow(), $name::HASH, default))
+        }
+
+        #[allow(dead_code)]
+        pub mod $name {
+            pub const KEY: &str = concat!($(stringify!($prefix), ".",)* stringify!($name));
+            pub const HASH: u64 = $crate::__key_hash!(KEY);
+
+            /// Set the parameter in the innermost entered scope.
+            pub fn set(val: $ty) {
+                $crate::ParamScopeOps::put(&mut $crate::ParamScope::Nothing, KEY, val)
+            }
+        }
+    };
+
+    ([$($prefix:ident)*]) => {};
+
+    ([$($prefix:ident)*] , $($rest:tt)*) => {
+        $crate::__declare_params!([$($prefix)*] $($rest)*);
     };
 
-    (
-        get $name:ident = $($key:ident).+ or $default:expr;
+    ([$($prefix:ident)*] $group:ident { $($inner:tt)* } $($rest:tt)*) => {
+        pub mod $group {
+            $crate::__declare_params!([$($prefix)* $group] $($inner)*);
+        }
+        $crate::__declare_params!([$($prefix)*] $($rest)*);
+    };
 
-        $($body:tt)*
+    (
+        [$($prefix:ident)*]
+        $(#[doc = $doc:expr])*
+        $name:ident : $ty:ty = $default:expr, $($rest:tt)*
     ) => {
-        let $name = get_param!($($key).+, $default);
-        with_params_readonly!($($body)*)
+        $crate::__declare_params!(@param [$($prefix)*] [$($doc),*] $name: $ty = $default);
+        $crate::__declare_params!([$($prefix)*] $($rest)*);
     };
 
     (
+        [$($prefix:ident)*]
         $(#[doc = $doc:expr])*
-        get $name:ident = $($key:ident).+ or $default:expr;
-
-        $($body:tt)*
+        $name:ident : $ty:ty = $default:expr
     ) => {
-        let $name = get_param!($($key).+, $default, $($doc)*);
-        with_params_readonly!($($body)*)
+        $crate::__declare_params!(@param [$($prefix)*] [$($doc),*] $name: $ty = $default);
     };
+}
 
-    (
-        params $ps:expr;
-        get $name:ident = $($key:ident).+ or $default:expr;
+/// Types whose fields are read from parameters, usually declared with [`declare_params_struct!`].
+pub trait FromParamScope: Sized {
+    /// Read the fields from `scope`, falling back to the thread storage.
+    fn from_param_scope(scope: &ParamScope) -> Self;
 
-        $($body:tt)*
-    ) => {
-        $ps.enter();
-        let ret = {
-            let $name = get_param!($($key).+, $default);
+    /// Read the fields from the current thread scope.
+    fn from_scope() -> Self {
+        Self::from_param_scope(&ParamScope::Nothing)
+    }
+}
 
-            with_params_readonly!($($body)*)
-        };
-        $ps.exit();
-        ret
-    };
+/// Values that expand to several parameters, for `@set_all` in [`with_params!`].
+pub trait IntoParams {
+    /// The parameters as `(key, value)` pairs.
+    fn into_params(self) -> Vec<(String, Value)>;
 
-    (
-        params $ps:expr;
+    /// Put every parameter into `ps`.
+    fn put_into(self, ps: &mut ParamScope)
+    where
+        Self: Sized,
+    {
+        for (key, val) in self.into_params() {
+            if let Err(e) = ps.try_put(key, val) {
+                println!("hyperparameter warning: {}", e);
+            }
+        }
+    }
+}
 
-        $($body:tt)*
-    ) => {{
-            $ps.enter();
-            let ret = {$($body)*};
-            $ps.exit();
-            ret
-    }};
+impl<K: Into<String>, V: Into<Value>, S: BuildHasher> IntoParams for HashMap<K, V, S> {
+    fn into_params(self) -> Vec<(String, Value)> {
+        self.into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect()
+    }
+}
 
-    ($($body:tt)*) => {{
-        let ret = {$($body)*};
-        ret
-    }};
+impl<K: Into<String>, V: Into<Value>> IntoParams for BTreeMap<K, V> {
+    fn into_params(self) -> Vec<(String, Value)> {
+        self.into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect()
+    }
 }
 
+impl<K: Into<String>, V: Into<Value>> IntoParams for Vec<(K, V)> {
+    fn into_params(self) -> Vec<(String, Value)> {
+        self.into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect()
+    }
+}
+
+/// Declare a struct whose fields map to the parameters under a prefix.
+///
+/// Each field `name: type = default` is read from the key `prefix.name`,
+/// whose hash is computed at compile time. The struct implements
+/// [`FromParamScope`].
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// declare_params_struct! {
+///     #[derive(Debug, PartialEq)]
+///     pub struct TrainParams in train {
+///         pub lr: f64 = 0.1,
+///         pub batch_size: i64 = 32,
+///     }
+/// }
+///
+/// with_params! {
+///     set train.batch_size = 64;
+///
+///     let cfg = TrainParams::from_scope();
+///     assert_eq!(TrainParams { lr: 0.1, batch_size: 64 }, cfg);
+/// }
+/// ```
 #[macro_export]
-macro_rules! with_params_readonly {
+macro_rules! declare_params_struct {
     (
-        get $name:ident = $($key:ident).+ or $default:expr;
-
-        $($body:tt)*
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident in $($prefix:ident).+ { $($fields:tt)* }
     ) => {
-        let $name = get_param!($($key).+, $default);
-        with_params_readonly!($($body)*)
+        $crate::__declare_params_struct! {
+            [$($prefix)+]
+            $(#[$meta])*
+            $vis struct $name { $($fields)* }
+        }
     };
+}
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __declare_params_struct {
     (
-        set $($key:ident).+ = $val:expr;
+        $prefix:tt
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$fmeta:meta])*
+                $fvis:vis $field:ident : $ty:ty = $default:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$fmeta])*
+                $fvis $field: $ty,
+            )*
+        }
 
-        $($body:tt)*
-    ) =>{
-        let mut ps = ParamScope::default();
-        {
-            const CONST_KEY: &str = const_str::replace!(stringify!($($key).+), ";", "");
-            ps.put(CONST_KEY, $val);
+        impl $crate::FromParamScope for $name {
+            fn from_param_scope(scope: &$crate::ParamScope) -> Self {
+                $name {
+                    $(
+                        $field: {
+                            let default: $ty = $default;
+                            let hash: u64 = {
+                                const KEY: &str = $crate::__declare_params_struct!(@key $prefix $field);
+                                const HASH: u64 = $crate::__key_hash!(KEY);
+                                HASH
+                            };
+                            $crate::ParamScopeOps::get_or_else(scope, hash, default)
+                        },
+                    )*
+                }
+            }
         }
-        with_params!(params ps; $($body)*)
     };
 
-    ($($body:tt)*) => {{
-            let ret = {$($body)*};
-            ret
-    }};
+    (@key [$($prefix:ident)+] $field:ident) => {
+        concat!($(stringify!($prefix), ".",)+ stringify!($field))
+    };
 }
 
 #[cfg(test)]
 mod tests {
     use crate::get_param;
-    use crate::storage::{GetOrElse, THREAD_STORAGE};
+    use crate::storage::{GetOrElse, MissingParamError, THREAD_STORAGE};
     use crate::with_params;
 
     use super::{ParamScope, ParamScopeOps};
@@ -518,6 +1623,338 @@ mod tests {
             assert_eq!(3, a_b_c);
         }
     }
+
+    #[test]
+    fn test_param_scope_with_param_setdefault() {
+        with_params! {
+            @setdefault weak.a = 1;
+
+            assert_eq!(1, get_param!(weak.a, 0));
+        }
+
+        with_params! {
+            set weak.a = 2;
+
+            with_params! {
+                @setdefault weak.a = 1;
+                @setdefault weak.b = 3;
+
+                assert_eq!(2, get_param!(weak.a, 0));
+                assert_eq!(3, get_param!(weak.b, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_param_scope_with_param_required() {
+        with_params! {
+            set required.a = 1;
+            set required.b = "x".to_string();
+
+            @get a: i64 = required.a;
+            assert_eq!(1, a);
+
+            assert_eq!(
+                Err(MissingParamError::Missing {
+                    key: "required.c".to_string()
+                }),
+                ParamScope::Nothing.get_required::<i64>("required.c")
+            );
+            let err = ParamScope::Nothing
+                .get_required::<i64>("required.b")
+                .unwrap_err();
+            assert_eq!("required.b", err.key());
+            assert!(matches!(err, MissingParamError::Mismatch { .. }));
+
+            let mut ps = ParamScope::default();
+            ps.hide("required.a");
+            assert_eq!(
+                "required parameter `required.a` is not set",
+                ps.get_required::<i64>("required.a").unwrap_err().to_string()
+            );
+        }
+
+        let missing = std::panic::catch_unwind(|| {
+            with_params! {
+                @get a: i64 = required.a;
+                let _ = a;
+            }
+        });
+        let message = missing.unwrap_err();
+        assert_eq!(
+            Some(&"required parameter `required.a` is not set".to_string()),
+            message.downcast_ref::<String>()
+        );
+    }
+
+    #[test]
+    fn test_param_scope_get_opt() {
+        assert_eq!(None::<i64>, get_param_opt!(optional.a));
+        with_params! {
+            set optional.a = 1;
+            set optional.b = "x".to_string();
+
+            assert_eq!(Some(1), get_param_opt!(optional.a));
+            assert_eq!(Some("x".to_string()), get_param_opt!(optional.b));
+            let mismatch = std::panic::catch_unwind(|| {
+                let b: Option<i64> = get_param_opt!(optional.b);
+                b
+            });
+            assert_eq!(cfg!(debug_assertions), mismatch.is_err());
+
+            let mut ps = ParamScope::default();
+            ps.put("optional.c", 2.5);
+            ps.hide("optional.a");
+            assert_eq!(Some(2.5), ps.get_opt("optional.c"));
+            assert_eq!(None::<i64>, ps.get_opt("optional.a"));
+            assert_eq!(Some(1), ParamScope::Nothing.get_opt::<_, i64>("optional.a"));
+            let mismatch = std::panic::catch_unwind(|| ps.get_opt::<_, bool>("optional.c"));
+            assert_eq!(cfg!(debug_assertions), mismatch.is_err());
+
+            with_params! {
+                @del optional.a;
+
+                assert_eq!(None::<i64>, get_param_opt!(optional.a));
+            }
+        }
+    }
+
+    #[test]
+    fn test_param_scope_with_param_del() {
+        with_params! {
+            set hidden.a = 1;
+            set hidden.b = 2;
+
+            with_params! {
+                @del hidden.a;
+                set hidden.b = 3;
+
+                assert_eq!(0, get_param!(hidden.a, 0));
+                assert_eq!(3, get_param!(hidden.b, 0));
+                assert!(!ParamScope::Nothing.keys().contains(&"hidden.a".to_string()));
+
+                with_params! {
+                    set hidden.a = 4;
+
+                    assert_eq!(4, get_param!(hidden.a, 0));
+                }
+                assert_eq!(0, get_param!(hidden.a, 0));
+            }
+            assert_eq!(1, get_param!(hidden.a, 0));
+            assert_eq!(2, get_param!(hidden.b, 0));
+
+            with_params! {
+                get b = hidden.b or 0;
+                @del hidden.b;
+
+                assert_eq!(2, b);
+                assert_eq!(0, get_param!(hidden.b, 0));
+            }
+            assert_eq!(2, get_param!(hidden.b, 0));
+        }
+
+        let mut ps = ParamScope::default();
+        ps.hide("hidden.c");
+        assert_eq!(None, ps.try_get("hidden.c"));
+        assert!(!ps.keys().contains(&"hidden.c".to_string()));
+    }
+
+    #[test]
+    fn test_param_scope_del_nested() {
+        with_params! {
+            set hidden.nested.a = 1;
+
+            let mut ps = ParamScope::Nothing;
+            ps.hide("hidden.nested.a");
+            assert_eq!(1, get_param!(hidden.nested.a, 0));
+            ps.enter();
+            assert_eq!(0, get_param!(hidden.nested.a, 0));
+            ps.exit();
+            assert_eq!(1, get_param!(hidden.nested.a, 0));
+
+            let mut ps = ParamScope::default();
+            ps.put("hidden.nested.b", 2);
+            ps.enter();
+            ParamScope::Nothing.del("hidden.nested.a");
+            with_params! {
+                set hidden.nested.a = 3;
+
+                assert_eq!(3, get_param!(hidden.nested.a, 0));
+            }
+            assert_eq!(0, get_param!(hidden.nested.a, 0));
+            ps.exit();
+            assert_eq!(1, get_param!(hidden.nested.a, 0));
+
+            // the delete is kept by the scope object
+            ps.enter();
+            assert_eq!(0, get_param!(hidden.nested.a, 0));
+            assert_eq!(2, get_param!(hidden.nested.b, 0));
+            ps.exit();
+            assert_eq!(1, get_param!(hidden.nested.a, 0));
+        }
+    }
+
+    #[test]
+    fn test_param_scope_with_param_set_all() {
+        let map = std::collections::HashMap::from([("bulk.a", 1), ("bulk.b", 2)]);
+        with_params! {
+            set bulk.a = 0;
+            @set_all map;
+            @set_all vec![("bulk.c".to_string(), crate::Value::from("x"))];
+
+            assert_eq!(1, get_param!(bulk.a, 0));
+            assert_eq!(2, get_param!(bulk.b, 0));
+            assert_eq!("x", get_param!(bulk.c, String::new()));
+
+            with_params! {
+                get b = bulk.b or 0;
+                @set_all std::collections::BTreeMap::from([("bulk.b", b + 1)]);
+
+                assert_eq!(3, get_param!(bulk.b, 0));
+            }
+        }
+        assert_eq!(0, get_param!(bulk.a, 0));
+    }
+
+    #[test]
+    fn test_param_scope_with_param_foreach() {
+        let mut totals = vec![];
+        with_params! {
+            set shard.total = 0;
+
+            @foreach shard in 0..3 => {
+                @setdefault shard.total = 100;
+                get total = shard.total or -1;
+
+                with_params! {
+                    set shard.total = total + shard;
+
+                    totals.push(get_param!(shard.total, -1));
+                }
+                set_shard_total(shard);
+            }
+
+            get total = shard.total or -1;
+            assert_eq!(0, total);
+        }
+        assert_eq!(vec![0, 1, 2], totals);
+    }
+
+    #[test]
+    fn test_param_scope_with_param_foreach_continue() {
+        let depth = THREAD_STORAGE.with(|ts| ts.borrow().depth());
+        let mut seen = vec![];
+        with_params! {
+            set grid.lr = 0.1;
+
+            @foreach i in 0..4 => {
+                set grid.lr = 0.01;
+
+                if i % 2 == 0 {
+                    continue;
+                }
+                with_params! {
+                    set grid.index = i;
+
+                    if i == 3 {
+                        break;
+                    }
+                }
+                seen.push(i);
+            }
+
+            assert_eq!(0.1, get_param!(grid.lr, 0.0));
+            assert_eq!(-1, get_param!(grid.index, -1));
+        }
+        assert_eq!(vec![1], seen);
+        assert_eq!(0.0, get_param!(grid.lr, 0.0));
+        assert_eq!(depth, THREAD_STORAGE.with(|ts| ts.borrow().depth()));
+    }
+
+    fn set_shard_total(shard: i64) {
+        THREAD_STORAGE.with(|ts| ts.borrow_mut().put("shard.total", shard * 10));
+    }
+}
+
+#[cfg(test)]
+mod test_declare_params {
+    crate::declare_params! {
+        declared {
+            a: i64 = 1,
+            b { c: bool = false }
+        }
+    }
+
+    #[test]
+    fn test_declare_params() {
+        assert_eq!("declared.b.c", declared::b::c::KEY);
+        assert_eq!(crate::xxhash(b"declared.a"), declared::a::HASH);
+        assert_eq!(1, declared::a());
+        crate::with_params! {
+            set declared.a = 2;
+
+            assert_eq!(2, declared::a());
+            declared::b::c::set(true);
+            assert!(declared::b::c());
+        }
+        assert!(!declared::b::c());
+    }
+}
+
+#[cfg(test)]
+mod test_macro_hygiene {
+    // names that would be captured by an unqualified macro expansion
+    #[allow(dead_code)]
+    struct ParamScope;
+    #[allow(dead_code)]
+    static THREAD_STORAGE: () = ();
+
+    #[test]
+    fn test_with_params_does_not_capture_caller_names() {
+        crate::with_params! {
+            set a.b = 1;
+
+            crate::with_params! {
+                get a_b = a.b or 0;
+
+                assert_eq!(1, a_b);
+            }
+        }
+        assert_eq!(0, crate::get_param!(a.b, 0));
+    }
+
+    // items the macros declare next to the key of a parameter
+    const CONST_KEY: &str = "fallback";
+    const CONST_HASH: i64 = 7;
+    const KEY: &str = "field";
+    static PARAM: i64 = 3;
+
+    crate::declare_params_struct! {
+        struct Shadowed in hygiene {
+            key: String = KEY.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_macros_do_not_capture_key_constants() {
+        assert_eq!("fallback", crate::get_param!(a.b, CONST_KEY.to_string()));
+        assert_eq!(7, crate::get_param!(a.b, CONST_HASH, "help"));
+        assert_eq!(7, crate::get_param_hier!(a.b.c, CONST_HASH));
+        crate::with_params! {
+            set a.b = PARAM;
+            @setdefault a.c = CONST_HASH;
+
+            assert_eq!(3, crate::get_param!(a.b, 0));
+            assert_eq!(7, crate::get_param!(a.c, 0));
+            let shadowed: Shadowed = crate::FromParamScope::from_scope();
+            assert_eq!("field", shadowed.key);
+        }
+        crate::with_params_readonly! {
+            set a.d = CONST_KEY.to_string();
+
+            assert_eq!("fallback", crate::get_param!(a.d, String::new()));
+        }
+    }
 }
 
 // FILEPATH: /home/reiase/workspace/hyperparameter/core/src/api.rs
@@ -543,8 +1980,14 @@ mod test_param_scope {
         let ps: ParamScope = (&vec).into();
         match ps {
             ParamScope::Just(params) => {
-                assert_eq!(params.get(&"param1".xxh()).unwrap().value(), &Value::from("value1"));
-                assert_eq!(params.get(&"param2".xxh()).unwrap().value(), &Value::from("value2"));
+                assert_eq!(
+                    params.get(&"param1".xxh()).unwrap().value(),
+                    &Value::from("value1")
+                );
+                assert_eq!(
+                    params.get(&"param2".xxh()).unwrap().value(),
+                    &Value::from("value2")
+                );
             }
             _ => assert!(false, "ParamScope should be ParamScope::Just"),
         }
@@ -572,7 +2015,10 @@ mod test_param_scope {
         ps.add("param=value");
         match ps {
             ParamScope::Just(params) => {
-                assert_eq!(params.get(&"param".xxh()).unwrap().value(), &Value::from("value"));
+                assert_eq!(
+                    params.get(&"param".xxh()).unwrap().value(),
+                    &Value::from("value")
+                );
             }
             _ => assert!(false, "ParamScope should be ParamScope::Just"),
         }
@@ -586,6 +2032,127 @@ mod test_param_scope {
         assert_eq!(keys, vec!["param"]);
     }
 
+    #[test]
+    fn test_param_scope_namespace() {
+        with_params! {
+            set ns.a = 1;
+            set ns.b.c = 2;
+            set nsx.a = 3;
+
+            let mut ps = ParamScope::default();
+            ps.put("ns.d", 4);
+            ps.hide("ns.a");
+            let ns = ps.namespace("ns.");
+            assert_eq!(vec!["b.c", "d"], ns.keys().collect::<Vec<_>>());
+            assert_eq!(Value::Int(2), ns["b.c"]);
+
+            let all = ParamScope::Nothing.namespace("");
+            assert_eq!(Some(&Value::Int(3)), all.get("nsx.a"));
+            assert!(ParamScope::Nothing.namespace("missing").is_empty());
+        }
+    }
+
+    #[test]
+    fn test_param_scope_query() {
+        assert!(key_matches("model.*.dropout", "model.layer1.dropout"));
+        assert!(key_matches("model.layer*.d*t", "model.layer12.dropout"));
+        assert!(key_matches("model.**.dropout", "model.dropout"));
+        assert!(key_matches("**", "a.b.c"));
+        assert!(!key_matches("model.*.dropout", "model.a.b.dropout"));
+        assert!(!key_matches("model.*", "model"));
+        assert!(!key_matches("model.la*er", "model.layer.er"));
+        assert!(!key_matches("a*aa", "aa"));
+
+        with_params! {
+            set query.l1.dropout = 0.1;
+            set query.l2.dropout = 0.2;
+            set query.l2.width = 8;
+
+            let mut ps = ParamScope::default();
+            ps.put("query.l3.dropout", 0.3);
+            ps.hide("query.l1.dropout");
+            let dropouts = ps.query("query.*.dropout");
+            assert_eq!(
+                vec!["query.l2.dropout", "query.l3.dropout"],
+                dropouts.keys().collect::<Vec<_>>()
+            );
+            assert_eq!(3, ParamScope::Nothing.query("query.**").len());
+        }
+    }
+
+    #[test]
+    fn test_param_scope_capture() {
+        let mut ps = ParamScope::default();
+        ps.add("captured=value");
+        ps.enter();
+        let captured = ParamScope::capture();
+        ps.exit();
+
+        std::thread::spawn(move || {
+            let mut captured = captured;
+            captured.enter();
+            let value: String = ParamScope::Nothing.get("captured").try_into().unwrap();
+            assert_eq!(value, "value");
+            captured.exit();
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_param_scope_del_and_clear() {
+        let mut ps = ParamScope::default();
+        ps.put("empty.a", 1);
+        ps.put("empty.b", 2);
+        ps.put("empty.b", Value::Empty);
+        assert_eq!(Some(Value::Int(1)), ps.try_get("empty.a"));
+        assert_eq!(None, ps.try_get("empty.b"));
+        assert_eq!(vec!["empty.a".to_string()], ps.keys());
+
+        ps.del("empty.a");
+        assert_eq!(None, ps.try_get("empty.a"));
+
+        with_params! {
+            set empty.c = 3;
+
+            let mut current = ParamScope::Nothing;
+            with_params! {
+                params ParamScope::default();
+
+                current.clear();
+                assert_eq!(None, current.try_get("empty.c"));
+                assert!(current.keys().is_empty());
+            }
+            assert_eq!(Some(Value::Int(3)), current.try_get("empty.c"));
+        }
+    }
+
+    #[test]
+    fn test_param_scope_with_fallback() {
+        let mut defaults = ParamScope::default();
+        defaults.put("fallback.a", 1);
+        defaults.put("fallback.b", 2);
+        let mut overrides = ParamScope::default();
+        overrides.put("fallback.a", 10);
+        let mut extra = ParamScope::default();
+        extra.put("fallback.c", 3);
+
+        let view = overrides.with_fallback(&defaults).with_fallback(&extra);
+        assert_eq!(10, view.get_or_else("fallback.a", 0));
+        assert_eq!(2, view.get_or_else("fallback.b", 0));
+        assert_eq!(3, view.get_or_else("fallback.c", 0));
+        assert_eq!(Value::Int(2), view.get("fallback.b"));
+
+        with_params! {
+            set fallback.d = 4;
+            set fallback.b = 20;
+
+            assert_eq!(4, view.get_or_else("fallback.d", 0));
+            assert_eq!(2, view.get_or_else("fallback.b", 0));
+        }
+        assert_eq!(Value::Empty, view.get("fallback.d"));
+    }
+
     #[test]
     fn test_param_scope_enter_exit() {
         let mut ps = ParamScope::default();
@@ -593,7 +2160,10 @@ mod test_param_scope {
         ps.enter();
         match ps {
             ParamScope::Nothing => assert!(true),
-            _ => assert!(false, "ParamScope should be ParamScope::Nothing after enter"),
+            _ => assert!(
+                false,
+                "ParamScope should be ParamScope::Nothing after enter"
+            ),
         }
         ps.exit();
         match ps {
@@ -601,6 +2171,23 @@ mod test_param_scope {
             _ => assert!(false, "ParamScope should be ParamScope::Just after exit"),
         }
     }
+
+    crate::declare_params_struct! {
+        struct OptimParams in model.optim {
+            kind: String = "sgd".to_string(),
+            momentum: f64 = 0.9,
+        }
+    }
+
+    #[test]
+    fn test_from_param_scope() {
+        let mut ps = ParamScope::default();
+        ps.put("model.optim.kind", "adam".to_string());
+        let cfg = OptimParams::from_param_scope(&ps);
+        assert_eq!("adam", cfg.kind);
+        assert_eq!(0.9, cfg.momentum);
+        assert_eq!("sgd", OptimParams::from_scope().kind);
+    }
 }
 
-// END: test_code
\ No newline at end of file
+// END: test_code
@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::ThreadId;
+use std::time::SystemTime;
+
+use lazy_static::lazy_static;
+
+use crate::value::Value;
+
+/// A parameter write recorded by [`audit_log`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamMutation {
+    /// The key, or its hash in hex if its name is unknown.
+    pub key: String,
+    pub hkey: u64,
+    /// The value before the write, `Value::Empty` if the parameter was unset.
+    pub old: Value,
+    /// The value after the write, `Value::Empty` for a deletion.
+    pub new: Value,
+    pub time: SystemTime,
+    pub thread: ThreadId,
+    /// Nesting depth of the thread scope the write happened in.
+    pub depth: usize,
+}
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref MUTATIONS: Mutex<VecDeque<ParamMutation>> = Mutex::new(VecDeque::new());
+}
+
+/// Keep the last `capacity` parameter writes of all threads, `None` to stop recording.
+///
+/// Recording is off by default. Every `put` and `del` on a thread storage is
+/// recorded, including the writes of scopes being entered; updates of the
+/// frozen global storage are not.
+pub fn set_audit_log(capacity: Option<usize>) {
+    CAPACITY.store(capacity.unwrap_or(0), Ordering::Relaxed);
+    let mut mutations = MUTATIONS.lock().unwrap();
+    match capacity {
+        Some(capacity) => {
+            while mutations.len() > capacity {
+                mutations.pop_front();
+            }
+        }
+        None => mutations.clear(),
+    }
+}
+
+/// The parameter writes recorded since [`set_audit_log`] turned recording on, oldest first.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// set_audit_log(Some(1024));
+/// with_params! {
+///     set doc.audit.lr = 0.1;
+/// }
+/// let writes: Vec<ParamMutation> = audit_log()
+///     .into_iter()
+///     .filter(|m| m.key == "doc.audit.lr")
+///     .collect();
+/// assert_eq!(Value::Float(0.1), writes[0].new);
+/// assert_eq!(std::thread::current().id(), writes[0].thread);
+/// set_audit_log(None);
+/// ```
+pub fn audit_log() -> Vec<ParamMutation> {
+    MUTATIONS.lock().unwrap().iter().cloned().collect()
+}
+
+pub(crate) fn enabled() -> bool {
+    CAPACITY.load(Ordering::Relaxed) > 0
+}
+
+pub(crate) fn record_mutation(hkey: u64, key: String, old: &Value, new: &Value, depth: usize) {
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+    let mutation = ParamMutation {
+        key,
+        hkey,
+        old: old.clone(),
+        new: new.clone(),
+        time: crate::reads::now(),
+        thread: std::thread::current().id(),
+        depth,
+    };
+    let mut mutations = MUTATIONS.lock().unwrap();
+    while mutations.len() >= capacity {
+        mutations.pop_front();
+    }
+    mutations.push_back(mutation);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::THREAD_STORAGE;
+    use crate::xxh::XXHashable;
+    use crate::*;
+
+    fn writes(key: &str) -> Vec<ParamMutation> {
+        let thread = std::thread::current().id();
+        audit_log()
+            .into_iter()
+            .filter(|m| m.key == key && m.thread == thread)
+            .collect()
+    }
+
+    #[test]
+    fn test_audit_log() {
+        set_audit_log(Some(4096));
+        with_params! {
+            set audit.a = 1;
+            with_params! {
+                set audit.a = 2;
+            }
+            ParamScope::Nothing.del("audit.a");
+        }
+
+        let log = writes("audit.a");
+        let changes: Vec<(Value, Value, usize)> =
+            log.into_iter().map(|m| (m.old, m.new, m.depth)).collect();
+        assert_eq!(
+            vec![
+                (Value::Empty, Value::Int(1), 1),
+                (Value::Int(1), Value::Int(2), 2),
+                (Value::Int(1), Value::Empty, 1),
+            ],
+            changes
+        );
+
+        // written by hash only, as through the C API
+        let hkey = "audit.hash_only".xxh();
+        with_params! {
+            THREAD_STORAGE.with(|ts| ts.borrow_mut().try_put_with_hash(hkey, "".into(), Value::Int(1))).unwrap();
+        }
+        let log: Vec<ParamMutation> = audit_log().into_iter().filter(|m| m.hkey == hkey).collect();
+        assert_eq!(1, log.len());
+        assert_eq!(crate::xxh::describe_key(hkey), log[0].key);
+        assert!(!log[0].key.is_empty());
+
+        set_audit_log(Some(1));
+        assert!(audit_log().len() <= 1);
+        set_audit_log(None);
+        assert!(writes("audit.a").is_empty());
+    }
+}
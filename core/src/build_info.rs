@@ -0,0 +1,59 @@
+use crate::xxh::HASH_SEED;
+
+/// How this build of the crate was compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Version of the `hyperparameter` crate.
+    pub version: &'static str,
+    /// Enabled cargo features, in alphabetical order.
+    pub features: Vec<&'static str>,
+    /// Seed of the hash used for parameter keys, see [`xxhash`](crate::xxhash).
+    pub hash_seed: u64,
+}
+
+impl BuildInfo {
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+/// Describe how this build of the crate was compiled.
+///
+/// Embedding applications can check the feature set at startup instead of
+/// failing later on a missing feature.
+///
+/// ```
+/// let info = hyperparameter::build_info();
+/// assert_eq!(42, info.hash_seed);
+/// assert!(info.has_feature("json"));
+/// ```
+pub fn build_info() -> BuildInfo {
+    let features = [
+        ("chaos", cfg!(feature = "chaos")),
+        ("clap", cfg!(feature = "clap")),
+        ("debug-http", cfg!(feature = "debug-http")),
+        ("defaults", cfg!(feature = "defaults")),
+        ("hardware", cfg!(feature = "hardware")),
+        ("interning", cfg!(feature = "interning")),
+        ("json", cfg!(feature = "json")),
+        ("prometheus", cfg!(feature = "prometheus")),
+        ("provenance", cfg!(feature = "provenance")),
+        ("rayon", cfg!(feature = "rayon")),
+        ("registry", cfg!(feature = "registry")),
+        ("serde", cfg!(feature = "serde")),
+        ("toml", cfg!(feature = "toml")),
+        ("tracing", cfg!(feature = "tracing")),
+        ("tracking", cfg!(feature = "tracking")),
+        ("wasm", cfg!(feature = "wasm")),
+        ("yaml", cfg!(feature = "yaml")),
+    ];
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        features: features
+            .into_iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name)
+            .collect(),
+        hash_seed: HASH_SEED,
+    }
+}
@@ -6,6 +6,8 @@ pub use crate::storage::THREAD_STORAGE;
 pub use crate::value::Value;
 pub use crate::xxh::XXHashable;
 
+use crate::provenance::{with_default_source, Source};
+
 pub trait AsParamScope {
     fn param_scope(&self) -> ParamScope;
 }
@@ -18,17 +20,48 @@ impl AsParamScope for config::Config {
                 (None, config::ValueKind::Table(v)) => v.iter().for_each(|(k, v)| {
                     unpack(ps, Some(k.to_string()), v.clone());
                 }),
-                (Some(k), config::ValueKind::Boolean(v)) => ps.put(k, v),
-                (Some(k), config::ValueKind::I64(v)) => ps.put(k, v),
-                (Some(k), config::ValueKind::Float(v)) => ps.put(k, v),
-                (Some(k), config::ValueKind::String(v)) => ps.put(k, v),
                 (Some(prefix), config::ValueKind::Table(v)) => v.iter().for_each(|(k, v)| {
                     unpack(ps, Some(format!("{}.{}", prefix, k)), v.clone());
                 }),
-                _ => todo!(),
+                (Some(k), kind) => {
+                    if let Some(v) = convert(&k, kind) {
+                        ps.put(k, v)
+                    }
+                }
+                (None, _) => {}
+            };
+        }
+        /// Convert a value other than a table, `None` if it is nil or not representable.
+        fn convert(key: &str, kind: config::ValueKind) -> Option<Value> {
+            let skip = |what: &str| {
+                println!(
+                    "hyperparameter warning: skipped {} in parameter `{}`",
+                    what, key
+                );
+                None
             };
+            let int = |v: Result<i64, std::num::TryFromIntError>| match v {
+                Ok(v) => Some(Value::from(v)),
+                Err(_) => skip("an integer out of range"),
+            };
+            match kind {
+                config::ValueKind::Nil => None,
+                config::ValueKind::Boolean(v) => Some(v.into()),
+                config::ValueKind::I64(v) => Some(v.into()),
+                config::ValueKind::I128(v) => int(i64::try_from(v)),
+                config::ValueKind::U64(v) => int(i64::try_from(v)),
+                config::ValueKind::U128(v) => int(i64::try_from(v)),
+                config::ValueKind::Float(v) => Some(v.into()),
+                config::ValueKind::String(v) => Some(v.into()),
+                config::ValueKind::Array(v) => Some(Value::List(
+                    v.into_iter().filter_map(|v| convert(key, v.kind)).collect(),
+                )),
+                config::ValueKind::Table(_) => skip("a table inside a list"),
+            }
         }
-        unpack(&mut ps, None, self.cache.clone());
+        with_default_source(Source::File("config".into()), || {
+            unpack(&mut ps, None, self.cache.clone())
+        });
 
         ps
     }
@@ -79,4 +112,43 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_param_scope_from_config_with_nil() -> Result<(), ConfigError> {
+        let ps = config::Config::builder()
+            .set_default("nil.a", 1)?
+            .set_default("nil.b", config::Value::new(None, config::ValueKind::Nil))?
+            .set_default(
+                "nil.list",
+                vec![
+                    config::Value::new(None, config::ValueKind::Nil),
+                    config::Value::from(2),
+                ],
+            )?
+            .build()?
+            .param_scope();
+        assert_eq!(Value::Int(1), ps.get("nil.a"));
+        assert_eq!(None, ps.try_get("nil.b"));
+        assert_eq!(Value::from(vec![2]), ps.get("nil.list"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_param_scope_from_config_with_table_array() -> Result<(), ConfigError> {
+        let layer = config::Config::builder()
+            .set_default("width", 64)?
+            .build()?
+            .cache
+            .into_table()?;
+        let ps = config::Config::builder()
+            .set_default("tables.a", 1)?
+            .set_default("tables.layers", vec![config::Value::from(layer), 3.into()])?
+            .set_default("tables.big", u64::MAX)?
+            .build()?
+            .param_scope();
+        assert_eq!(Value::Int(1), ps.get("tables.a"));
+        assert_eq!(Value::from(vec![3]), ps.get("tables.layers"));
+        assert_eq!(None, ps.try_get("tables.big"));
+        Ok(())
+    }
 }
@@ -0,0 +1,143 @@
+//! Randomized perturbation of numeric parameters for resilience testing.
+//!
+//! Once enabled, every integer or float read through the thread storage
+//! whose key starts with the configured prefix is scaled by a bounded random
+//! factor. The factor only depends on the seed and the key, so repeated runs
+//! with the same seed see the same values.
+//!
+//! ```
+//! use hyperparameter::*;
+//!
+//! chaos::enable("doc.chaos.", 0.1, 42);
+//! with_params! {
+//!     set doc.chaos.lr = 1.0;
+//!
+//!     let lr: f64 = get_param!(doc.chaos.lr, 0.0);
+//!     assert!((0.9..=1.1).contains(&lr));
+//! }
+//! chaos::disable();
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::storage::Entry;
+use crate::value::Value;
+
+/// A perturbation applied to a parameter read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Perturbation {
+    pub key: String,
+    pub original: Value,
+    pub perturbed: Value,
+}
+
+struct Chaos {
+    prefix: String,
+    magnitude: f64,
+    seed: u64,
+    audit: BTreeMap<String, Perturbation>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref CHAOS: Mutex<Option<Chaos>> = Mutex::new(None);
+}
+
+/// Perturb numeric reads of keys starting with `prefix` by up to `±magnitude`
+/// (e.g. `0.1` for ±10%), deterministically for a given `seed`.
+///
+/// Enabling again replaces the previous settings and clears the audit.
+pub fn enable<P: Into<String>>(prefix: P, magnitude: f64, seed: u64) {
+    *CHAOS.lock().unwrap() = Some(Chaos {
+        prefix: prefix.into(),
+        magnitude: magnitude.abs(),
+        seed,
+        audit: BTreeMap::new(),
+    });
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Stop perturbing reads. The audit is kept until the next `enable`.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Release);
+}
+
+/// Perturbations applied since the last `enable`, one per key, ordered by key.
+pub fn audit() -> Vec<Perturbation> {
+    CHAOS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|c| c.audit.values().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub(crate) fn perturb(hkey: u64, e: &Entry) -> Option<Value> {
+    if !ENABLED.load(Ordering::Acquire) {
+        return None;
+    }
+    let mut chaos = CHAOS.lock().unwrap();
+    let chaos = chaos.as_mut()?;
+    if !e.key.starts_with(&chaos.prefix) {
+        return None;
+    }
+    let h = xxhash_rust::const_xxh64::xxh64(&hkey.to_le_bytes(), chaos.seed);
+    let unit = (h >> 11) as f64 / (1u64 << 53) as f64;
+    let factor = 1.0 + chaos.magnitude * (2.0 * unit - 1.0);
+    let perturbed = match e.value() {
+        Value::Int(v) => Value::Int((*v as f64 * factor).round() as i64),
+        Value::Float(v) => Value::Float(v * factor),
+        _ => return None,
+    };
+    chaos
+        .audit
+        .entry(e.key.to_string())
+        .or_insert_with(|| Perturbation {
+            key: e.key.to_string(),
+            original: e.clone_value(),
+            perturbed: perturbed.clone(),
+        });
+    Some(perturbed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_chaos_perturbation() {
+        chaos::enable("chaos.test.", 0.1, 7);
+        let read = || {
+            with_params! {
+                set chaos.test.lr = 1.0;
+                set chaos.test.steps = 1000;
+                set other.lr = 1.0;
+
+                (
+                    get_param!(chaos.test.lr, 0.0),
+                    get_param!(chaos.test.steps, 0),
+                    get_param!(other.lr, 0.0),
+                )
+            }
+        };
+        let (lr, steps, other) = read();
+        assert!((0.9..=1.1).contains(&lr));
+        assert!((900..=1100).contains(&steps));
+        assert_eq!(1.0, other);
+        assert_eq!((lr, steps, other), read());
+
+        let audit = chaos::audit();
+        assert_eq!(2, audit.len());
+        assert_eq!("chaos.test.lr", audit[0].key);
+        assert_eq!(Value::Float(1.0), audit[0].original);
+        assert_eq!(Value::Float(lr), audit[0].perturbed);
+
+        chaos::disable();
+        assert_eq!(1.0, read().0);
+    }
+}
@@ -1,6 +1,11 @@
 use std::collections::{HashMap, HashSet};
 
 use clap::builder::Styles;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use crate::api::{ParamScope, ParamScopeOps};
+use crate::provenance::{with_default_source, Source};
+use crate::value::{Value, ValueError, ValueKind};
 
 #[::linkme::distributed_slice]
 pub static PARAMS: [(&str, &str)];
@@ -49,3 +54,166 @@ pub fn generate_params_help() -> String {
         .collect::<Vec<String>>()
         .join("\n\n")
 }
+
+/// Id of the argument built by [`define_arg`].
+pub const DEFINE_ARG: &str = "define";
+
+/// The `-D key=value` / `--define key=value` argument, to add to a clap [`Command`].
+///
+/// Definitions are checked while the command line is parsed, so a value
+/// that does not fit the type of the key's registered default is reported
+/// by clap like any other invalid argument. Read the definitions back with
+/// [`ParamScope::from_clap_matches`].
+pub fn define_arg() -> Arg {
+    Arg::new(DEFINE_ARG)
+        .short('D')
+        .long("define")
+        .value_name("KEY=VALUE")
+        .help("Set a hyperparameter, may be repeated")
+        .action(ArgAction::Append)
+        .value_parser(|expr: &str| parse_define(expr).map(|_| expr.to_string()))
+}
+
+/// Parse a `key=value` definition.
+///
+/// Keys with a registered default get a value of the same type, other keys
+/// keep the text, as [`ParamScope::add`] does.
+fn parse_define(expr: &str) -> Result<(String, Value), String> {
+    let (key, raw) = expr
+        .split_once('=')
+        .ok_or_else(|| format!("`{}` is not in the key=value format", expr))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("`{}` has an empty key", expr));
+    }
+    let text = Value::Text(raw.into());
+    let kind = registered_default(key).map(|v| v.kind());
+    let val = match kind {
+        None | Some(ValueKind::Text) => Ok(text),
+        Some(ValueKind::Int) => i64::try_from(&text).map(Value::Int),
+        Some(ValueKind::Float) => f64::try_from(&text).map(Value::Float),
+        Some(ValueKind::Boolean) => bool::try_from(&text).map(Value::Boolean),
+        Some(_) => Err(ValueError::WrongType {
+            expected: "a parameter value",
+            actual: ValueKind::Text,
+        }),
+    };
+    val.map(|val| (key.to_string(), val)).map_err(|_| {
+        format!(
+            "`{}` expects a {:?} value, got `{}`",
+            key,
+            kind.unwrap_or(ValueKind::Text),
+            raw
+        )
+    })
+}
+
+#[cfg(feature = "defaults")]
+fn registered_default(key: &str) -> Option<Value> {
+    use crate::xxh::XXHashable;
+    crate::defaults::library_default(key.xxh())
+}
+
+#[cfg(not(feature = "defaults"))]
+fn registered_default(_key: &str) -> Option<Value> {
+    None
+}
+
+impl ParamScope {
+    /// Build a scope from the [`define_arg`] definitions of parsed command line arguments.
+    ///
+    /// ```
+    /// use clap::Command;
+    /// use hyperparameter::*;
+    ///
+    /// register_library_defaults!(module_path!(), |ps| {
+    ///     ps.put("doc.cli.epochs", 10);
+    /// });
+    ///
+    /// let app = Command::new("train").arg(define_arg());
+    /// let matches = app
+    ///     .clone()
+    ///     .try_get_matches_from(["train", "-D", "doc.cli.epochs=20", "-D", "doc.cli.name=run"])
+    ///     .unwrap();
+    /// let ps = ParamScope::from_clap_matches(&matches).unwrap();
+    /// assert_eq!(Value::Int(20), ps.get("doc.cli.epochs"));
+    /// assert_eq!(Value::Text("run".into()), ps.get("doc.cli.name"));
+    ///
+    /// // values are checked against the type of the registered default
+    /// assert!(app
+    ///     .try_get_matches_from(["train", "-D", "doc.cli.epochs=many"])
+    ///     .is_err());
+    /// ```
+    pub fn from_clap_matches(matches: &ArgMatches) -> Result<ParamScope, String> {
+        let defines = matches
+            .try_get_many::<String>(DEFINE_ARG)
+            .map_err(|e| e.to_string())?;
+        let mut ps = ParamScope::default();
+        for expr in defines.into_iter().flatten() {
+            let (key, val) = parse_define(expr)?;
+            with_default_source(Source::Cli, || ps.put(key, val));
+        }
+        Ok(ps)
+    }
+}
+
+/// The [`define_arg`] argument, to flatten into a clap derive parser.
+///
+/// The definitions are checked by clap while parsing, see [`define_arg`].
+///
+/// ```
+/// use clap::Parser;
+/// use hyperparameter::*;
+///
+/// #[derive(Parser)]
+/// struct Cli {
+///     #[command(flatten)]
+///     params: ParamsArgs,
+/// }
+///
+/// let cli = Cli::parse_from(["app", "-D", "doc.cli.flatten=1"]);
+/// with_params! {
+///     params cli.params.param_scope();
+///
+///     assert_eq!(1, get_param!(doc.cli.flatten, 0));
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParamsArgs {
+    pub define: Vec<String>,
+}
+
+impl crate::cfg::AsParamScope for ParamsArgs {
+    fn param_scope(&self) -> ParamScope {
+        let mut ps = ParamScope::default();
+        for (key, val) in self.define.iter().filter_map(|e| parse_define(e).ok()) {
+            with_default_source(Source::Cli, || ps.put(key, val));
+        }
+        ps
+    }
+}
+
+impl clap::FromArgMatches for ParamsArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let mut args = ParamsArgs::default();
+        args.update_from_arg_matches(matches)?;
+        Ok(args)
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        if let Some(defines) = matches.get_many::<String>(DEFINE_ARG) {
+            self.define = defines.cloned().collect();
+        }
+        Ok(())
+    }
+}
+
+impl clap::Args for ParamsArgs {
+    fn augment_args(cmd: Command) -> Command {
+        cmd.arg(define_arg())
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        cmd.arg(define_arg())
+    }
+}
@@ -0,0 +1,269 @@
+//! A small HTTP server to inspect and update the frozen parameters, behind
+//! the `debug-http` feature.
+//!
+//! `GET /params` lists the frozen global parameters as a JSON object, and
+//! `PUT /params` merges a JSON object into them, nested objects giving
+//! dotted keys:
+//!
+//! ```no_run
+//! use hyperparameter::*;
+//!
+//! let server = serve_debug_http("127.0.0.1:8787").unwrap();
+//! // curl localhost:8787/params
+//! // curl -X PUT localhost:8787/params -d '{"train": {"lr": 0.01}}'
+//! server.stop();
+//! ```
+//!
+//! With the `prometheus` feature, `GET /metrics` serves
+//! [`prometheus_metrics`](crate::prometheus_metrics).
+//!
+//! Like [`ConfigWatcher`](crate::ConfigWatcher), updates reach the threads
+//! created afterwards, not the ones already running. Requests are served one
+//! at a time; the server is meant for debugging, not for production traffic.
+//!
+//! There is no authentication: anyone who can connect can change the
+//! parameters, so bind it to a loopback address such as `127.0.0.1`.
+//! Requests that stall for longer than five seconds are dropped, and bodies
+//! over 1 MiB are refused with `413 Payload Too Large`.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::api::ParamScope;
+use crate::storage::{frozen_params, update_global_params};
+use crate::value::Value;
+
+/// Serve `GET /params` and `PUT /params` on `addr` from a background thread.
+///
+/// `addr` should be a loopback address, since requests are not authenticated.
+pub fn serve_debug_http<A: ToSocketAddrs>(addr: A) -> io::Result<DebugServer> {
+    let listener = TcpListener::bind(addr)?;
+    let addr = listener.local_addr()?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let stop = stop.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Err(e) = stream.and_then(handle) {
+                    println!("hyperparameter warning: debug http: {}", e);
+                }
+            }
+        })
+    };
+    Ok(DebugServer {
+        addr,
+        stop,
+        thread: Some(thread),
+    })
+}
+
+/// The background thread started by [`serve_debug_http`].
+pub struct DebugServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DebugServer {
+    /// The address the server listens on, with the actual port if port 0 was asked for.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stop serving and wait for the background thread to exit.
+    pub fn stop(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            // Wake up the blocking accept.
+            let _ = TcpStream::connect(self.addr);
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for DebugServer {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+/// How long a client may stall while sending a request or reading the response.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// The largest request body accepted.
+const MAX_BODY: usize = 1 << 20;
+/// The largest request line and headers accepted.
+const MAX_HEAD: u64 = 64 << 10;
+
+fn handle(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new((&stream).take(MAX_HEAD));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut request = line.split_whitespace();
+    let method = request.next().unwrap_or("").to_string();
+    let path = request.next().unwrap_or("");
+    let path = path.split('?').next().unwrap_or("").to_string();
+
+    let mut len = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                len = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let (status, content_type, body) = if len > MAX_BODY {
+        (
+            "413 Payload Too Large",
+            JSON,
+            error_json("request body too large"),
+        )
+    } else {
+        let mut body = vec![0; len];
+        reader.get_mut().set_limit(len as u64);
+        reader.read_exact(&mut body)?;
+        respond(&method, &path, &body)
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+const JSON: &str = "application/json";
+
+fn respond(method: &str, path: &str, body: &[u8]) -> (&'static str, &'static str, String) {
+    #[cfg(feature = "prometheus")]
+    if path == "/metrics" && method == "GET" {
+        return (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            crate::prometheus::prometheus_metrics(),
+        );
+    }
+    if path != "/params" {
+        return ("404 Not Found", JSON, error_json("not found"));
+    }
+    let (status, body) = match method {
+        "GET" => ("200 OK", params_json()),
+        "PUT" => match update(body) {
+            Ok(()) => ("200 OK", params_json()),
+            Err(e) => ("400 Bad Request", error_json(&e)),
+        },
+        _ => ("405 Method Not Allowed", error_json("use GET or PUT")),
+    };
+    (status, JSON, body)
+}
+
+/// Merge the parameters of a JSON document into the frozen global storage.
+fn update(body: &[u8]) -> Result<(), String> {
+    let text = std::str::from_utf8(body).map_err(|e| e.to_string())?;
+    if let ParamScope::Just(changes) = ParamScope::from_json_str(text)? {
+        update_global_params(|global| {
+            for (hkey, e) in changes.iter() {
+                global.insert(*hkey, e.shallow());
+            }
+        });
+    }
+    Ok(())
+}
+
+fn params_json() -> String {
+    let params = frozen_params();
+    let values: BTreeMap<&str, &Value> = params
+        .values()
+        .filter(|e| !matches!(e.value(), Value::Empty | Value::UserDefined(..)))
+        .map(|e| (&*e.key, e.value()))
+        .collect();
+    serde_json::to_string(&values).unwrap_or_default()
+}
+
+fn error_json(message: &str) -> String {
+    let error: BTreeMap<&str, &str> = [("error", message)].into();
+    serde_json::to_string(&error).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+
+    use super::serve_debug_http;
+    use crate::*;
+
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_debug_http() {
+        // the server updates the frozen global storage
+        if !crate::storage::in_child_process(concat!(module_path!(), "::test_debug_http")) {
+            return;
+        }
+        let server = serve_debug_http("127.0.0.1:0").unwrap();
+        let addr = server.local_addr();
+
+        let put = request(addr, "PUT", "/params", r#"{"debughttp": {"lr": 0.5}}"#);
+        assert!(put.starts_with("HTTP/1.1 200 OK"), "{}", put);
+        assert!(put.contains(r#""debughttp.lr":0.5"#), "{}", put);
+        let get = request(addr, "GET", "/params", "");
+        assert!(get.contains(r#""debughttp.lr":0.5"#), "{}", get);
+        let lr = std::thread::spawn(|| get_param!(debughttp.lr, 0.0))
+            .join()
+            .unwrap();
+        assert_eq!(0.5, lr);
+
+        assert!(request(addr, "PUT", "/params", "[1]").starts_with("HTTP/1.1 400"));
+        assert!(request(addr, "POST", "/params", "").starts_with("HTTP/1.1 405"));
+        assert!(request(addr, "GET", "/other", "").starts_with("HTTP/1.1 404"));
+        #[cfg(feature = "prometheus")]
+        assert!(request(addr, "GET", "/metrics", "")
+            .contains("hyperparameter_value{key=\"debughttp.lr\"} 0.5"));
+        // refused before reading the body
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "PUT /params HTTP/1.1\r\nContent-Length: 2000000\r\n\r\n"
+        )
+        .unwrap();
+        let mut put = String::new();
+        stream.read_to_string(&mut put).unwrap();
+        assert!(put.starts_with("HTTP/1.1 413"), "{}", put);
+        server.stop();
+    }
+}
@@ -0,0 +1,133 @@
+use std::cell::Cell;
+
+use lazy_static::lazy_static;
+
+use crate::api::ParamScope;
+use crate::storage::Params;
+
+type Init = fn(&mut ParamScope);
+
+/// Default initializers registered with [`register_library_defaults!`],
+/// as `(module_path, init)` pairs.
+#[::linkme::distributed_slice]
+pub static LIBRARY_DEFAULTS: [(&str, Init)];
+
+lazy_static! {
+    static ref DEFAULTS: Params = collect_apart(&LIBRARY_DEFAULTS);
+}
+
+thread_local! {
+    static COLLECTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the current thread is running the registered initializers.
+pub(crate) fn collecting() -> bool {
+    COLLECTING.with(|c| c.get())
+}
+
+/// The defaults layer built from all registered library defaults.
+///
+/// It is built once, when the first storage is created, and seeds the global
+/// storage that every thread storage starts from.
+pub fn library_defaults() -> Params {
+    DEFAULTS.clone()
+}
+
+/// The registered library default or hardware fact for a hashed key, if any.
+#[cfg(feature = "clap")]
+pub(crate) fn library_default(hkey: u64) -> Option<crate::value::Value> {
+    let e = DEFAULTS.get(&hkey);
+    #[cfg(feature = "hardware")]
+    let e = e.or_else(|| crate::hardware::hardware_fact(hkey));
+    e.map(|e| e.clone_value())
+}
+
+/// Run the initializers on a thread of their own.
+///
+/// The defaults are built while the first storage is being created, so an
+/// initializer that reads a parameter with `get_param!` would re-enter that
+/// storage. On the collecting thread it gets an empty storage instead.
+fn collect_apart(inits: &'static [(&'static str, Init)]) -> Params {
+    std::thread::spawn(move || {
+        COLLECTING.with(|c| c.set(true));
+        collect(inits)
+    })
+    .join()
+    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+}
+
+fn collect(inits: &[(&str, Init)]) -> Params {
+    let mut inits: Vec<_> = inits.iter().collect();
+    inits.sort_by_key(|(module, _)| *module);
+    #[cfg(not(feature = "hardware"))]
+    let mut ps = ParamScope::default();
+    // the hardware facts are visible to the initializers, and kept in their own layer
+    #[cfg(feature = "hardware")]
+    let mut ps = ParamScope::Just(crate::hardware::hardware_facts());
+    for (_, init) in inits {
+        init(&mut ps);
+    }
+    let mut params = match ps {
+        ParamScope::Just(params) => params,
+        ParamScope::Nothing => Params::default(),
+    };
+    #[cfg(feature = "hardware")]
+    params.retain(|_, e| !e.key.starts_with(crate::hardware::HW_PREFIX));
+    params.values_mut().for_each(|e| e.default = true);
+    params
+}
+
+/// Contribute default parameter values from a library.
+///
+/// Each registration runs exactly once, when the first storage is created,
+/// regardless of which library is initialized first. Registrations are
+/// applied in module path order, so if two libraries set the same key the
+/// one with the later module path wins. Values set by the application,
+/// including `frozen()` ones, always take precedence.
+///
+/// Initializers run before any storage exists: `get_param!` in an
+/// initializer only sees its default, read the other defaults through `ps`.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// register_library_defaults!(module_path!(), |ps| {
+///     ps.put("doc.defaults.batch_size", 32);
+///     ps.put("doc.defaults.workers", get_param!(doc.defaults.cores, 4));
+/// });
+///
+/// assert_eq!(32, get_param!(doc.defaults.batch_size, 0));
+/// assert_eq!(4, get_param!(doc.defaults.workers, 0));
+/// ```
+#[macro_export]
+macro_rules! register_library_defaults {
+    ($module:expr, $init:expr) => {
+        const _: () = {
+            #[$crate::linkme::distributed_slice($crate::LIBRARY_DEFAULTS)]
+            #[linkme(crate = $crate::linkme)]
+            static DEFAULTS: (&str, fn(&mut $crate::ParamScope)) = ($module, $init);
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_collect_library_defaults() {
+        let params = super::collect(&[
+            ("lib_b", |ps| ps.put("defaults.test.a", 2)),
+            ("lib_a", |ps| {
+                ps.put("defaults.test.a", 1);
+                ps.put("defaults.test.b", 1);
+            }),
+        ]);
+        assert!(params.values().all(|e| e.is_default()));
+        let mut ps = ParamScope::Just(params);
+        assert_eq!(2, ps.get_or_else("defaults.test.a", 0));
+        assert_eq!(1, ps.get_or_else("defaults.test.b", 0));
+        ps.put("defaults.test.b", 3);
+        assert_eq!(3, ps.get_or_else("defaults.test.b", 0));
+    }
+}
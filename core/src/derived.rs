@@ -0,0 +1,180 @@
+//! Values derived from parameters, recomputed only when their inputs change.
+//!
+//! ```
+//! use hyperparameter::*;
+//!
+//! let schedule = derived::cached(&["train.lr", "train.steps"], || {
+//!     let lr: f64 = get_param!(train.lr, 0.1);
+//!     let steps: i64 = get_param!(train.steps, 4);
+//!     (0..steps).map(|i| lr / (i + 1) as f64).collect::<Vec<_>>()
+//! });
+//!
+//! assert_eq!(4, schedule.get().len());
+//! with_params! {
+//!     set train.steps = 2;
+//!
+//!     assert_eq!(vec![0.1, 0.05], *schedule.get());
+//! }
+//! assert_eq!(4, schedule.get().len());
+//! ```
+
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex};
+
+use crate::api::ParamScope;
+use crate::value::Value;
+use crate::xxh::XXHashable;
+
+/// Number of dependency values remembered by default, see [`Derived::capacity`].
+pub const DEFAULT_CAPACITY: usize = 4;
+
+/// A value computed from parameters and memoized by the values of its dependencies.
+///
+/// Created with [`cached`].
+pub struct Derived<T> {
+    deps: Vec<u64>,
+    compute: Box<dyn Fn() -> T + Send + Sync>,
+    capacity: usize,
+    /// Most recently used last.
+    cache: Mutex<Vec<(Vec<Value>, Arc<T>)>>,
+}
+
+impl<T> Debug for Derived<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Derived")
+            .field("deps", &self.deps)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// Memoize `compute`, which reads the parameters named in `deps`.
+///
+/// The value is recomputed whenever one of the dependencies resolves to a
+/// different value in the current scope, so no explicit invalidation is
+/// needed. Parameters read by `compute` but missing from `deps` do not
+/// invalidate the cache.
+pub fn cached<T, F>(deps: &[&str], compute: F) -> Derived<T>
+where
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    Derived {
+        deps: deps.iter().map(|k| k.xxh()).collect(),
+        compute: Box::new(compute),
+        capacity: DEFAULT_CAPACITY,
+        cache: Mutex::new(Vec::new()),
+    }
+}
+
+impl<T> Derived<T> {
+    /// Remember the results for up to `capacity` distinct sets of dependency values.
+    ///
+    /// Scopes that alternate between a few configurations, such as train
+    /// and eval, then do not evict each other.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Get the value for the current scope, computing it on a miss.
+    ///
+    /// The cache is not locked while computing, so threads missing at the
+    /// same time each compute the value and the first one to finish is kept.
+    pub fn get(&self) -> Arc<T> {
+        let inputs: Vec<Value> = self
+            .deps
+            .iter()
+            .map(|hkey| ParamScope::Nothing.get_with_hash(*hkey))
+            .collect();
+        if let Some(value) = self.lookup(&inputs) {
+            return value;
+        }
+        let value = Arc::new((self.compute)());
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(pos) = cache.iter().position(|(vals, _)| *vals == inputs) {
+            return cache[pos].1.clone();
+        }
+        if cache.len() >= self.capacity {
+            cache.remove(0);
+        }
+        cache.push((inputs, value.clone()));
+        value
+    }
+
+    /// The memoized value for `inputs`, marked as most recently used.
+    fn lookup(&self, inputs: &[Value]) -> Option<Arc<T>> {
+        let mut cache = self.cache.lock().unwrap();
+        let pos = cache.iter().position(|(vals, _)| *vals == inputs)?;
+        let hit = cache.remove(pos);
+        let value = hit.1.clone();
+        cache.push(hit);
+        Some(value)
+    }
+
+    /// Drop every memoized value.
+    pub fn invalidate(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+
+    use crate::*;
+
+    #[test]
+    fn test_cached_recomputes_on_change() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let doubled = {
+            let calls = calls.clone();
+            derived::cached(&["derived.x"], move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                get_param!(derived.x, 1) * 2
+            })
+            .capacity(2)
+        };
+
+        assert_eq!(2, *doubled.get());
+        assert_eq!(2, *doubled.get());
+        with_params! {
+            set derived.x = 5;
+
+            assert_eq!(10, *doubled.get());
+        }
+        assert_eq!(2, *doubled.get());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+
+        doubled.invalidate();
+        assert_eq!(2, *doubled.get());
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_cached_computes_without_lock() {
+        // both threads must be computing at once to get past the barrier
+        let barrier = Arc::new(Barrier::new(2));
+        let slow = Arc::new({
+            let barrier = barrier.clone();
+            derived::cached(&["derived.slow"], move || {
+                barrier.wait();
+                get_param!(derived.slow, 0)
+            })
+        });
+        let threads: Vec<_> = (1..=2)
+            .map(|i| {
+                let slow = slow.clone();
+                std::thread::spawn(move || {
+                    with_params! {
+                        set derived.slow = i;
+
+                        *slow.get()
+                    }
+                })
+            })
+            .collect();
+        let values: Vec<i64> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert_eq!(vec![1, 2], values);
+    }
+}
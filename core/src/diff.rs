@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+
+use crate::api::ParamScope;
+use crate::snapshot::ParamSnapshot;
+use crate::storage::{Params, THREAD_STORAGE};
+use crate::tags::has_any_tag;
+use crate::value::Value;
+use crate::xxh::XXHashable;
+
+/// A key added, removed or changed between two scopes, see [`ParamScope::diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamChange {
+    pub key: String,
+    /// The value before, `Value::Empty` if the key was added.
+    pub before: Value,
+    /// The value after, `Value::Empty` if the key was removed.
+    pub after: Value,
+}
+
+impl ParamChange {
+    pub fn is_added(&self) -> bool {
+        matches!(self.before, Value::Empty)
+    }
+
+    pub fn is_removed(&self) -> bool {
+        matches!(self.after, Value::Empty)
+    }
+}
+
+/// Compare two sets of parameters by key, the result sorted by key.
+fn diff(before: &BTreeMap<String, Value>, after: &BTreeMap<String, Value>) -> Vec<ParamChange> {
+    let mut changes: Vec<ParamChange> = Vec::new();
+    for (key, old) in before.iter() {
+        match after.get(key) {
+            Some(new) if new.same(old) => {}
+            new => changes.push(ParamChange {
+                key: key.clone(),
+                before: old.clone(),
+                after: new.cloned().unwrap_or(Value::Empty),
+            }),
+        }
+    }
+    for (key, new) in after.iter() {
+        if !before.contains_key(key) {
+            changes.push(ParamChange {
+                key: key.clone(),
+                before: Value::Empty,
+                after: new.clone(),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    changes
+}
+
+/// Keep the changes of keys carrying any of `tags`.
+fn tagged(changes: Vec<ParamChange>, tags: &[&str]) -> Vec<ParamChange> {
+    let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+    changes
+        .into_iter()
+        .filter(|c| has_any_tag(c.key.as_str().xxh(), &tags))
+        .collect()
+}
+
+impl ParamScope {
+    /// The keys added, removed or changed from this scope to `other`, sorted by key.
+    ///
+    /// Scope objects are compared by the parameters they hold, and
+    /// `ParamScope::Nothing` by the parameters visible on the current thread.
+    /// Compare the result of [`ParamScope::capture`] to a scope to print the
+    /// effective overrides of an experiment:
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// with_params! {
+    ///     set train.lr = 0.1;
+    ///     set train.epochs = 10;
+    ///
+    ///     let base = ParamScope::capture();
+    ///     with_params! {
+    ///         set train.lr = 0.01;
+    ///         set train.warmup = 100;
+    ///
+    ///         for change in base.diff(&ParamScope::capture()) {
+    ///             println!("{}: {:?} -> {:?}", change.key, change.before, change.after);
+    ///         }
+    ///         let changes = base.diff(&ParamScope::Nothing);
+    ///         let keys: Vec<&str> = changes.iter().map(|c| c.key.as_str()).collect();
+    ///         assert_eq!(vec!["train.lr", "train.warmup"], keys);
+    ///         assert!(changes[1].is_added());
+    ///     }
+    /// }
+    /// ```
+    pub fn diff(&self, other: &ParamScope) -> Vec<ParamChange> {
+        diff(&self.diffed_params(), &other.diffed_params())
+    }
+
+    /// Like [`ParamScope::diff`], but only for keys carrying any of `tags`,
+    /// see [`tag_key`](crate::tag_key).
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// tag_key("doc.diff.lr", &["tunable"]);
+    /// let mut before = ParamScope::default();
+    /// before.put("doc.diff.lr", 0.1);
+    /// before.put("doc.diff.workers", 4);
+    /// let mut after = before.clone();
+    /// after.put("doc.diff.lr", 0.01);
+    /// after.put("doc.diff.workers", 8);
+    ///
+    /// let changes = before.diff_tagged(&after, &["tunable"]);
+    /// assert_eq!(1, changes.len());
+    /// assert_eq!("doc.diff.lr", changes[0].key);
+    /// ```
+    pub fn diff_tagged(&self, other: &ParamScope, tags: &[&str]) -> Vec<ParamChange> {
+        tagged(self.diff(other), tags)
+    }
+
+    fn diffed_params(&self) -> BTreeMap<String, Value> {
+        let params: Params = match self {
+            ParamScope::Just(params) => params.clone(),
+            ParamScope::Nothing => THREAD_STORAGE.with(|ts| ts.borrow().capture()),
+        };
+        params
+            .values()
+            .filter(|e| !e.key.is_empty() && !matches!(e.value(), Value::Empty))
+            .map(|e| (e.key.to_string(), e.clone_value()))
+            .collect()
+    }
+}
+
+impl ParamSnapshot {
+    /// The keys added, removed or changed from this snapshot to `other`, sorted by key.
+    pub fn diff(&self, other: &ParamSnapshot) -> Vec<ParamChange> {
+        diff(&self.params, &other.params)
+    }
+
+    /// Like [`ParamSnapshot::diff`], but only for keys carrying any of `tags`.
+    pub fn diff_tagged(&self, other: &ParamSnapshot, tags: &[&str]) -> Vec<ParamChange> {
+        tagged(self.diff(other), tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_diff() {
+        let mut before = ParamScope::default();
+        before.put("diff.kept", 1);
+        before.put("diff.changed", 0.1);
+        before.put("diff.removed", true);
+        let mut after = ParamScope::default();
+        after.put("diff.kept", 1);
+        after.put("diff.changed", 0.2);
+        after.put("diff.added", "x".to_string());
+
+        let changes = before.diff(&after);
+        assert_eq!(
+            vec![
+                ParamChange {
+                    key: "diff.added".to_string(),
+                    before: Value::Empty,
+                    after: Value::from("x"),
+                },
+                ParamChange {
+                    key: "diff.changed".to_string(),
+                    before: Value::Float(0.1),
+                    after: Value::Float(0.2),
+                },
+                ParamChange {
+                    key: "diff.removed".to_string(),
+                    before: Value::Boolean(true),
+                    after: Value::Empty,
+                },
+            ],
+            changes
+        );
+        assert!(changes[0].is_added());
+        assert!(changes[2].is_removed());
+        assert!(after.diff(&after).is_empty());
+
+        assert_eq!(changes, before.snapshot().diff(&after.snapshot()));
+    }
+
+    #[test]
+    fn test_diff_tagged() {
+        tag_key("diff.tagged.lr", &["tunable"]);
+        tag_key("diff.tagged.host", &["infra"]);
+        let mut before = ParamScope::default();
+        before.put("diff.tagged.lr", 0.1);
+        before.put("diff.tagged.host", "a".to_string());
+        let mut after = ParamScope::default();
+        after.put("diff.tagged.lr", 0.2);
+        after.put("diff.tagged.host", "b".to_string());
+        after.put("diff.tagged.seed", 1);
+
+        let keys = |changes: Vec<ParamChange>| -> Vec<String> {
+            changes.into_iter().map(|c| c.key).collect()
+        };
+        assert_eq!(
+            vec!["diff.tagged.lr"],
+            keys(before.diff_tagged(&after, &["tunable"]))
+        );
+        assert_eq!(
+            vec!["diff.tagged.host", "diff.tagged.lr"],
+            keys(before.diff_tagged(&after, &["tunable", "infra"]))
+        );
+        assert!(before.diff_tagged(&after, &[]).is_empty());
+        assert_eq!(
+            before.diff_tagged(&after, &["infra"]),
+            before.snapshot().diff_tagged(&after.snapshot(), &["infra"])
+        );
+    }
+}
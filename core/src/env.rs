@@ -0,0 +1,484 @@
+use std::env;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::api::{ParamScope, ParamScopeOps};
+use crate::cfg::AsParamScope;
+use crate::provenance::{with_default_source, Source};
+use crate::value::Value;
+
+/// A parameter scope that re-reads environment variables each time it is entered.
+///
+/// Each bound variable is copied into its parameter key on `enter`, on top of
+/// the wrapped scope. Variables that are unset at that moment leave the
+/// wrapped scope's value in place.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let mut scope = EnvRefreshedScope::new(ParamScope::default())
+///     .bind("DOC_BATCH_SIZE", "train.batch_size");
+///
+/// std::env::set_var("DOC_BATCH_SIZE", "32");
+/// with_params! {
+///     params scope;
+///
+///     assert_eq!(32, get_param!(train.batch_size, 0));
+/// }
+///
+/// std::env::set_var("DOC_BATCH_SIZE", "64");
+/// with_params! {
+///     params scope;
+///
+///     assert_eq!(64, get_param!(train.batch_size, 0));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EnvRefreshedScope {
+    base: ParamScope,
+    active: ParamScope,
+    vars: Vec<(String, String)>,
+}
+
+impl EnvRefreshedScope {
+    pub fn new(base: ParamScope) -> Self {
+        EnvRefreshedScope {
+            base,
+            active: ParamScope::Nothing,
+            vars: Vec::new(),
+        }
+    }
+
+    /// Read environment variable `var` into parameter `key` on every enter.
+    pub fn bind<V: Into<String>, K: Into<String>>(mut self, var: V, key: K) -> Self {
+        self.vars.push((var.into(), key.into()));
+        self
+    }
+
+    /// Build the scope that `enter` would apply right now.
+    pub fn refreshed(&self) -> ParamScope {
+        let mut ps = self.base.clone();
+        for (var, key) in self.vars.iter() {
+            if let Ok(val) = env::var(var) {
+                ps.put(key.clone(), Value::from(val));
+            }
+        }
+        ps
+    }
+
+    /// Re-read the bound environment variables and enter the resulting scope.
+    pub fn enter(&mut self) {
+        self.active = self.refreshed();
+        self.active.enter();
+    }
+
+    /// Exit the scope entered by the last `enter`.
+    pub fn exit(&mut self) {
+        self.active.exit();
+    }
+}
+
+/// Parameters read from the environment variables starting with a prefix.
+///
+/// `HP_TRAIN__LR=0.01` becomes `train.lr = 0.01` for the prefix `HP_`: the
+/// prefix is stripped, `__` separates key segments and names are
+/// lowercased. Values that parse as an integer, a float or `true`/`false`
+/// are stored as such, anything else as text. The `{prefix}PARAMS` and
+/// `{prefix}PARAMS_FILE` variables used by [`ParamScope::to_env_vars`] are
+/// skipped.
+///
+/// Enter the scope inside a scope built from config files to let the
+/// environment override them:
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let mut file = ParamScope::default();
+/// file.put("train.lr", 0.1);
+/// file.put("train.epochs", 10);
+///
+/// std::env::set_var("DOC_HP_TRAIN__LR", "0.01");
+/// let mut env = EnvSource::new("DOC_HP_").param_scope();
+/// with_params! {
+///     params file;
+///
+///     with_params! {
+///         params env;
+///
+///         assert_eq!(0.01, get_param!(train.lr, 0.0));
+///         assert_eq!(10, get_param!(train.epochs, 0));
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EnvSource {
+    prefix: String,
+}
+
+impl EnvSource {
+    pub fn new<P: Into<String>>(prefix: P) -> Self {
+        EnvSource {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+impl AsParamScope for EnvSource {
+    fn param_scope(&self) -> ParamScope {
+        let reserved = [
+            format!("{}PARAMS", self.prefix),
+            format!("{}PARAMS_FILE", self.prefix),
+        ];
+        let mut ps = ParamScope::default();
+        for (var, raw) in env::vars_os() {
+            // variables that are not UTF-8 can not hold parameters
+            let (var, raw) = match (var.into_string(), raw.into_string()) {
+                (Ok(var), Ok(raw)) => (var, raw),
+                _ => continue,
+            };
+            let name = match var.strip_prefix(&self.prefix) {
+                Some(name) if !name.is_empty() && !reserved.contains(&var) => name,
+                _ => continue,
+            };
+            let key = name.replace("__", ".").to_lowercase();
+            with_default_source(Source::Env(var.as_str().into()), || ps.put(key, infer(raw)));
+        }
+        ps
+    }
+}
+
+fn infer(raw: String) -> Value {
+    if let Ok(v) = raw.parse::<i64>() {
+        Value::Int(v)
+    } else if let Ok(v) = raw.parse::<f64>() {
+        Value::Float(v)
+    } else if let Ok(v) = raw.parse::<bool>() {
+        Value::Boolean(v)
+    } else {
+        Value::Text(raw.into())
+    }
+}
+
+/// Largest encoded scope passed inline by [`ParamScope::to_env_vars`].
+///
+/// Bigger scopes are spilled to a temporary file, since environment blocks
+/// are limited in size on most platforms.
+pub const ENV_INLINE_LIMIT: usize = 32 * 1024;
+
+static SPILLED: AtomicUsize = AtomicUsize::new(0);
+
+impl ParamScope {
+    /// Read the parameters set by environment variables starting with `prefix`.
+    ///
+    /// See [`EnvSource`] for how variables map to keys and values.
+    pub fn from_env(prefix: &str) -> ParamScope {
+        EnvSource::new(prefix).param_scope()
+    }
+
+    /// Encode the parameters of this scope as environment variables for a child process.
+    ///
+    /// The parameters go into `{prefix}PARAMS`, or, past [`ENV_INLINE_LIMIT`],
+    /// into a temporary file named by `{prefix}PARAMS_FILE`. The file is only
+    /// readable by the current user and is removed by the first
+    /// [`ParamScope::from_env_vars`] that reads it, so pass it to a single
+    /// child process. Use
+    /// `ParamScope::capture()` to pass every parameter visible on the current
+    /// thread. User-defined values can not cross processes and are skipped.
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// with_params! {
+    ///     set train.lr = 0.1;
+    ///     set train.name = "run\n1".to_string();
+    ///
+    ///     for (var, val) in ParamScope::capture().to_env_vars("DOC_HP_").unwrap() {
+    ///         std::env::set_var(var, val); // or Command::envs for a child process
+    ///     }
+    /// }
+    ///
+    /// let mut ps = ParamScope::from_env_vars("DOC_HP_").unwrap();
+    /// with_params! {
+    ///     params ps;
+    ///
+    ///     assert_eq!(0.1, get_param!(train.lr, 0.0));
+    ///     assert_eq!("run\n1", get_param!(train.name, "".to_string()));
+    /// }
+    /// ```
+    pub fn to_env_vars(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        let mut lines: Vec<String> = Vec::new();
+        if let ParamScope::Just(params) = self {
+            for e in params.values() {
+                if let Some(encoded) = encode(e.value()) {
+                    lines.push(format!("{}={}", escape(&e.key), encoded));
+                }
+            }
+        }
+        lines.sort();
+        let encoded = lines.join("\n");
+        if encoded.len() <= ENV_INLINE_LIMIT {
+            return Ok(vec![(format!("{}PARAMS", prefix), encoded)]);
+        }
+        let path = env::temp_dir().join(format!(
+            "hyperparameter-{}-{}.env",
+            std::process::id(),
+            SPILLED.fetch_add(1, Ordering::Relaxed)
+        ));
+        spill(&path, &encoded).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(vec![(
+            format!("{}PARAMS_FILE", prefix),
+            path.display().to_string(),
+        )])
+    }
+
+    /// Decode the parameters passed by a parent process with [`ParamScope::to_env_vars`].
+    ///
+    /// Returns an empty scope if neither variable is set. A spilled file is
+    /// removed once read.
+    pub fn from_env_vars(prefix: &str) -> Result<ParamScope, String> {
+        let var = format!("{}PARAMS", prefix);
+        let (encoded, source) = match env::var(&var) {
+            Ok(encoded) => (encoded, Source::Env(var.into())),
+            Err(_) => match env::var(format!("{}PARAMS_FILE", prefix)) {
+                Ok(path) => {
+                    let encoded =
+                        std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+                    let _ = std::fs::remove_file(&path);
+                    (encoded, Source::File(path.into()))
+                }
+                Err(_) => (String::new(), Source::Code),
+            },
+        };
+        let mut ps = ParamScope::default();
+        for line in encoded.lines().filter(|l| !l.is_empty()) {
+            let invalid = || format!("invalid parameter in environment: {}", line);
+            let (key, encoded) = line.split_once('=').ok_or_else(invalid)?;
+            let val = decode(encoded).ok_or_else(invalid)?;
+            with_default_source(source.clone(), || ps.put(unescape(key), val));
+        }
+        Ok(ps)
+    }
+}
+
+/// Write a spilled scope to a new file only the current user can read.
+fn spill(path: &std::path::Path, encoded: &str) -> io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(encoded.as_bytes())
+}
+
+/// Encode a value as `kind:raw`, None for values that can not be passed on.
+///
+/// List items are encoded in turn and joined with `,`, escaping `\` and
+/// `,` so that nested lists split unambiguously.
+pub(crate) fn encode(val: &Value) -> Option<String> {
+    match val {
+        Value::Int(v) => Some(format!("i:{}", v)),
+        Value::Float(v) => Some(format!("f:{:?}", v)),
+        Value::Boolean(v) => Some(format!("b:{}", v)),
+        Value::Text(v) => Some(format!("s:{}", escape(v))),
+        Value::List(vals) => {
+            let items: Option<Vec<String>> = vals
+                .iter()
+                .map(|v| encode(v).map(|item| item.replace('\\', "\\\\").replace(',', "\\c")))
+                .collect();
+            Some(format!("l:{}", items?.join(",")))
+        }
+        Value::Empty | Value::UserDefined(..) => None,
+    }
+}
+
+pub(crate) fn decode(encoded: &str) -> Option<Value> {
+    let (kind, raw) = encoded.split_once(':')?;
+    let val = match kind {
+        "i" => Value::Int(raw.parse().ok()?),
+        "f" => Value::Float(raw.parse().ok()?),
+        "b" => Value::Boolean(raw.parse().ok()?),
+        "s" => Value::Text(unescape(raw).into()),
+        "l" => Value::List(
+            split_items(raw)
+                .iter()
+                .map(|item| decode(item))
+                .collect::<Option<_>>()?,
+        ),
+        _ => return None,
+    };
+    Some(val)
+}
+
+/// Split the items of an encoded list, undoing the escaping done by [`encode`].
+fn split_items(raw: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    if raw.is_empty() {
+        return items;
+    }
+    let mut item = String::new();
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ',' => items.push(std::mem::take(&mut item)),
+            '\\' => match chars.next() {
+                Some('c') => item.push(','),
+                Some(c) => item.push(c),
+                None => item.push('\\'),
+            },
+            c => item.push(c),
+        }
+    }
+    items.push(item);
+    items
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\0', "\\0")
+        .replace('=', "\\e")
+}
+
+pub(crate) fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some('e') => out.push('='),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_env_refreshed_scope() {
+        let mut base = ParamScope::default();
+        base.put("env.a", 1);
+        base.put("env.b", 2);
+        let mut scope = EnvRefreshedScope::new(base).bind("HP_TEST_ENV_REFRESH_A", "env.a");
+
+        std::env::remove_var("HP_TEST_ENV_REFRESH_A");
+        with_params! {
+            params scope;
+
+            assert_eq!(1, get_param!(env.a, 0));
+            assert_eq!(2, get_param!(env.b, 0));
+        }
+
+        std::env::set_var("HP_TEST_ENV_REFRESH_A", "10");
+        with_params! {
+            params scope;
+
+            assert_eq!(10, get_param!(env.a, 0));
+            assert_eq!(2, get_param!(env.b, 0));
+        }
+
+        std::env::set_var("HP_TEST_ENV_REFRESH_A", "20");
+        with_params! {
+            params scope;
+
+            assert_eq!(20, get_param!(env.a, 0));
+        }
+        assert_eq!(0, get_param!(env.a, 0));
+    }
+
+    #[test]
+    fn test_env_vars_spill_to_file() {
+        let mut ps = ParamScope::default();
+        ps.put("env.snapshot.big", "x=\\".repeat(super::ENV_INLINE_LIMIT));
+        ps.put("env.snapshot.flag", true);
+        let vars = ps.to_env_vars("HP_TEST_SPILL_").unwrap();
+        assert_eq!("HP_TEST_SPILL_PARAMS_FILE", vars[0].0);
+        std::env::set_var(&vars[0].0, &vars[0].1);
+
+        let decoded = ParamScope::from_env_vars("HP_TEST_SPILL_").unwrap();
+        assert_eq!(ps.get("env.snapshot.big"), decoded.get("env.snapshot.big"));
+        assert_eq!(Value::Boolean(true), decoded.get("env.snapshot.flag"));
+        assert!(!std::path::Path::new(&vars[0].1).exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_env_vars_spill_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut ps = ParamScope::default();
+        ps.put("env.mode.big", "x".repeat(super::ENV_INLINE_LIMIT));
+        let vars = ps.to_env_vars("HP_TEST_SPILL_MODE_").unwrap();
+        let mode = std::fs::metadata(&vars[0].1).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+        std::fs::remove_file(&vars[0].1).unwrap();
+    }
+
+    #[test]
+    fn test_escape_round_trip() {
+        for s in [
+            "", "a=b", "a\\nb", "line\r\n", "nul\0", "\\", "x\\", "=\\e\\0",
+        ] {
+            let escaped = super::escape(s);
+            assert!(!escaped.contains(['\n', '\r', '\0', '=']), "{:?}", escaped);
+            assert_eq!(s, super::unescape(&escaped));
+        }
+        let text = Value::from("a\r\n\0=b");
+        assert_eq!(
+            Some(text.clone()),
+            super::decode(&super::encode(&text).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_from_env() {
+        std::env::set_var("HP_TEST_FROM_ENV_MODEL__OPTIM__KIND", "adam");
+        std::env::set_var("HP_TEST_FROM_ENV_MODEL__LAYERS", "12");
+        std::env::set_var("HP_TEST_FROM_ENV_USE_AMP", "true");
+        std::env::set_var("HP_TEST_FROM_ENV_PARAMS", "ignored");
+
+        let ps = ParamScope::from_env("HP_TEST_FROM_ENV_");
+        let mut keys = ps.keys();
+        keys.retain(|k| k.starts_with("model.") || k == "use_amp" || k == "params");
+        keys.sort();
+        assert_eq!(vec!["model.layers", "model.optim.kind", "use_amp"], keys);
+        assert_eq!(Value::from("adam"), ps.get("model.optim.kind"));
+        assert_eq!(Value::Int(12), ps.get("model.layers"));
+        assert_eq!(Value::Boolean(true), ps.get("use_amp"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_from_env_skips_non_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        std::env::set_var(OsStr::from_bytes(b"HP_TEST_NON_UTF8_\xff"), "1");
+        std::env::set_var("HP_TEST_NON_UTF8_TEXT", OsStr::from_bytes(b"\xff"));
+        std::env::set_var("HP_TEST_NON_UTF8_LAYERS", "12");
+
+        let ps = ParamScope::from_env("HP_TEST_NON_UTF8_");
+        assert_eq!(vec!["layers"], ps.keys());
+        assert_eq!(Value::Int(12), ps.get("layers"));
+    }
+
+    #[test]
+    fn test_env_vars_nested_list() {
+        let list = Value::from(vec![
+            Value::from(vec!["a,b", "c\\"]),
+            Value::from(Vec::<i64>::new()),
+            Value::from(1.5),
+        ]);
+        let encoded = super::encode(&list).unwrap();
+        assert_eq!(Some(list), super::decode(&encoded));
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::api::ParamScope;
+use crate::storage::{frozen_params, Params};
+use crate::tags::has_any_tag;
+use crate::value::Value;
+
+/// Output format for [`export_config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+/// Options for [`export_config`].
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Only export keys whose value differs from the frozen global storage.
+    pub only_overrides: bool,
+    /// Write registered help text as comments (TOML and YAML only).
+    pub with_help: bool,
+    /// Only export keys carrying at least one of these tags; empty exports all keys.
+    pub tags: Vec<String>,
+    /// Skip keys carrying any of these tags.
+    pub exclude_tags: Vec<String>,
+}
+
+#[derive(Default)]
+struct Node {
+    value: Option<Value>,
+    children: BTreeMap<String, Node>,
+}
+
+/// Render the parameters visible on the current thread as a config file.
+///
+/// Dotted keys are nested into tables, so `train.lr` is written as `lr`
+/// inside a `train` table. A key that is both a value and the parent of other
+/// keys is written with its full dotted name at the top level. `UserDefined`
+/// values cannot be represented in a config file and are skipped.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set train.lr = 0.1;
+///
+///     let toml = export_config(ConfigFormat::Toml, &ExportOptions::default());
+///     assert_eq!(toml, "[train]\nlr = 0.1\n");
+/// }
+/// ```
+pub fn export_config(format: ConfigFormat, options: &ExportOptions) -> String {
+    let params = match ParamScope::capture() {
+        ParamScope::Just(params) => params,
+        ParamScope::Nothing => Params::default(),
+    };
+    let frozen = if options.only_overrides {
+        frozen_params()
+    } else {
+        Params::default()
+    };
+
+    let mut root = Node::default();
+    let mut flat: BTreeMap<String, Value> = BTreeMap::new();
+    for (hkey, e) in params.iter() {
+        let v = e.value();
+        if matches!(v, Value::Empty | Value::UserDefined(..)) {
+            continue;
+        }
+        if options.only_overrides && frozen.get(hkey).is_some_and(|f| f.value().same(v)) {
+            continue;
+        }
+        if !options.tags.is_empty() && !has_any_tag(*hkey, &options.tags) {
+            continue;
+        }
+        if has_any_tag(*hkey, &options.exclude_tags) {
+            continue;
+        }
+        flat.insert(e.key.to_string(), v.clone());
+    }
+    for (key, v) in flat.iter() {
+        let mut node = &mut root;
+        for part in key.split('.') {
+            node = node.children.entry(part.to_string()).or_default();
+        }
+        node.value = Some(v.clone());
+    }
+
+    // keys that have both a value and children can not be nested
+    let mut hoisted: Vec<(String, Value)> = Vec::new();
+    hoist(&mut root, "", &mut hoisted);
+    let help = if options.with_help {
+        help_texts()
+    } else {
+        HashMap::new()
+    };
+
+    let mut out = String::new();
+    match format {
+        ConfigFormat::Toml => {
+            for (k, v) in hoisted.iter() {
+                write_comment(&mut out, &help, k, "");
+                let _ = writeln!(out, "{} = {}", quote_key(k), render(v, format));
+            }
+            write_toml(&mut out, &root, "", &help);
+        }
+        ConfigFormat::Json => {
+            let mut entries: Vec<String> = hoisted
+                .iter()
+                .map(|(k, v)| format!("  {}: {}", escape(k), render(v, format)))
+                .collect();
+            entries.extend(json_entries(&root, 1));
+            if entries.is_empty() {
+                out.push_str("{}\n");
+            } else {
+                let _ = writeln!(out, "{{\n{}\n}}", entries.join(",\n"));
+            }
+        }
+        ConfigFormat::Yaml => {
+            for (k, v) in hoisted.iter() {
+                write_comment(&mut out, &help, k, "");
+                let _ = writeln!(out, "{}: {}", escape(k), render(v, format));
+            }
+            write_yaml(&mut out, &root, "", 0, &help);
+        }
+    }
+    out
+}
+
+fn hoist(node: &mut Node, path: &str, hoisted: &mut Vec<(String, Value)>) {
+    for (name, child) in node.children.iter_mut() {
+        let full = join(path, name);
+        if !child.children.is_empty() {
+            if let Some(v) = child.value.take() {
+                hoisted.push((full.clone(), v));
+            }
+        }
+        hoist(child, &full, hoisted);
+    }
+}
+
+fn write_toml(out: &mut String, node: &Node, path: &str, help: &HashMap<String, Vec<String>>) {
+    for (name, child) in node.children.iter() {
+        if let Some(v) = &child.value {
+            write_comment(out, help, &join(path, name), "");
+            let _ = writeln!(
+                out,
+                "{} = {}",
+                quote_key(name),
+                render(v, ConfigFormat::Toml)
+            );
+        }
+    }
+    for (name, child) in node.children.iter() {
+        if !child.children.is_empty() {
+            let full = join(path, &quote_key(name));
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let _ = writeln!(out, "[{}]", full);
+            write_toml(out, child, &full, help);
+        }
+    }
+}
+
+fn json_entries(node: &Node, depth: usize) -> Vec<String> {
+    let indent = "  ".repeat(depth);
+    node.children
+        .iter()
+        .map(|(name, child)| match &child.value {
+            Some(v) => format!(
+                "{}{}: {}",
+                indent,
+                escape(name),
+                render(v, ConfigFormat::Json)
+            ),
+            None => format!(
+                "{}{}: {{\n{}\n{}}}",
+                indent,
+                escape(name),
+                json_entries(child, depth + 1).join(",\n"),
+                indent
+            ),
+        })
+        .collect()
+}
+
+fn write_yaml(
+    out: &mut String,
+    node: &Node,
+    path: &str,
+    depth: usize,
+    help: &HashMap<String, Vec<String>>,
+) {
+    let indent = "  ".repeat(depth);
+    for (name, child) in node.children.iter() {
+        let full = join(path, name);
+        let key = if is_bare(name) {
+            name.clone()
+        } else {
+            escape(name)
+        };
+        match &child.value {
+            Some(v) => {
+                write_comment(out, help, &full, &indent);
+                let _ = writeln!(out, "{}{}: {}", indent, key, render(v, ConfigFormat::Yaml));
+            }
+            None => {
+                let _ = writeln!(out, "{}{}:", indent, key);
+                write_yaml(out, child, &full, depth + 1, help);
+            }
+        }
+    }
+}
+
+fn write_comment(out: &mut String, help: &HashMap<String, Vec<String>>, key: &str, indent: &str) {
+    if let Some(lines) = help.get(key) {
+        for line in lines.iter().flat_map(|h| h.lines()) {
+            let _ = writeln!(out, "{}# {}", indent, line);
+        }
+    }
+}
+
+/// Render the registered parameters as a markdown reference, one section per top-level prefix.
+///
+/// Parameters are collected from the help registered with `get_param!` and
+/// from the frozen global storage, which includes library defaults. Each
+/// row lists the key, the type and value of its frozen default, its tags and
+/// its help. The output is sorted and stable, so it can be committed to the
+/// project docs and regenerated in CI.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set doc.reference.lr = 0.1;
+///
+///     frozen();
+/// }
+/// tag_key("doc.reference.lr", &["tunable"]);
+///
+/// let md = params_reference();
+/// assert!(md.contains("## doc\n"));
+/// assert!(md.contains("| `doc.reference.lr` | float | `0.1` | tunable |  |"));
+/// ```
+pub fn params_reference() -> String {
+    let help = help_texts();
+    let frozen = frozen_params();
+    let mut keys: BTreeMap<String, Option<Value>> =
+        help.keys().map(|k| (k.clone(), None)).collect();
+    for e in frozen.values() {
+        if !e.key.is_empty() && !matches!(e.value(), Value::Empty) {
+            keys.insert(e.key.to_string(), Some(e.clone_value()));
+        }
+    }
+    let mut out = String::from("# Parameters\n");
+    let mut section = None;
+    for (key, default) in keys.iter() {
+        let prefix = key.split('.').next().unwrap_or_default();
+        if section != Some(prefix) {
+            section = Some(prefix);
+            let _ = write!(
+                out,
+                "\n## {}\n\n| key | type | default | tags | help |\n| --- | --- | --- | --- | --- |\n",
+                prefix
+            );
+        }
+        let (kind, default) = match default {
+            Some(v) if !matches!(v, Value::UserDefined(..)) => (
+                format!("{:?}", v.kind()).to_lowercase(),
+                format!("`{}`", render(v, ConfigFormat::Toml)),
+            ),
+            Some(v) => (format!("{:?}", v.kind()).to_lowercase(), String::new()),
+            None => (String::new(), String::new()),
+        };
+        let help = help
+            .get(key)
+            .map(|h| h.join("<br>").replace('|', "\\|"))
+            .unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "| `{}` | {} | {} | {} | {} |",
+            key,
+            kind,
+            default,
+            crate::tags::key_tags(key.as_str()).join(", "),
+            help
+        );
+    }
+    out
+}
+
+#[cfg(feature = "clap")]
+fn help_texts() -> HashMap<String, Vec<String>> {
+    let mut help: HashMap<String, Vec<String>> = HashMap::new();
+    for (k, h) in crate::cli::PARAMS {
+        let descs = help.entry(k.to_string()).or_default();
+        if !descs.iter().any(|d| d == h) {
+            descs.push(h.to_string());
+        }
+    }
+    help.values_mut().for_each(|descs| descs.sort());
+    help
+}
+
+#[cfg(not(feature = "clap"))]
+fn help_texts() -> HashMap<String, Vec<String>> {
+    HashMap::new()
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn is_bare(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn quote_key(key: &str) -> String {
+    if is_bare(key) {
+        key.to_string()
+    } else {
+        escape(key)
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn render(v: &Value, format: ConfigFormat) -> String {
+    match v {
+        Value::Int(v) => v.to_string(),
+        Value::Float(v) if v.is_finite() => format!("{:?}", v),
+        Value::Float(v) => match format {
+            ConfigFormat::Json => "null".to_string(),
+            ConfigFormat::Toml if v.is_nan() => "nan".to_string(),
+            ConfigFormat::Yaml if v.is_nan() => ".nan".to_string(),
+            ConfigFormat::Toml => (if *v > 0.0 { "inf" } else { "-inf" }).to_string(),
+            ConfigFormat::Yaml => (if *v > 0.0 { ".inf" } else { "-.inf" }).to_string(),
+        },
+        Value::Text(v) => escape(v),
+        Value::Boolean(v) => v.to_string(),
+        Value::List(vals) => format!(
+            "[{}]",
+            vals.iter()
+                .map(|v| render(v, format))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Empty | Value::UserDefined(..) => "null".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_export_toml() {
+        with_params! {
+            set model.name = "resnet".to_string();
+            set train.lr = 0.1;
+            set train.epochs = 10;
+            set train.sched.warmup = true;
+
+            let toml = export_config(ConfigFormat::Toml, &ExportOptions::default());
+            assert_eq!(
+                toml,
+                "[model]\nname = \"resnet\"\n\n[train]\nepochs = 10\nlr = 0.1\n\n[train.sched]\nwarmup = true\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_export_json() {
+        with_params! {
+            set train.lr = 0.1;
+            set train.opt.kind = "adam".to_string();
+
+            let json = export_config(ConfigFormat::Json, &ExportOptions::default());
+            assert_eq!(
+                json,
+                "{\n  \"train\": {\n    \"lr\": 0.1,\n    \"opt\": {\n      \"kind\": \"adam\"\n    }\n  }\n}\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_export_yaml_with_hoisted_key() {
+        with_params! {
+            set a.b = 1;
+            set a.b.c = 2;
+
+            let yaml = export_config(ConfigFormat::Yaml, &ExportOptions::default());
+            assert_eq!(yaml, "\"a.b\": 1\na:\n  b:\n    c: 2\n");
+        }
+    }
+}
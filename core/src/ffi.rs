@@ -1,7 +1,8 @@
-use std::ffi::{CStr, CString};
+use std::ffi::{c_char, CStr, CString};
 
 use super::api::ParamScope;
 use super::api::ParamScopeOps;
+use super::value::{Value, ValueError, ValueKind};
 
 /// Creates a new ParamScope object and returns a pointer to it.
 #[no_mangle]
@@ -28,12 +29,50 @@ pub unsafe extern "C" fn param_scope_exit(this: *mut ParamScope) {
     (*this).exit();
 }
 
+/// Captures the parameters visible on the current thread into an opaque token.
+///
+/// The token can be handed to another thread and installed there with
+/// `param_scope_install_token`. Release it with `param_scope_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_capture_token() -> *mut ParamScope {
+    Box::leak(Box::new(ParamScope::capture()))
+}
+
+/// Installs a captured token on the current thread.
+///
+/// Returns an entered ParamScope object; call `param_scope_exit` and
+/// `param_scope_destroy` on it once the callback is done. The token itself
+/// is left untouched and can be installed on several threads.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_install_token(token: *const ParamScope) -> *mut ParamScope {
+    let mut ps = Box::new((*token).clone());
+    ps.enter();
+    Box::leak(ps)
+}
+
+/// Hashes a string key into the hashed key taken by the `hget` functions.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_hash(key: *const c_char) -> u64 {
+    crate::xxh::xxhash(CStr::from_ptr(key).to_bytes())
+}
+
 /// Gets an integer value from the given ParamScope object by hashed key.
 #[no_mangle]
-pub unsafe extern "C" fn param_scope_hget_i64(this: *mut ParamScope, hkey: u64, def: i64) -> i64 {
+pub unsafe extern "C" fn param_scope_hget_or_i64(
+    this: *mut ParamScope,
+    hkey: u64,
+    def: i64,
+) -> i64 {
     (*this).get_or_else(hkey, def)
 }
 
+/// Gets an integer value by hashed key; the former name of
+/// `param_scope_hget_or_i64`, kept for existing callers.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_hget_i64(this: *mut ParamScope, hkey: u64, def: i64) -> i64 {
+    param_scope_hget_or_i64(this, hkey, def)
+}
+
 /// Gets a float value from the given ParamScope object by hashed key.
 #[no_mangle]
 pub unsafe extern "C" fn param_scope_hget_or_f64(
@@ -45,32 +84,26 @@ pub unsafe extern "C" fn param_scope_hget_or_f64(
 }
 
 /// Gets a string value from the given ParamScope object by hashed key.
-#[cfg(target_arch = "x86_64")]
+///
+/// The returned string is owned by the caller; release it with
+/// `param_scope_free_str`.
 #[no_mangle]
 pub unsafe extern "C" fn param_scope_hget_or_str(
     this: *mut ParamScope,
     hkey: u64,
-    def: *mut i8,
-) -> *mut i8 {
+    def: *const c_char,
+) -> *mut c_char {
     let raw = CStr::from_ptr(def).to_str().unwrap().to_string();
     let s = (*this).get_or_else(hkey, raw);
     CString::new(s).unwrap().into_raw()
 }
 
-/// Gets a string value from the given ParamScope object by hashed key.
-#[cfg(target_arch = "aarch64")]
+/// Releases a string returned by `param_scope_hget_or_str`.
 #[no_mangle]
-pub unsafe extern "C" fn param_scope_hget_or_str(
-    this: *mut ParamScope,
-    hkey: u64,
-    def: *mut i8,
-) -> *mut i8 {
-    let raw = CStr::from_ptr(def as *const i8)
-        .to_str()
-        .unwrap()
-        .to_string();
-    let s = (*this).get_or_else(hkey, raw);
-    CString::new(s).unwrap().into_raw()
+pub unsafe extern "C" fn param_scope_free_str(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
 }
 
 /// Gets a boolean value from the given ParamScope object by hashed key.
@@ -84,60 +117,36 @@ pub unsafe extern "C" fn param_scope_hget_or_bool(
 }
 
 /// Sets an integer value in the given ParamScope object by string key.
-#[cfg(target_arch = "x86_64")]
 #[no_mangle]
-pub unsafe extern "C" fn param_scope_put_i64(this: *mut ParamScope, key: *const i8, val: i64) {
+pub unsafe extern "C" fn param_scope_put_i64(this: *mut ParamScope, key: *const c_char, val: i64) {
     let key = CStr::from_ptr(key);
     (*this).put(key.to_string_lossy().to_string(), val)
 }
 
-/// Sets an integer value in the given ParamScope object.
-#[cfg(target_arch = "aarch64")]
-#[no_mangle]
-pub unsafe extern "C" fn param_scope_put_i64(this: *mut ParamScope, key: *const i8, val: i64) {
-    let key = CStr::from_ptr(key as *const i8);
-    (*this).put(key.to_string_lossy().to_string(), val)
-}
-
 /// Sets a float value in the given ParamScope object by string key.
-#[cfg(target_arch = "x86_64")]
 #[no_mangle]
-pub unsafe extern "C" fn param_scope_put_f64(this: *mut ParamScope, key: *const i8, val: f64) {
+pub unsafe extern "C" fn param_scope_put_f64(this: *mut ParamScope, key: *const c_char, val: f64) {
     let key = CStr::from_ptr(key);
     (*this).put(key.to_string_lossy().to_string(), val)
 }
 
-/// Sets a float value in the given ParamScope object by string key.
-#[cfg(target_arch = "aarch64")]
-#[no_mangle]
-pub unsafe extern "C" fn param_scope_put_f64(this: *mut ParamScope, key: *const i8, val: f64) {
-    let key = CStr::from_ptr(key as *const i8);
-    (*this).put(key.to_string_lossy().to_string(), val)
-}
-
 /// Sets a boolean value in the given ParamScope object by string key.
-#[cfg(target_arch = "x86_64")]
 #[no_mangle]
-pub unsafe extern "C" fn param_scope_put_bool(this: *mut ParamScope, key: *const i8, val: bool) {
+pub unsafe extern "C" fn param_scope_put_bool(
+    this: *mut ParamScope,
+    key: *const c_char,
+    val: bool,
+) {
     let key = CStr::from_ptr(key);
     (*this).put(key.to_string_lossy().to_string(), val)
 }
 
-/// Sets a boolean value in the given ParamScope object by string key.
-#[cfg(target_arch = "aarch64")]
-#[no_mangle]
-pub unsafe extern "C" fn param_scope_put_bool(this: *mut ParamScope, key: *const i8, val: bool) {
-    let key = CStr::from_ptr(key as *const i8);
-    (*this).put(key.to_string_lossy().to_string(), val)
-}
-
 /// Sets a string value in the given ParamScope object by string key.
-#[cfg(target_arch = "x86_64")]
 #[no_mangle]
 pub unsafe extern "C" fn param_scope_put_str(
     this: *mut ParamScope,
-    key: *const i8,
-    val: *const i8,
+    key: *const c_char,
+    val: *const c_char,
 ) {
     let key = CStr::from_ptr(key);
     let val = CStr::from_ptr(val);
@@ -147,18 +156,182 @@ pub unsafe extern "C" fn param_scope_put_str(
     )
 }
 
-/// Sets a string value in the given ParamScope object by string key.
-#[cfg(target_arch = "aarch64")]
+/// Copies a list from the given ParamScope object by hashed key into `out`.
+///
+/// At most `cap` items are copied. Returns the length of the list, -1 if the
+/// key is not set, or -2 if it does not hold a list of values convertible to T.
+unsafe fn hget_list<T>(this: *mut ParamScope, hkey: u64, out: *mut T, cap: usize) -> i64
+where
+    T: for<'a> TryFrom<&'a Value, Error = ValueError>,
+{
+    let vals: Vec<T> = match Vec::<T>::try_from((*this).get_with_hash(hkey)) {
+        Ok(vals) => vals,
+        Err(ValueError::Empty) => return -1,
+        Err(_) => return -2,
+    };
+    let len = vals.len();
+    for (i, v) in vals.into_iter().take(cap).enumerate() {
+        out.add(i).write(v);
+    }
+    len as i64
+}
+
+/// Gets an integer list from the given ParamScope object by hashed key.
+///
+/// Pass `cap` 0 to query the length before allocating `out`.
 #[no_mangle]
-pub unsafe extern "C" fn param_scope_put_str(
+pub unsafe extern "C" fn param_scope_hget_i64_list(
+    this: *mut ParamScope,
+    hkey: u64,
+    out: *mut i64,
+    cap: usize,
+) -> i64 {
+    hget_list(this, hkey, out, cap)
+}
+
+/// Gets a float list from the given ParamScope object by hashed key.
+///
+/// Pass `cap` 0 to query the length before allocating `out`.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_hget_f64_list(
+    this: *mut ParamScope,
+    hkey: u64,
+    out: *mut f64,
+    cap: usize,
+) -> i64 {
+    hget_list(this, hkey, out, cap)
+}
+
+/// Sets an integer list in the given ParamScope object by string key.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_put_i64_list(
     this: *mut ParamScope,
-    key: *const i8,
-    val: *const i8,
+    key: *const c_char,
+    vals: *const i64,
+    len: usize,
 ) {
-    let key = CStr::from_ptr(key as *const i8);
-    let val = CStr::from_ptr(val as *const i8);
-    (*this).put(
-        key.to_string_lossy().to_string(),
-        val.to_string_lossy().to_string(),
-    )
+    let key = CStr::from_ptr(key);
+    let vals = std::slice::from_raw_parts(vals, len).to_vec();
+    (*this).put(key.to_string_lossy().to_string(), vals)
+}
+
+/// Sets a float list in the given ParamScope object by string key.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_put_f64_list(
+    this: *mut ParamScope,
+    key: *const c_char,
+    vals: *const f64,
+    len: usize,
+) {
+    let key = CStr::from_ptr(key);
+    let vals = std::slice::from_raw_parts(vals, len).to_vec();
+    (*this).put(key.to_string_lossy().to_string(), vals)
+}
+
+/// Deletes a parameter from the given ParamScope object by string key.
+///
+/// On an entered scope the key stays deleted from the thread until the scope
+/// exits; see `ParamScope::del`.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_del(this: *mut ParamScope, key: *const c_char) {
+    let key = CStr::from_ptr(key);
+    (*this).del(key.to_string_lossy().as_ref())
+}
+
+/// Lists the keys visible from the given ParamScope object, sorted.
+///
+/// The number of keys is written to `len`. Release the array and its strings
+/// with `param_scope_free_keys`.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_keys(
+    this: *mut ParamScope,
+    len: *mut usize,
+) -> *mut *mut c_char {
+    let mut keys = (*this).keys();
+    keys.sort();
+    let keys: Box<[*mut c_char]> = keys
+        .into_iter()
+        .map(|k| CString::new(k).unwrap_or_default().into_raw())
+        .collect();
+    *len = keys.len();
+    Box::into_raw(keys) as *mut *mut c_char
+}
+
+/// Releases an array returned by `param_scope_keys`.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_free_keys(keys: *mut *mut c_char, len: usize) {
+    if keys.is_null() {
+        return;
+    }
+    let keys = Box::from_raw(std::ptr::slice_from_raw_parts_mut(keys, len));
+    for k in keys.iter() {
+        drop(CString::from_raw(*k));
+    }
+}
+
+/// Gets the type of a parameter from the given ParamScope object by hashed key.
+///
+/// Returns 0 if the key is not set, then 1 for integers, 2 for floats,
+/// 3 for strings, 4 for booleans, 5 for lists and 6 for other values.
+#[no_mangle]
+pub unsafe extern "C" fn param_scope_hget_type(this: *mut ParamScope, hkey: u64) -> i32 {
+    match (*this).get_with_hash(hkey).kind() {
+        ValueKind::Empty => 0,
+        ValueKind::Int => 1,
+        ValueKind::Float => 2,
+        ValueKind::Text => 3,
+        ValueKind::Boolean => 4,
+        ValueKind::List => 5,
+        ValueKind::UserDefined => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xxh::xxhash;
+
+    #[test]
+    fn test_keys_del_and_type() {
+        unsafe {
+            let ps = param_scope_create();
+            let key = CString::new("ffi.a").unwrap();
+            let list_key = CString::new("ffi.b").unwrap();
+            param_scope_put_f64(ps, key.as_ptr(), 1.5);
+            param_scope_put_i64_list(ps, list_key.as_ptr(), [1i64, 2].as_ptr(), 2);
+            assert_eq!(2, param_scope_hget_type(ps, xxhash(b"ffi.a")));
+            assert_eq!(5, param_scope_hget_type(ps, xxhash(b"ffi.b")));
+
+            let mut len = 0;
+            let keys = param_scope_keys(ps, &mut len);
+            let names: Vec<_> = std::slice::from_raw_parts(keys, len)
+                .iter()
+                .map(|k| CStr::from_ptr(*k).to_str().unwrap())
+                .filter(|k| k.starts_with("ffi."))
+                .collect();
+            assert_eq!(vec!["ffi.a", "ffi.b"], names);
+            param_scope_free_keys(keys, len);
+
+            param_scope_del(ps, key.as_ptr());
+            assert_eq!(0, param_scope_hget_type(ps, xxhash(b"ffi.a")));
+            param_scope_destroy(ps);
+        }
+    }
+
+    #[test]
+    fn test_hash_and_hget_or_i64() {
+        unsafe {
+            let ps = param_scope_create();
+            let key = CString::new("ffi.hash").unwrap();
+            assert_eq!(xxhash(b"ffi.hash"), param_scope_hash(key.as_ptr()));
+            param_scope_put_i64(ps, key.as_ptr(), 3);
+            assert_eq!(
+                3,
+                param_scope_hget_or_i64(ps, param_scope_hash(key.as_ptr()), 0)
+            );
+            assert_eq!(7, param_scope_hget_or_i64(ps, xxhash(b"ffi.unset"), 7));
+            assert_eq!(3, param_scope_hget_i64(ps, xxhash(b"ffi.hash"), 0));
+            param_scope_destroy(ps);
+        }
+    }
 }
@@ -0,0 +1,259 @@
+//! Loaders for JSON, YAML and TOML documents that do not go through the
+//! `config` crate.
+//!
+//! Nested tables are flattened into dotted keys, arrays become
+//! `Value::List` and nulls are skipped, so an empty document gives an
+//! empty scope.
+
+use std::fmt;
+
+use ::serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::api::{IntoParams, ParamScope};
+use crate::provenance::{with_default_source, Source};
+use crate::serialize::ValueVisitor;
+use crate::value::Value;
+
+/// A parsed document, before flattening.
+enum Node {
+    Leaf(Value),
+    Table(Vec<(String, Node)>),
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a table or a parameter value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Node, E> {
+        ValueVisitor.visit_unit().map(Node::Leaf)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Node, E> {
+        ValueVisitor.visit_none().map(Node::Leaf)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Node, D::Error> {
+        Node::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Node, E> {
+        ValueVisitor.visit_bool(v).map(Node::Leaf)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Node, E> {
+        ValueVisitor.visit_i64(v).map(Node::Leaf)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Node, E> {
+        ValueVisitor.visit_u64(v).map(Node::Leaf)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Node, E> {
+        ValueVisitor.visit_f64(v).map(Node::Leaf)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Node, E> {
+        ValueVisitor.visit_str(v).map(Node::Leaf)
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Node, E> {
+        ValueVisitor.visit_string(v).map(Node::Leaf)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Node, A::Error> {
+        ValueVisitor.visit_seq(seq).map(Node::Leaf)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut table = Vec::new();
+        while let Some(entry) = map.next_entry::<String, Node>()? {
+            table.push(entry);
+        }
+        Ok(Node::Table(table))
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Node, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+impl Node {
+    fn into_param_scope(self, format: &str) -> Result<ParamScope, String> {
+        let mut ps = ParamScope::default();
+        match self {
+            Node::Table(table) => with_default_source(Source::File(format.into()), || {
+                flatten(&mut ps, None, table)
+            })?,
+            Node::Leaf(Value::Empty) => {}
+            Node::Leaf(_) => return Err("the document is not a table".to_string()),
+        }
+        Ok(ps)
+    }
+}
+
+fn flatten(
+    ps: &mut ParamScope,
+    prefix: Option<&str>,
+    table: Vec<(String, Node)>,
+) -> Result<(), String> {
+    for (name, node) in table {
+        let key = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name,
+        };
+        match node {
+            Node::Table(table) => flatten(ps, Some(&key), table)?,
+            Node::Leaf(Value::Empty) => {}
+            Node::Leaf(val) => ps.try_put(key, val)?,
+        }
+    }
+    Ok(())
+}
+
+impl ParamScope {
+    /// Read the parameters of a JSON document.
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// let mut ps = ParamScope::from_json_str(r#"{
+    ///     "train": {"lr": 0.01, "layers": [64, 32]},
+    ///     "name": "run"
+    /// }"#).unwrap();
+    /// with_params! {
+    ///     params ps;
+    ///
+    ///     assert_eq!(0.01, get_param!(train.lr, 0.0));
+    ///     assert_eq!(vec![64, 32], get_param!(train.layers, Vec::<i64>::new()));
+    ///     assert_eq!("run", get_param!(name, String::new()));
+    /// }
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn from_json_str(json: &str) -> Result<ParamScope, String> {
+        serde_json::from_str::<Node>(json)
+            .map_err(|e| e.to_string())?
+            .into_param_scope("json")
+    }
+
+    /// Read the parameters of a YAML document.
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// let ps = ParamScope::from_yaml_str("train:\n  lr: 0.01\n  epochs: 10\n").unwrap();
+    /// assert_eq!(Value::Int(10), ps.get("train.epochs"));
+    /// ```
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(yaml: &str) -> Result<ParamScope, String> {
+        serde_yaml::from_str::<Node>(yaml)
+            .map_err(|e| e.to_string())?
+            .into_param_scope("yaml")
+    }
+
+    /// Read the parameters of a TOML document.
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// let ps = ParamScope::from_toml_str("[train]\nlr = 0.01\nepochs = 10\n").unwrap();
+    /// assert_eq!(Value::Int(10), ps.get("train.epochs"));
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(toml: &str) -> Result<ParamScope, String> {
+        toml::from_str::<Node>(toml)
+            .map_err(|e| e.to_string())?
+            .into_param_scope("toml")
+    }
+}
+
+/// A JSON object gives its parameters, nested objects giving dotted keys as
+/// in [`ParamScope::from_json_str`]. Other documents give none.
+#[cfg(feature = "json")]
+impl IntoParams for serde_json::Value {
+    fn into_params(self) -> Vec<(String, Value)> {
+        let ps = Node::deserialize(self)
+            .map_err(|e| e.to_string())
+            .and_then(|node| node.into_param_scope("json"));
+        match ps {
+            Ok(ParamScope::Just(params)) => params
+                .into_iter()
+                .map(|(_, e)| (e.key.to_string(), e.clone_value()))
+                .collect(),
+            Ok(ParamScope::Nothing) => Vec::new(),
+            Err(e) => {
+                println!("hyperparameter warning: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_from_json_str() {
+        let ps = ParamScope::from_json_str(
+            r#"{"a": {"b": {"c": 1}, "d": null}, "e": [true, "x"], "f": -2.5}"#,
+        )
+        .unwrap();
+        assert_eq!(Value::Int(1), ps.get("a.b.c"));
+        assert_eq!(None, ps.try_get("a.d"));
+        assert_eq!(
+            Value::List(vec![Value::Boolean(true), Value::Text("x".into())]),
+            ps.get("e")
+        );
+        assert_eq!(Value::Float(-2.5), ps.get("f"));
+
+        assert!(ParamScope::from_json_str("[1, 2]").is_err());
+        assert!(ParamScope::from_json_str("{\"a\": [{\"b\": 1}]}").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_set_all_json() {
+        let doc: serde_json::Value = r#"{"json": {"set_all": {"lr": 0.5}}}"#.parse().unwrap();
+        with_params! {
+            @set_all doc;
+
+            assert_eq!(0.5, get_param!(json.set_all.lr, 0.0));
+        }
+        let list: serde_json::Value = "[1]".parse().unwrap();
+        assert!(list.into_params().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_from_toml_str() {
+        let ps = ParamScope::from_toml_str(
+            "name = \"run\"\n\n[model]\nlayers = [64, 32]\n\n[model.optimizer]\nlr = 0.01\n",
+        )
+        .unwrap();
+        assert_eq!(Value::Text("run".into()), ps.get("name"));
+        assert_eq!(Value::from(vec![64, 32]), ps.get("model.layers"));
+        assert_eq!(Value::Float(0.01), ps.get("model.optimizer.lr"));
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_from_yaml_str() {
+        let ps = ParamScope::from_yaml_str(
+            "model:\n  layers: [64, 32]\n  optimizer:\n    name: adam\n    decay: ~\n",
+        )
+        .unwrap();
+        assert_eq!(Value::from(vec![64, 32]), ps.get("model.layers"));
+        assert_eq!(Value::Text("adam".into()), ps.get("model.optimizer.name"));
+        assert_eq!(None, ps.try_get("model.optimizer.decay"));
+
+        assert_eq!(0, ParamScope::from_yaml_str("").unwrap().keys().len());
+    }
+}
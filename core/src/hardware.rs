@@ -0,0 +1,189 @@
+//! Hardware facts published as defaults under the reserved `hw.` prefix.
+//!
+//! The probes run once, before any library defaults, so a library can derive
+//! its defaults from them. The facts live in a layer of their own, read after
+//! every other one: `get_param!(hw.cpu_cores, 1)` sees them, but they are not
+//! listed by `keys()` or exported with the configuration.
+//!
+//! ```
+//! use hyperparameter::*;
+//!
+//! register_library_defaults!(module_path!(), |ps| {
+//!     let cores: i64 = ps.get_or_else("hw.cpu_cores", 1);
+//!     ps.put("doc.hardware.workers", (cores - 1).max(1));
+//! });
+//!
+//! assert!(get_param!(doc.hardware.workers, 0) >= 1);
+//! ```
+
+use lazy_static::lazy_static;
+
+use crate::api::{ParamScope, ParamScopeOps};
+use crate::storage::{Entry, Params};
+
+/// Prefix of the keys set by the hardware probes.
+pub const HW_PREFIX: &str = "hw.";
+
+/// A source of hardware facts, registered with [`register_hardware_probe!`].
+pub trait HardwareProbe: Sync {
+    /// Put the detected facts into `ps`, under [`HW_PREFIX`].
+    ///
+    /// Facts that can not be detected are left unset.
+    fn probe(&self, ps: &mut ParamScope);
+}
+
+/// Probes registered with [`register_hardware_probe!`], besides the builtin ones.
+#[::linkme::distributed_slice]
+pub static HARDWARE_PROBES: [&'static dyn HardwareProbe];
+
+/// Register a [`HardwareProbe`] to run when the defaults layer is built.
+#[macro_export]
+macro_rules! register_hardware_probe {
+    ($probe:expr) => {
+        const _: () = {
+            #[$crate::linkme::distributed_slice($crate::HARDWARE_PROBES)]
+            #[linkme(crate = $crate::linkme)]
+            static PROBE: &'static dyn $crate::HardwareProbe = &$probe;
+        };
+    };
+}
+
+/// Sets `hw.cpu_cores`, the parallelism available to the process.
+pub struct CpuProbe;
+
+impl HardwareProbe for CpuProbe {
+    fn probe(&self, ps: &mut ParamScope) {
+        if let Ok(n) = std::thread::available_parallelism() {
+            ps.put("hw.cpu_cores", n.get() as i64);
+        }
+    }
+}
+
+/// Sets `hw.total_mem_mb` from `/proc/meminfo`.
+pub struct MemoryProbe;
+
+impl HardwareProbe for MemoryProbe {
+    fn probe(&self, ps: &mut ParamScope) {
+        let meminfo = match std::fs::read_to_string("/proc/meminfo") {
+            Ok(meminfo) => meminfo,
+            Err(_) => return,
+        };
+        let kb = meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix("MemTotal:"))
+            .and_then(|rest| {
+                rest.trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<i64>()
+                    .ok()
+            });
+        if let Some(kb) = kb {
+            ps.put("hw.total_mem_mb", kb / 1024);
+        }
+    }
+}
+
+/// Sets `hw.gpu_count` to the number of NVIDIA GPUs seen by the driver.
+///
+/// Only detects GPUs on Linux, where a missing driver means no GPU.
+pub struct GpuProbe;
+
+impl HardwareProbe for GpuProbe {
+    fn probe(&self, ps: &mut ParamScope) {
+        if !cfg!(target_os = "linux") {
+            return;
+        }
+        let count = std::fs::read_dir("/proc/driver/nvidia/gpus")
+            .map(|entries| entries.count())
+            .unwrap_or(0);
+        ps.put("hw.gpu_count", count as i64);
+    }
+}
+
+/// Run the builtin probes, then the registered ones.
+fn probe_hardware(ps: &mut ParamScope) {
+    let builtin: [&dyn HardwareProbe; 3] = [&CpuProbe, &MemoryProbe, &GpuProbe];
+    for probe in builtin.into_iter().chain(HARDWARE_PROBES.iter().copied()) {
+        probe.probe(ps);
+    }
+}
+
+lazy_static! {
+    static ref FACTS: Params = {
+        let mut ps = ParamScope::default();
+        probe_hardware(&mut ps);
+        let mut facts = match ps {
+            ParamScope::Just(params) => params,
+            ParamScope::Nothing => Params::default(),
+        };
+        facts.values_mut().for_each(|e| e.default = true);
+        facts
+    };
+}
+
+/// The facts found by the probes, which run on the first call.
+pub(crate) fn hardware_facts() -> Params {
+    FACTS.clone()
+}
+
+/// The fact for a hashed key, read when no other layer sets it.
+pub(crate) fn hardware_fact(hkey: u64) -> Option<&'static Entry> {
+    FACTS.get(&hkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::*;
+
+    #[test]
+    fn test_probe_hardware() {
+        let facts = super::hardware_facts();
+        assert!(ParamScope::Just(facts.clone()).get_or_else("hw.cpu_cores", 0) >= 1);
+        assert!(facts.values().all(|e| e.key.starts_with(super::HW_PREFIX)));
+    }
+
+    static PROBED: AtomicUsize = AtomicUsize::new(0);
+
+    struct CountingProbe;
+
+    impl HardwareProbe for CountingProbe {
+        fn probe(&self, _: &mut ParamScope) {
+            PROBED.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    register_hardware_probe!(CountingProbe);
+
+    #[test]
+    fn test_hardware_layer() {
+        assert!(get_param!(hw.cpu_cores, 0) >= 1);
+        assert_eq!(1, PROBED.load(Ordering::SeqCst));
+        assert!(library_defaults()
+            .keys()
+            .all(|k| super::hardware_fact(*k).is_none()));
+        assert!(!ParamScope::Nothing
+            .keys()
+            .iter()
+            .any(|k| k.starts_with(super::HW_PREFIX)));
+        assert_eq!(
+            Some(Layer::Defaults),
+            resolve("hw.cpu_cores").map(|r| r.layer)
+        );
+
+        with_params! {
+            set hw.cpu_cores = 1024;
+
+            assert_eq!(1024, get_param!(hw.cpu_cores, 0));
+            with_params! {
+                @del hw.cpu_cores;
+
+                assert_eq!(0, get_param!(hw.cpu_cores, 0));
+            }
+        }
+        unfreeze_prefix("hw");
+        assert_eq!(1, PROBED.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::api::ParamScope;
+use crate::storage::THREAD_STORAGE;
+use crate::value::Value;
+use crate::xxh::XXHashable;
+
+/// A source of parameter values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    /// Defaults registered by libraries with `register_library_defaults!`,
+    /// and the hardware facts of the `hardware` feature.
+    Defaults,
+    /// The frozen global storage that every thread starts from.
+    Frozen,
+    /// A parameter scope on the current thread.
+    Thread,
+}
+
+impl Layer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Defaults => "defaults",
+            Layer::Frozen => "frozen",
+            Layer::Thread => "thread",
+        }
+    }
+}
+
+/// Where the current value of a parameter comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution {
+    pub value: Value,
+    pub layer: Layer,
+    /// Nesting depth of the thread scope that set the value, 0 being the
+    /// thread's root scope. Always 0 for the other layers.
+    pub depth: usize,
+}
+
+/// Find the value of `key` visible on the current thread and the layer it comes from.
+///
+/// Returns `None` if the key is not set.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set doc.layers.a = 1;
+///
+///     let r = resolve("doc.layers.a").unwrap();
+///     assert_eq!(Value::Int(1), r.value);
+///     assert_eq!((Layer::Thread, 1), (r.layer, r.depth));
+/// }
+/// assert_eq!(None, resolve("doc.layers.a"));
+/// ```
+pub fn resolve<K: XXHashable>(key: K) -> Option<Resolution> {
+    resolve_hash(key.xxh())
+}
+
+fn resolve_hash(hkey: u64) -> Option<Resolution> {
+    let (value, default, depth) = THREAD_STORAGE.with(|ts| {
+        let ts = ts.borrow();
+        let e = ts.params.get(&hkey);
+        #[cfg(feature = "hardware")]
+        let e = e.or_else(|| crate::hardware::hardware_fact(hkey));
+        let e = e?;
+        let depth = ts.history.iter().rposition(|keys| keys.contains(&hkey));
+        Some((e.clone_value(), e.is_default(), depth))
+    })?;
+    if matches!(value, Value::Empty) {
+        return None;
+    }
+    let layer = match depth {
+        Some(_) => Layer::Thread,
+        None if default => Layer::Defaults,
+        None => Layer::Frozen,
+    };
+    Some(Resolution {
+        value,
+        layer,
+        depth: depth.unwrap_or(0),
+    })
+}
+
+/// A key whose value would change if a scope were entered, see [`simulate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedChange {
+    pub key: String,
+    /// The current value and the layer it comes from, `None` if the key is unset.
+    pub old: Option<Resolution>,
+    pub new: Value,
+}
+
+/// Preview the effect of entering `overrides` on the current thread, without entering it.
+///
+/// Only keys whose value would change are reported, sorted by key.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let mut overrides = ParamScope::default();
+/// overrides.put("doc.simulate.a", 1);
+/// overrides.put("doc.simulate.b", 3);
+/// with_params! {
+///     set doc.simulate.a = 1;
+///     set doc.simulate.b = 2;
+///
+///     let changes = simulate(&overrides);
+///     assert_eq!(1, changes.len());
+///     assert_eq!("doc.simulate.b", changes[0].key);
+///     assert_eq!(Some(Value::Int(2)), changes[0].old.as_ref().map(|r| r.value.clone()));
+///     assert_eq!(Value::Int(3), changes[0].new);
+/// }
+/// ```
+pub fn simulate(overrides: &ParamScope) -> Vec<SimulatedChange> {
+    let params = match overrides {
+        ParamScope::Just(params) => params,
+        ParamScope::Nothing => return Vec::new(),
+    };
+    let mut changes: Vec<SimulatedChange> = params
+        .iter()
+        .filter_map(|(hkey, e)| {
+            let old = resolve_hash(*hkey);
+            if old.as_ref().is_some_and(|r| r.value.same(e.value())) {
+                return None;
+            }
+            Some(SimulatedChange {
+                key: e.key.to_string(),
+                old,
+                new: e.clone_value(),
+            })
+        })
+        .collect();
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    changes
+}
+
+/// The layers active on the current thread, from highest to lowest precedence.
+///
+/// Thread scopes are listed with their nesting depth, innermost first.
+pub fn layers() -> Vec<(Layer, usize)> {
+    let depth = THREAD_STORAGE.with(|ts| ts.borrow().depth());
+    let mut layers: Vec<(Layer, usize)> = (0..=depth).rev().map(|d| (Layer::Thread, d)).collect();
+    layers.push((Layer::Frozen, 0));
+    if cfg!(feature = "defaults") {
+        layers.push((Layer::Defaults, 0));
+    }
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(None, resolve("layers.test.a"));
+        let base = layers().len();
+        with_params! {
+            set layers.test.a = 1;
+            set layers.test.b = 2;
+
+            with_params! {
+                set layers.test.b = 3;
+
+                let a = resolve("layers.test.a").unwrap();
+                assert_eq!((Value::Int(1), Layer::Thread, 1), (a.value, a.layer, a.depth));
+                let b = resolve("layers.test.b").unwrap();
+                assert_eq!((Value::Int(3), Layer::Thread, 2), (b.value, b.layer, b.depth));
+                assert_eq!(base + 2, layers().len());
+                assert_eq!((Layer::Thread, 2), layers()[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolve_tags_defaults() {
+        use crate::storage::{Entry, MultipleVersion, THREAD_STORAGE};
+
+        let hkey = "layers.test.default".xxh();
+        let mut default = Entry::new("layers.test.default", 1);
+        default.default = true;
+        THREAD_STORAGE.with(|ts| ts.borrow_mut().params.insert(hkey, default));
+        assert_eq!(
+            Layer::Defaults,
+            resolve("layers.test.default").unwrap().layer
+        );
+        with_params! {
+            set layers.test.default = 1;
+
+            assert_eq!(Layer::Thread, resolve("layers.test.default").unwrap().layer);
+        }
+        assert_eq!(
+            Layer::Defaults,
+            resolve("layers.test.default").unwrap().layer
+        );
+
+        // the same value set over the default, as a frozen one is, is no longer the default
+        THREAD_STORAGE.with(|ts| ts.borrow_mut().params.update(hkey, 1));
+        let r = resolve("layers.test.default").unwrap();
+        assert_eq!((Value::Int(1), Layer::Frozen), (r.value, r.layer));
+        THREAD_STORAGE.with(|ts| ts.borrow_mut().params.remove(&hkey));
+    }
+
+    #[test]
+    fn test_simulate_ignores_float_noise() {
+        let mut overrides = ParamScope::default();
+        overrides.put("layers.simulate.lr", 0.1 + 1e-15);
+        overrides.put("layers.simulate.wd", 0.02);
+        with_params! {
+            set layers.simulate.lr = 0.1;
+            set layers.simulate.wd = 0.01;
+
+            let changes = simulate(&overrides);
+            assert_eq!(vec!["layers.simulate.wd"], changes.iter().map(|c| c.key.as_str()).collect::<Vec<_>>());
+        }
+    }
+}
@@ -3,28 +3,236 @@
 extern crate proptest;
 
 mod storage;
+mod strict;
+mod subscribe;
 mod value;
 
 mod api;
+mod audit;
+mod build_info;
 mod cfg;
+pub mod derived;
+mod diff;
+mod env;
+mod export;
 mod ffi;
+mod layers;
+mod logging;
+mod merge;
+mod mode;
+mod profiles;
+mod provenance;
+mod reads;
+pub mod rollout;
+mod snapshot;
+mod spawn;
+mod tags;
+mod task;
+mod ticket;
+mod units;
+mod validate;
+mod watch;
 mod xxh;
 
+pub use crate::api::freeze_hard;
+pub use crate::api::freeze_prefix;
 pub use crate::api::frozen;
+pub use crate::api::unfreeze_prefix;
+pub use crate::api::with_param_str;
+pub use crate::api::FromParamScope;
+pub use crate::api::IntoParams;
 pub use crate::api::ParamScope;
 pub use crate::api::ParamScopeOps;
+pub use crate::api::ScopeView;
+pub use crate::audit::audit_log;
+pub use crate::audit::set_audit_log;
+pub use crate::audit::ParamMutation;
+pub use crate::build_info::build_info;
+pub use crate::build_info::BuildInfo;
 pub use crate::cfg::AsParamScope;
+pub use crate::diff::ParamChange;
+pub use crate::env::EnvRefreshedScope;
+pub use crate::env::EnvSource;
+pub use crate::env::ENV_INLINE_LIMIT;
+pub use crate::export::export_config;
+pub use crate::export::params_reference;
+pub use crate::export::ConfigFormat;
+pub use crate::export::ExportOptions;
+pub use crate::layers::layers;
+pub use crate::layers::resolve;
+pub use crate::layers::simulate;
+pub use crate::layers::Layer;
+pub use crate::layers::Resolution;
+pub use crate::layers::SimulatedChange;
+pub use crate::logging::log_level;
+pub use crate::logging::LOG_LEVEL_PREFIX;
+pub use crate::merge::Conflict;
+pub use crate::merge::ConflictPolicy;
+pub use crate::merge::MergePolicy;
+pub use crate::mode::modes;
+pub use crate::mode::set_mode;
+pub use crate::mode::set_prefix_mode;
+pub use crate::mode::Mode;
+pub use crate::profiles::Profiles;
+pub use crate::provenance::with_source;
+#[cfg(feature = "provenance")]
+pub use crate::provenance::Provenance;
+pub use crate::provenance::Source;
+pub use crate::reads::dump_recent_reads;
+pub use crate::reads::install_read_history_panic_hook;
+pub use crate::reads::recent_reads;
+pub use crate::reads::set_read_history;
+pub use crate::reads::ParamRead;
+pub use crate::snapshot::ParamSnapshot;
+pub use crate::snapshot::SNAPSHOT_VERSION;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::spawn::spawn_with_params;
+#[doc(hidden)]
+pub use crate::spawn::EnteredScope;
+pub use crate::storage::guardrail_stats;
+pub use crate::storage::key_matches;
+pub use crate::storage::set_history_limit;
+pub use crate::storage::set_text_limit;
+pub use crate::storage::set_user_defined_limit;
+pub use crate::storage::DetachedStorage;
 pub use crate::storage::GetOrElse;
+pub use crate::storage::GuardrailStats;
+pub use crate::storage::MissingParamError;
+pub use crate::storage::Storage;
 pub use crate::storage::THREAD_STORAGE;
+pub use crate::strict::set_strict;
+pub use crate::subscribe::subscribe;
+pub use crate::subscribe::Subscription;
+pub use crate::tags::key_tags;
+pub use crate::tags::keys_with_tag;
+pub use crate::tags::tag_key;
+pub use crate::task::InScope;
+pub use crate::task::Scoped;
+pub use crate::ticket::set_ticket_timeout;
+pub use crate::ticket::ScopeTicket;
+pub use crate::ticket::TicketGuard;
+pub use crate::units::ByteSize;
+pub use crate::validate::ValidationError;
+pub use crate::validate::Validators;
+pub use crate::value::conversion_table;
+pub use crate::value::Coercion;
 pub use crate::value::Value;
+pub use crate::value::ValueError;
+pub use crate::value::ValueKind;
+pub use crate::watch::ConfigChange;
+pub use crate::watch::ConfigWatcher;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::watch::WatcherHandle;
+pub use crate::watch::DEFAULT_WATCH_INTERVAL;
+pub use crate::xxh::key_depth;
+pub use crate::xxh::key_hierarchy_hashes;
+pub use crate::xxh::verify_hash_consistency;
 pub use crate::xxh::xxhash;
 pub use crate::xxh::XXHashable;
+pub use crate::xxh::HASH_SEED;
 pub use const_str;
+#[cfg(any(feature = "registry", feature = "defaults"))]
+#[doc(hidden)]
+pub use linkme;
 pub use xxhash_rust;
 
+#[cfg(feature = "tracking")]
+mod tracking;
+#[cfg(feature = "tracking")]
+pub use crate::tracking::key_stats;
+#[cfg(feature = "tracking")]
+pub use crate::tracking::reset_key_stats;
+#[cfg(feature = "tracking")]
+pub use crate::tracking::set_key_stats;
+#[cfg(feature = "tracking")]
+pub use crate::tracking::KeyStats;
+#[cfg(feature = "tracking")]
+pub use crate::tracking::UsageReport;
+#[cfg(feature = "tracking")]
+pub use crate::tracking::MAX_MISSING_KEYS;
+
+#[cfg(feature = "chaos")]
+pub mod chaos;
+
+#[cfg(feature = "serde")]
+mod serialize;
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+mod formats;
+
+#[cfg(feature = "tracing")]
+pub use crate::logging::log_current_params;
+#[cfg(feature = "tracing")]
+pub use crate::logging::ScopedLevelFilter;
+
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "rayon")]
+pub use crate::parallel::WithCurrentParams;
+#[cfg(feature = "rayon")]
+pub use crate::parallel::WithParams;
+
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wasm")]
+pub use crate::wasm::WasmParamScope;
+
+#[cfg(all(feature = "debug-http", not(target_arch = "wasm32")))]
+mod debug_http;
+#[cfg(all(feature = "debug-http", not(target_arch = "wasm32")))]
+pub use crate::debug_http::serve_debug_http;
+#[cfg(all(feature = "debug-http", not(target_arch = "wasm32")))]
+pub use crate::debug_http::DebugServer;
+
+#[cfg(feature = "prometheus")]
+mod prometheus;
+#[cfg(feature = "prometheus")]
+pub use crate::prometheus::prometheus_metrics;
+
+#[cfg(feature = "defaults")]
+mod defaults;
+#[cfg(feature = "defaults")]
+pub use crate::defaults::library_defaults;
+#[cfg(feature = "defaults")]
+pub use crate::defaults::LIBRARY_DEFAULTS;
+
+#[cfg(feature = "hardware")]
+mod hardware;
+#[cfg(feature = "hardware")]
+pub use crate::hardware::CpuProbe;
+#[cfg(feature = "hardware")]
+pub use crate::hardware::GpuProbe;
+#[cfg(feature = "hardware")]
+pub use crate::hardware::HardwareProbe;
+#[cfg(feature = "hardware")]
+pub use crate::hardware::MemoryProbe;
+#[cfg(feature = "hardware")]
+pub use crate::hardware::HARDWARE_PROBES;
+#[cfg(feature = "hardware")]
+pub use crate::hardware::HW_PREFIX;
+
+#[cfg(feature = "registry")]
+mod registry;
+#[cfg(feature = "registry")]
+pub use crate::registry::registered_params;
+#[cfg(feature = "registry")]
+pub use crate::registry::verify_keys;
+#[cfg(feature = "registry")]
+pub use crate::registry::ParamInfo;
+#[cfg(feature = "registry")]
+pub use crate::registry::PARAM_REGISTRY;
+#[cfg(feature = "registry")]
+pub use crate::registry::SET_PARAM_REGISTRY;
+
 #[cfg(feature = "clap")]
 mod cli;
 #[cfg(feature = "clap")]
+pub use crate::cli::define_arg;
+#[cfg(feature = "clap")]
 pub use crate::cli::generate_params_help;
 #[cfg(feature = "clap")]
-pub use crate::cli::PARAMS;
\ No newline at end of file
+pub use crate::cli::ParamsArgs;
+#[cfg(feature = "clap")]
+pub use crate::cli::DEFINE_ARG;
+#[cfg(feature = "clap")]
+pub use crate::cli::PARAMS;
@@ -0,0 +1,247 @@
+use crate::api::ParamScope;
+#[cfg(feature = "tracing")]
+use crate::storage::Params;
+#[cfg(feature = "tracing")]
+use crate::value::Value;
+
+/// Key prefix of the per-target log levels.
+pub const LOG_LEVEL_PREFIX: &str = "log.level";
+
+/// Find the log level configured for `target` in the current scope.
+///
+/// A target such as `my_crate::net` reads `log.level.my_crate.net`, then
+/// `log.level.my_crate` and finally `log.level`, returning the first one set.
+pub fn log_level(target: &str) -> Option<String> {
+    let mut key = format!("{}.{}", LOG_LEVEL_PREFIX, target.replace("::", "."));
+    loop {
+        if let Some(level) = ParamScope::Nothing.try_get(key.as_str()) {
+            return level.try_into().ok();
+        }
+        if key.len() == LOG_LEVEL_PREFIX.len() {
+            return None;
+        }
+        key.truncate(key.rfind('.').unwrap());
+    }
+}
+
+/// A `tracing_subscriber` filter driven by the `log.level.<target>` parameters.
+///
+/// The levels are read on every event, so entering a scope that sets
+/// `log.level.my_module = "debug"` raises verbosity for that scope only,
+/// including tasks that run with the scope captured.
+///
+/// ```ignore
+/// use hyperparameter::*;
+/// use tracing_subscriber::filter::LevelFilter;
+/// use tracing_subscriber::prelude::*;
+///
+/// tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer().with_filter(ScopedLevelFilter::new(LevelFilter::INFO)))
+///     .init();
+///
+/// with_params! {
+///     set log.level.my_crate.net = "debug".to_string();
+///
+///     tracing::debug!(target: "my_crate::net", "only logged in this scope");
+/// }
+/// ```
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy)]
+pub struct ScopedLevelFilter {
+    default: tracing_subscriber::filter::LevelFilter,
+}
+
+#[cfg(feature = "tracing")]
+impl ScopedLevelFilter {
+    /// Create a filter using `default` for targets without a configured level.
+    pub fn new(default: tracing_subscriber::filter::LevelFilter) -> Self {
+        ScopedLevelFilter { default }
+    }
+
+    fn level_filter(&self, target: &str) -> tracing_subscriber::filter::LevelFilter {
+        log_level(target)
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(self.default)
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<S> tracing_subscriber::layer::Filter<S> for ScopedLevelFilter {
+    fn enabled(
+        &self,
+        meta: &tracing::Metadata<'_>,
+        _: &tracing_subscriber::layer::Context<'_, S>,
+    ) -> bool {
+        meta.level() <= &self.level_filter(meta.target())
+    }
+
+    fn callsite_enabled(
+        &self,
+        _: &'static tracing::Metadata<'static>,
+    ) -> tracing::subscriber::Interest {
+        // the answer depends on the current scope, so ask again for every event
+        tracing::subscriber::Interest::sometimes()
+    }
+}
+
+/// Format parameters as `key = value` pairs ordered by key.
+#[cfg(feature = "tracing")]
+fn format_params<'a>(params: impl Iterator<Item = (&'a str, &'a Value)>) -> String {
+    let mut params: Vec<_> = params.collect();
+    params.sort_by_key(|(k, _)| *k);
+    params
+        .iter()
+        .map(|(k, v)| format!("{} = {:?}", k, v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Report the parameters set by a scope being entered.
+///
+/// Emits a `debug` event with target `hyperparameter`, and records the same
+/// text in the `params` field of the current span if it declares one, e.g.
+/// `tracing::info_span!("step", params = tracing::field::Empty)`. Must not be
+/// called with the thread storage borrowed, since filters such as
+/// [`ScopedLevelFilter`] read parameters.
+#[cfg(feature = "tracing")]
+pub(crate) fn trace_scope_enter(changes: &Params) {
+    if changes.is_empty() {
+        return;
+    }
+    let span = tracing::Span::current();
+    let record = span.has_field("params");
+    let event = tracing::enabled!(target: "hyperparameter", tracing::Level::DEBUG);
+    if !record && !event {
+        return;
+    }
+    let params = format_params(changes.values().map(|e| (&*e.key, e.value())));
+    if record {
+        span.record("params", params.as_str());
+    }
+    if event {
+        tracing::debug!(target: "hyperparameter", params = %params, "enter param scope");
+    }
+}
+
+/// Emit an event with every parameter visible in the current scope.
+///
+/// The event has target `hyperparameter` and lists the parameters in its
+/// `params` field, which tells which values a request or a training step
+/// actually used.
+///
+/// ```ignore
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set train.lr = 0.01;
+///
+///     log_current_params(tracing::Level::INFO);
+/// }
+/// ```
+#[cfg(feature = "tracing")]
+pub fn log_current_params(level: tracing::Level) {
+    let visible = ParamScope::Nothing.namespace("");
+    let params = format_params(visible.iter().map(|(k, v)| (k.as_str(), v)));
+    if level == tracing::Level::ERROR {
+        tracing::error!(target: "hyperparameter", params = %params, "current params");
+    } else if level == tracing::Level::WARN {
+        tracing::warn!(target: "hyperparameter", params = %params, "current params");
+    } else if level == tracing::Level::INFO {
+        tracing::info!(target: "hyperparameter", params = %params, "current params");
+    } else if level == tracing::Level::DEBUG {
+        tracing::debug!(target: "hyperparameter", params = %params, "current params");
+    } else {
+        tracing::trace!(target: "hyperparameter", params = %params, "current params");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::with_params;
+
+    use super::log_level;
+
+    #[test]
+    fn test_log_level() {
+        assert_eq!(None, log_level("logging_test::net"));
+        with_params! {
+            set log.level.logging_test = "info".to_string();
+
+            assert_eq!(Some("info".to_string()), log_level("logging_test::net"));
+            with_params! {
+                set log.level.logging_test.net = "debug".to_string();
+
+                assert_eq!(Some("debug".to_string()), log_level("logging_test::net"));
+                assert_eq!(Some("info".to_string()), log_level("logging_test::db"));
+            }
+            assert_eq!(Some("info".to_string()), log_level("logging_test::net"));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_scoped_level_filter() {
+        use tracing::level_filters::LevelFilter;
+
+        let filter = super::ScopedLevelFilter::new(LevelFilter::INFO);
+        assert_eq!(LevelFilter::INFO, filter.level_filter("logging_filter"));
+        with_params! {
+            set log.level.logging_filter = "trace".to_string();
+
+            assert_eq!(LevelFilter::TRACE, filter.level_filter("logging_filter::io"));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_trace_scope_enter_with_scoped_filter() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        use tracing::level_filters::LevelFilter;
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        struct Count(Arc<AtomicUsize>);
+
+        impl<S: tracing::Subscriber> Layer<S> for Count {
+            fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+                if event.metadata().target() == "hyperparameter" {
+                    self.0.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }
+
+        let events = Arc::new(AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry().with(
+            Count(events.clone()).with_filter(super::ScopedLevelFilter::new(LevelFilter::INFO)),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            with_params! {
+                set logging.trace.a = 1;
+
+                with_params! {
+                    set log.level.hyperparameter = "debug".to_string();
+
+                    with_params! {
+                        set logging.trace.b = 2;
+                    }
+                }
+            }
+        });
+        // the scope setting `log.level.hyperparameter` and its child
+        assert_eq!(2, events.load(Ordering::SeqCst));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_format_params() {
+        use crate::value::Value;
+
+        let lr = Value::from(0.1);
+        let layers = Value::from(4);
+        assert_eq!(
+            "model.layers = Int(4), train.lr = Float(0.1)",
+            super::format_params([("train.lr", &lr), ("model.layers", &layers)].into_iter())
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use crate::api::ParamScope;
+use crate::storage::{Entry, Params};
+use crate::value::Value;
+use crate::xxh::XXHashable;
+
+/// How to resolve a key that is set to different values on both sides of a merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Fail the merge.
+    Error,
+    /// Keep the value of the scope being merged into.
+    KeepFirst,
+    /// Keep the value of the scope being merged in.
+    KeepLast,
+    /// Keep the larger number; non-numeric values fail the merge.
+    Max,
+    /// Keep the smaller number; non-numeric values fail the merge.
+    Min,
+}
+
+/// Conflict policies for [`ParamScope::overlay_with`], per key with a default.
+#[derive(Debug, Clone)]
+pub struct MergePolicy {
+    default: ConflictPolicy,
+    keys: HashMap<u64, ConflictPolicy>,
+}
+
+impl Default for MergePolicy {
+    fn default() -> Self {
+        MergePolicy::new(ConflictPolicy::KeepLast)
+    }
+}
+
+impl MergePolicy {
+    pub fn new(default: ConflictPolicy) -> Self {
+        MergePolicy {
+            default,
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Use `policy` for conflicts on `key`.
+    pub fn key<K: XXHashable>(mut self, key: K, policy: ConflictPolicy) -> Self {
+        self.keys.insert(key.xxh(), policy);
+        self
+    }
+
+    pub fn policy<K: XXHashable>(&self, key: K) -> ConflictPolicy {
+        self.policy_with_hash(key.xxh())
+    }
+
+    /// The policy for a hashed key, which also covers entries written by hash only.
+    pub fn policy_with_hash(&self, hkey: u64) -> ConflictPolicy {
+        self.keys.get(&hkey).copied().unwrap_or(self.default)
+    }
+}
+
+/// A key set to different values by both scopes of a merge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub key: String,
+    pub first: Value,
+    pub last: Value,
+    pub policy: ConflictPolicy,
+    /// The value kept, or `None` if the policy failed the merge.
+    pub resolved: Option<Value>,
+}
+
+fn resolve(first: &Value, last: &Value, policy: ConflictPolicy) -> Option<Value> {
+    let number = |v: &Value| match v {
+        Value::Int(v) => Some(*v as f64),
+        Value::Float(v) => Some(*v),
+        _ => None,
+    };
+    let pick = |keep_first: bool| Some(if keep_first { first } else { last }.clone());
+    match policy {
+        ConflictPolicy::Error => None,
+        ConflictPolicy::KeepFirst => pick(true),
+        ConflictPolicy::KeepLast => pick(false),
+        ConflictPolicy::Max => pick(number(first)? >= number(last)?),
+        ConflictPolicy::Min => pick(number(first)? <= number(last)?),
+    }
+}
+
+impl ParamScope {
+    /// Merge the parameters of `other` into this scope object.
+    ///
+    /// Keys set to different values on both sides are resolved with `policy`,
+    /// and every such conflict is reported. If any conflict can not be
+    /// resolved, nothing is merged and all conflicts are returned as the error.
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// let mut team_a = ParamScope::default();
+    /// team_a.put("train.lr", 0.1);
+    /// team_a.put("train.batch", 32);
+    /// let mut team_b = ParamScope::default();
+    /// team_b.put("train.lr", 0.2);
+    /// team_b.put("train.batch", 64);
+    ///
+    /// let policy = MergePolicy::new(ConflictPolicy::Error).key("train.batch", ConflictPolicy::Max);
+    /// let conflicts = team_a.clone().overlay_with(&team_b, &policy).unwrap_err();
+    /// assert_eq!(2, conflicts.len());
+    ///
+    /// let policy = policy.key("train.lr", ConflictPolicy::KeepFirst);
+    /// team_a.overlay_with(&team_b, &policy).unwrap();
+    /// assert_eq!(0.1, team_a.get_or_else("train.lr", 0.0));
+    /// assert_eq!(64, team_a.get_or_else("train.batch", 0));
+    /// ```
+    pub fn overlay_with(
+        &mut self,
+        other: &ParamScope,
+        policy: &MergePolicy,
+    ) -> Result<Vec<Conflict>, Vec<Conflict>> {
+        let theirs = match other {
+            ParamScope::Just(params) => params,
+            ParamScope::Nothing => return Ok(Vec::new()),
+        };
+        let mut merged = match self {
+            ParamScope::Just(params) => params.clone(),
+            ParamScope::Nothing => Params::default(),
+        };
+        let mut conflicts = Vec::new();
+        for (hkey, e) in theirs.iter() {
+            let last = e.value();
+            if matches!(last, Value::Empty) {
+                continue;
+            }
+            // either side may have been written by hash only, without a name
+            let key = match merged.get(hkey) {
+                Some(mine) if e.key.is_empty() => mine.key.clone(),
+                _ => e.key.clone(),
+            };
+            let first = match merged.get(hkey).map(|e| e.value()) {
+                Some(first) if !matches!(first, Value::Empty) && !first.same(last) => first.clone(),
+                _ => {
+                    merged.insert(*hkey, Entry::new(key, last.clone()));
+                    continue;
+                }
+            };
+            let p = policy.policy_with_hash(*hkey);
+            let resolved = resolve(&first, last, p);
+            if let Some(v) = &resolved {
+                merged.insert(*hkey, Entry::new(key.clone(), v.clone()));
+            }
+            conflicts.push(Conflict {
+                key: key.to_string(),
+                first,
+                last: last.clone(),
+                policy: p,
+                resolved,
+            });
+        }
+        if conflicts.iter().any(|c| c.resolved.is_none()) {
+            return Err(conflicts);
+        }
+        *self = ParamScope::Just(merged);
+        Ok(conflicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_overlay_with_policies() {
+        let mut a = ParamScope::default();
+        a.put("merge.lr", 0.1);
+        a.put("merge.steps", 100);
+        a.put("merge.name", "a".to_string());
+        a.put("merge.same", 1);
+        let mut b = ParamScope::default();
+        b.put("merge.lr", 0.2);
+        b.put("merge.steps", 50);
+        b.put("merge.name", "b".to_string());
+        b.put("merge.same", 1);
+        b.put("merge.extra", true);
+
+        let policy = MergePolicy::default()
+            .key("merge.lr", ConflictPolicy::Min)
+            .key("merge.steps", ConflictPolicy::Max);
+        let mut merged = a.clone();
+        let conflicts = merged.overlay_with(&b, &policy).unwrap();
+        assert_eq!(3, conflicts.len());
+        assert_eq!(0.1, merged.get_or_else("merge.lr", 0.0));
+        assert_eq!(100, merged.get_or_else("merge.steps", 0));
+        assert_eq!("b", merged.get_or_else("merge.name", String::new()));
+        assert!(merged.get_or_else("merge.extra", false));
+
+        let policy = policy.key("merge.name", ConflictPolicy::Max);
+        let mut failed = a.clone();
+        let conflicts = failed.overlay_with(&b, &policy).unwrap_err();
+        let name = conflicts.iter().find(|c| c.key == "merge.name").unwrap();
+        assert_eq!(None, name.resolved);
+        assert_eq!(Value::Empty, failed.get("merge.extra"));
+    }
+
+    #[test]
+    fn test_overlay_with_hash_only_entries() {
+        use crate::storage::{Entry, Params};
+        use crate::xxh::XXHashable;
+
+        let mut a = ParamScope::default();
+        a.put("merge.hash.steps", 100);
+        let b = ParamScope::Just(Params::from_iter([(
+            "merge.hash.steps".xxh(),
+            Entry::new("", 50),
+        )]));
+
+        let policy =
+            MergePolicy::new(ConflictPolicy::Error).key("merge.hash.steps", ConflictPolicy::Max);
+        let conflicts = a.overlay_with(&b, &policy).unwrap();
+        assert_eq!(1, conflicts.len());
+        assert_eq!("merge.hash.steps", conflicts[0].key);
+        assert_eq!(100, a.get_or_else("merge.hash.steps", 0));
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+
+/// How parameter reads are answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Reads return stored values.
+    Normal,
+    /// Reads ignore stored values and return the caller's default.
+    DefaultsOnly,
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::DefaultsOnly => "defaults_only",
+        }
+    }
+}
+
+impl TryFrom<&str> for Mode {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "normal" => Ok(Mode::Normal),
+            "defaults_only" => Ok(Mode::DefaultsOnly),
+            _ => Err(format!("unknown mode `{}`", value)),
+        }
+    }
+}
+
+struct Modes {
+    global: Mode,
+    prefixes: Vec<(String, Mode)>,
+}
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref MODES: RwLock<Modes> = RwLock::new(Modes {
+        global: Mode::Normal,
+        prefixes: Vec::new(),
+    });
+}
+
+fn update(f: impl FnOnce(&mut Modes)) {
+    let mut modes = MODES.write().unwrap();
+    f(&mut modes);
+    let active =
+        modes.global != Mode::Normal || modes.prefixes.iter().any(|(_, m)| *m != Mode::Normal);
+    ACTIVE.store(active, Ordering::Release);
+}
+
+/// Set the read mode of all parameters without a prefix mode.
+///
+/// `Mode::DefaultsOnly` makes every read return the provided default, which
+/// helps bisecting whether an incident is driven by configuration.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set doc.mode.lr = 0.5;
+///
+///     set_mode(Mode::DefaultsOnly);
+///     assert_eq!(0.1, get_param!(doc.mode.lr, 0.1));
+///     set_mode(Mode::Normal);
+///     assert_eq!(0.5, get_param!(doc.mode.lr, 0.1));
+/// }
+/// ```
+pub fn set_mode(mode: Mode) {
+    update(|m| m.global = mode);
+}
+
+/// Set the read mode of keys starting with `prefix`; `None` removes it.
+///
+/// The longest matching prefix takes precedence over shorter ones and over
+/// the global mode.
+pub fn set_prefix_mode<P: Into<String>>(prefix: P, mode: Option<Mode>) {
+    let prefix = prefix.into();
+    update(|m| {
+        m.prefixes.retain(|(p, _)| *p != prefix);
+        if let Some(mode) = mode {
+            m.prefixes.push((prefix, mode));
+        }
+    });
+}
+
+/// The active modes: the global mode under the empty prefix, then prefix modes.
+pub fn modes() -> Vec<(String, Mode)> {
+    let modes = MODES.read().unwrap();
+    let mut all = vec![(String::new(), modes.global)];
+    all.extend(modes.prefixes.iter().cloned());
+    all
+}
+
+/// Whether reads of `key` must ignore stored values.
+#[inline]
+pub(crate) fn defaults_only(key: &str) -> bool {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return false;
+    }
+    let modes = MODES.read().unwrap();
+    let mode = modes
+        .prefixes
+        .iter()
+        .filter(|(p, _)| key.starts_with(p.as_str()))
+        .max_by_key(|(p, _)| p.len())
+        .map_or(modes.global, |(_, m)| *m);
+    mode == Mode::DefaultsOnly
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_prefix_mode() {
+        with_params! {
+            set mode.test.db.host = "db".to_string();
+            set mode.test.db.pool.size = 8;
+            set mode.test.lr = 0.5;
+
+            set_prefix_mode("mode.test.db.", Some(Mode::DefaultsOnly));
+            set_prefix_mode("mode.test.db.pool.", Some(Mode::Normal));
+            assert_eq!("local", get_param!(mode.test.db.host, "local".to_string()));
+            assert_eq!(8, get_param!(mode.test.db.pool.size, 1));
+            assert_eq!(0.5, get_param!(mode.test.lr, 0.1));
+            assert_eq!(None, ParamScope::Nothing.try_get("mode.test.db.host"));
+            assert!(modes().contains(&("mode.test.db.".to_string(), Mode::DefaultsOnly)));
+
+            set_prefix_mode("mode.test.db.", None);
+            set_prefix_mode("mode.test.db.pool.", None);
+            assert_eq!("db", get_param!(mode.test.db.host, "local".to_string()));
+        }
+    }
+}
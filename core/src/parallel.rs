@@ -0,0 +1,291 @@
+//! Rayon adapters that carry the parameters of the calling thread into the
+//! worker threads of a parallel iterator.
+
+use std::sync::Arc;
+
+use rayon::iter::plumbing::{Consumer, Folder, Producer, ProducerCallback, UnindexedConsumer};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+use crate::api::ParamScope;
+use crate::spawn::EnteredScope;
+
+/// Extension trait for rayon parallel iterators.
+///
+/// [`with_current_params`](WithCurrentParams::with_current_params) captures
+/// the parameters visible at the call, and every worker enters them while it
+/// processes its share of the items:
+///
+/// ```
+/// use hyperparameter::*;
+/// use rayon::prelude::*;
+///
+/// with_params! {
+///     set preprocess.scale = 10;
+///
+///     let scaled: Vec<i64> = vec![1, 2, 3]
+///         .par_iter()
+///         .with_current_params()
+///         .map(|x| x * get_param!(preprocess.scale, 1))
+///         .collect();
+///     assert_eq!(vec![10, 20, 30], scaled);
+/// }
+/// ```
+///
+/// The scope is exited once the worker is done, so pooled threads keep no
+/// parameters from one job to the next.
+pub trait WithCurrentParams: ParallelIterator {
+    fn with_current_params(self) -> WithParams<Self> {
+        WithParams {
+            base: self,
+            scope: Arc::new(ParamScope::capture()),
+        }
+    }
+}
+
+impl<I: ParallelIterator> WithCurrentParams for I {}
+
+/// Parallel iterator returned by [`WithCurrentParams::with_current_params`].
+pub struct WithParams<I> {
+    base: I,
+    scope: Arc<ParamScope>,
+}
+
+fn enter(scope: &ParamScope) -> EnteredScope {
+    EnteredScope::enter(scope.clone())
+}
+
+impl<I: ParallelIterator> ParallelIterator for WithParams<I> {
+    type Item = I::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.base.drive_unindexed(ParamsConsumer {
+            base: consumer,
+            scope: self.scope,
+        })
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.base.opt_len()
+    }
+}
+
+impl<I: IndexedParallelIterator> IndexedParallelIterator for WithParams<I> {
+    fn len(&self) -> usize {
+        self.base.len()
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        self.base.drive(ParamsConsumer {
+            base: consumer,
+            scope: self.scope,
+        })
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        return self.base.with_producer(Callback {
+            callback,
+            scope: self.scope,
+        });
+
+        struct Callback<CB> {
+            callback: CB,
+            scope: Arc<ParamScope>,
+        }
+
+        impl<T, CB: ProducerCallback<T>> ProducerCallback<T> for Callback<CB> {
+            type Output = CB::Output;
+
+            fn callback<P: Producer<Item = T>>(self, base: P) -> CB::Output {
+                self.callback.callback(ParamsProducer {
+                    base,
+                    scope: self.scope,
+                })
+            }
+        }
+    }
+}
+
+/// Enters the scope around every folder made from the consumer.
+struct ParamsConsumer<C> {
+    base: C,
+    scope: Arc<ParamScope>,
+}
+
+impl<T, C: Consumer<T>> Consumer<T> for ParamsConsumer<C> {
+    type Folder = ParamsFolder<C::Folder>;
+    type Reducer = C::Reducer;
+    type Result = C::Result;
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+        let left = ParamsConsumer {
+            base: left,
+            scope: self.scope.clone(),
+        };
+        let right = ParamsConsumer {
+            base: right,
+            scope: self.scope,
+        };
+        (left, right, reducer)
+    }
+
+    fn into_folder(self) -> Self::Folder {
+        let entered = enter(&self.scope);
+        ParamsFolder {
+            base: self.base.into_folder(),
+            _entered: entered,
+        }
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+impl<T, C: UnindexedConsumer<T>> UnindexedConsumer<T> for ParamsConsumer<C> {
+    fn split_off_left(&self) -> Self {
+        ParamsConsumer {
+            base: self.base.split_off_left(),
+            scope: self.scope.clone(),
+        }
+    }
+
+    fn to_reducer(&self) -> Self::Reducer {
+        self.base.to_reducer()
+    }
+}
+
+/// Keeps the scope entered until the folder completes.
+struct ParamsFolder<F> {
+    base: F,
+    _entered: EnteredScope,
+}
+
+impl<T, F: Folder<T>> Folder<T> for ParamsFolder<F> {
+    type Result = F::Result;
+
+    fn consume(self, item: T) -> Self {
+        ParamsFolder {
+            base: self.base.consume(item),
+            _entered: self._entered,
+        }
+    }
+
+    fn consume_iter<It: IntoIterator<Item = T>>(self, iter: It) -> Self {
+        ParamsFolder {
+            base: self.base.consume_iter(iter),
+            _entered: self._entered,
+        }
+    }
+
+    fn complete(self) -> F::Result {
+        self.base.complete()
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+/// Enters the scope while the items of the producer are iterated.
+struct ParamsProducer<P> {
+    base: P,
+    scope: Arc<ParamScope>,
+}
+
+impl<P: Producer> Producer for ParamsProducer<P> {
+    type Item = P::Item;
+    type IntoIter = ParamsIter<P::IntoIter>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let entered = enter(&self.scope);
+        ParamsIter {
+            base: self.base.into_iter(),
+            _entered: entered,
+        }
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let (left, right) = self.base.split_at(index);
+        let left = ParamsProducer {
+            base: left,
+            scope: self.scope.clone(),
+        };
+        let right = ParamsProducer {
+            base: right,
+            scope: self.scope,
+        };
+        (left, right)
+    }
+
+    fn fold_with<F: Folder<Self::Item>>(self, folder: F) -> F {
+        let _entered = enter(&self.scope);
+        self.base.fold_with(folder)
+    }
+}
+
+struct ParamsIter<I> {
+    base: I,
+    _entered: EnteredScope,
+}
+
+impl<I: Iterator> Iterator for ParamsIter<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.base.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.base.size_hint()
+    }
+}
+
+impl<I: DoubleEndedIterator> DoubleEndedIterator for ParamsIter<I> {
+    fn next_back(&mut self) -> Option<I::Item> {
+        self.base.next_back()
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for ParamsIter<I> {}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+
+    use crate::*;
+
+    #[test]
+    fn test_with_current_params() {
+        with_params! {
+            set parallel.offset = 100;
+
+            let items: Vec<i64> = (0..8).collect();
+            let shifted: Vec<i64> = items
+                .par_iter()
+                .with_current_params()
+                .map(|x| x + get_param!(parallel.offset, 0))
+                .collect();
+            assert_eq!((100..108).collect::<Vec<i64>>(), shifted);
+
+            let indexed: Vec<(usize, i64)> = items
+                .into_par_iter()
+                .with_current_params()
+                .enumerate()
+                .map(|(i, _)| (i, get_param!(parallel.offset, 0)))
+                .collect();
+            assert_eq!((0..8).map(|i| (i, 100)).collect::<Vec<_>>(), indexed);
+        }
+    }
+}
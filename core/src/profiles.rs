@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use crate::api::ParamScope;
+use crate::storage::Params;
+use crate::value::Value;
+use crate::xxh::XXHashable;
+
+/// Named parameter scopes merged in declared precedence order, later layers
+/// overriding earlier ones.
+///
+/// This replaces nesting one `with_params!` per configuration source, and
+/// tells which layer supplied each key:
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let mut defaults = ParamScope::default();
+/// defaults.put("train.lr", 0.1);
+/// defaults.put("train.epochs", 10);
+/// let file = ParamScope::from_toml_str("[train]\nepochs = 20\n").unwrap();
+/// let mut cli = ParamScope::default();
+/// cli.put("train.lr", 0.01);
+///
+/// let profiles = Profiles::new()
+///     .layer("defaults", defaults)
+///     .layer("file", file)
+///     .layer("env", ParamScope::from_env("DOC_PROFILES_"))
+///     .layer("cli", cli);
+///
+/// let mut ps = profiles.build();
+/// with_params! {
+///     params ps;
+///
+///     assert_eq!(0.01, get_param!(train.lr, 0.0));
+///     assert_eq!(20, get_param!(train.epochs, 0));
+/// }
+/// assert_eq!(Some("cli"), profiles.supplier("train.lr"));
+/// assert_eq!(Some("file"), profiles.supplier("train.epochs"));
+/// assert_eq!(None, profiles.supplier("train.warmup"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Profiles {
+    layers: Vec<(String, ParamScope)>,
+}
+
+impl Profiles {
+    pub fn new() -> Self {
+        Profiles::default()
+    }
+
+    /// Add a layer taking precedence over every layer added before.
+    pub fn layer<N: Into<String>>(mut self, name: N, scope: ParamScope) -> Self {
+        self.layers.push((name.into(), scope));
+        self
+    }
+
+    /// The names of the layers, lowest precedence first.
+    pub fn layer_names(&self) -> Vec<&str> {
+        self.layers.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Merge the layers into a single scope.
+    ///
+    /// A key hidden by a layer with [`ParamScope::hide`] stays hidden unless
+    /// a later layer sets it again.
+    pub fn build(&self) -> ParamScope {
+        let mut merged = Params::default();
+        for (_, scope) in self.layers.iter() {
+            if let ParamScope::Just(params) = scope {
+                for (hkey, e) in params.iter() {
+                    merged.insert(*hkey, e.shallow());
+                }
+            }
+        }
+        ParamScope::Just(merged)
+    }
+
+    /// The name of the layer that supplies `key` in [`Profiles::build`],
+    /// `None` if no layer sets it or the last layer mentioning it hides it.
+    pub fn supplier<K: XXHashable>(&self, key: K) -> Option<&str> {
+        let hkey = key.xxh();
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|(name, scope)| match scope {
+                ParamScope::Just(params) => params.get(&hkey).map(|e| (name, e.value())),
+                ParamScope::Nothing => None,
+            })
+            .filter(|(_, val)| !matches!(val, Value::Empty))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The layer supplying each key of [`Profiles::build`], by key.
+    pub fn suppliers(&self) -> BTreeMap<String, &str> {
+        let mut suppliers = BTreeMap::new();
+        for (name, scope) in self.layers.iter() {
+            if let ParamScope::Just(params) = scope {
+                for e in params.values().filter(|e| !e.key.is_empty()) {
+                    match e.value() {
+                        Value::Empty => suppliers.remove(&*e.key),
+                        _ => suppliers.insert(e.key.to_string(), name.as_str()),
+                    };
+                }
+            }
+        }
+        suppliers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_profiles() {
+        let mut base = ParamScope::default();
+        base.put("profiles.lr", 0.1);
+        base.put("profiles.debug", true);
+        base.put("profiles.workers", 1);
+        let mut prod = ParamScope::default();
+        prod.put("profiles.workers", 8);
+        prod.hide("profiles.debug");
+        let mut cli = ParamScope::default();
+        cli.put("profiles.lr", 0.05);
+
+        let profiles = Profiles::new()
+            .layer("base", base)
+            .layer("prod", prod)
+            .layer("cli", cli);
+        assert_eq!(vec!["base", "prod", "cli"], profiles.layer_names());
+
+        let mut ps = profiles.build();
+        assert_eq!(Value::Float(0.05), ps.get("profiles.lr"));
+        assert_eq!(Value::Int(8), ps.get("profiles.workers"));
+        with_params! {
+            params ps;
+
+            assert!(!get_param!(profiles.debug, false));
+        }
+
+        assert_eq!(Some("cli"), profiles.supplier("profiles.lr"));
+        assert_eq!(Some("prod"), profiles.supplier("profiles.workers"));
+        assert_eq!(None, profiles.supplier("profiles.debug"));
+        let suppliers = profiles.suppliers();
+        assert_eq!(2, suppliers.len());
+        assert_eq!(Some(&"prod"), suppliers.get("profiles.workers"));
+    }
+}
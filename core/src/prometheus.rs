@@ -0,0 +1,139 @@
+//! Parameter values and read counters in the Prometheus text format, behind
+//! the `prometheus` feature.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::storage::{frozen_params, Params};
+use crate::value::Value;
+
+/// Render the numeric frozen parameters as `hyperparameter_value` gauges, in
+/// the Prometheus text exposition format.
+///
+/// Booleans are exported as `0` and `1`; text, lists and user-defined values
+/// are left out. With the `tracking` feature, the read and write counters of
+/// [`key_stats`](crate::key_stats) are exported too, as
+/// `hyperparameter_reads_total` and `hyperparameter_writes_total`.
+///
+/// The debug HTTP server serves this at `GET /metrics` when both features are
+/// enabled.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let text = prometheus_metrics();
+/// assert!(text.contains("# TYPE hyperparameter_value gauge"));
+/// ```
+pub fn prometheus_metrics() -> String {
+    render(&frozen_params())
+}
+
+fn render(params: &Params) -> String {
+    let values: BTreeMap<&str, f64> = params
+        .values()
+        .filter_map(|e| {
+            let value = match e.value() {
+                Value::Int(v) => *v as f64,
+                Value::Float(v) => *v,
+                Value::Boolean(v) => *v as i64 as f64,
+                _ => return None,
+            };
+            Some((&*e.key, value))
+        })
+        .collect();
+
+    let mut out = String::new();
+    write_family(
+        &mut out,
+        "hyperparameter_value",
+        "gauge",
+        "Current value of a numeric frozen parameter.",
+        values.into_iter(),
+    );
+
+    #[cfg(feature = "tracking")]
+    {
+        let stats: BTreeMap<String, crate::tracking::KeyStats> =
+            crate::tracking::key_stats().into_iter().collect();
+        write_family(
+            &mut out,
+            "hyperparameter_reads_total",
+            "counter",
+            "Reads of a parameter by any thread.",
+            stats.iter().map(|(k, s)| (k.as_str(), s.reads as f64)),
+        );
+        write_family(
+            &mut out,
+            "hyperparameter_writes_total",
+            "counter",
+            "Writes of a parameter by any thread.",
+            stats.iter().map(|(k, s)| (k.as_str(), s.writes as f64)),
+        );
+    }
+    out
+}
+
+fn write_family<'a>(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    samples: impl Iterator<Item = (&'a str, f64)>,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (key, value) in samples {
+        let _ = writeln!(
+            out,
+            "{}{{key=\"{}\"}} {}",
+            name,
+            escape_label(key),
+            format_sample(value)
+        );
+    }
+}
+
+fn escape_label(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_sample(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_label, format_sample, render};
+    use crate::storage::{Entry, Params};
+    use crate::xxh::XXHashable;
+
+    #[test]
+    fn test_prometheus_metrics() {
+        let params: Params = [
+            Entry::new("prom.lr", 0.5),
+            Entry::new("prom.layers", 4),
+            Entry::new("prom.name", "resnet"),
+        ]
+        .into_iter()
+        .map(|e| ((&*e.key).xxh(), e))
+        .collect();
+        let text = render(&params);
+
+        assert!(text.contains("hyperparameter_value{key=\"prom.lr\"} 0.5\n"));
+        assert!(text.contains("hyperparameter_value{key=\"prom.layers\"} 4\n"));
+        assert!(!text.contains("prom.name"));
+
+        assert_eq!("a\\\"b\\\\c", escape_label("a\"b\\c"));
+        assert_eq!("+Inf", format_sample(f64::INFINITY));
+        assert_eq!("NaN", format_sample(f64::NAN));
+    }
+}
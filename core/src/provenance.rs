@@ -0,0 +1,210 @@
+//! Where parameter values come from.
+//!
+//! Loaders such as [`ParamScope::from_json_str`], [`ParamScope::from_env`] or
+//! the command line parser tag the values they put with a [`Source`]. With
+//! the `provenance` feature, every entry keeps the source of each of its
+//! versions, and [`ParamScope::provenance`] reports them.
+
+use std::cell::RefCell;
+use std::sync::Arc;
+
+#[cfg(feature = "provenance")]
+use crate::api::ParamScope;
+#[cfg(feature = "provenance")]
+use crate::storage::THREAD_STORAGE;
+#[cfg(feature = "provenance")]
+use crate::value::Value;
+#[cfg(feature = "provenance")]
+use crate::xxh::XXHashable;
+
+/// The origin of a parameter value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Source {
+    /// Set by code, with `with_params!` or `put`.
+    Code,
+    /// Read from a configuration document; holds the file path when known,
+    /// else the format, e.g. `json`.
+    File(Arc<str>),
+    /// Read from an environment variable, by name.
+    Env(Arc<str>),
+    /// Defined on the command line with `-D key=value`.
+    Cli,
+    /// Set from Python.
+    Python,
+}
+
+thread_local! {
+    static CURRENT_SOURCE: RefCell<Source> = const { RefCell::new(Source::Code) };
+}
+
+/// Restores the previous source when dropped, also on panic.
+struct SourceGuard(Option<Source>);
+
+impl Drop for SourceGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            CURRENT_SOURCE.with(|s| *s.borrow_mut() = previous);
+        }
+    }
+}
+
+/// Run `f`, tagging the values it puts on this thread with `source`.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let text = r#"{"train": {"lr": 0.01}}"#; // e.g. read from train.json
+/// let ps = with_source(Source::File("train.json".into()), || {
+///     ParamScope::from_json_str(text).unwrap()
+/// });
+/// # let _ = ps;
+/// ```
+///
+/// Without the `provenance` feature the source is not recorded.
+pub fn with_source<R>(source: Source, f: impl FnOnce() -> R) -> R {
+    let _guard = SourceGuard(Some(
+        CURRENT_SOURCE.with(|s| std::mem::replace(&mut *s.borrow_mut(), source)),
+    ));
+    f()
+}
+
+/// Like [`with_source`], unless a caller already set a source other than
+/// [`Source::Code`], which then wins since it knows more, e.g. a file path.
+pub(crate) fn with_default_source<R>(source: Source, f: impl FnOnce() -> R) -> R {
+    if current_source() == Source::Code {
+        with_source(source, f)
+    } else {
+        f()
+    }
+}
+
+/// The source that values put on this thread are tagged with.
+pub(crate) fn current_source() -> Source {
+    CURRENT_SOURCE.with(|s| s.borrow().clone())
+}
+
+/// One version of a parameter, see [`ParamScope::provenance`].
+#[cfg(feature = "provenance")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub value: Value,
+    pub source: Source,
+    /// Nesting depth of the scope that set the value, 0 being the thread's
+    /// root scope and the frozen global storage.
+    pub depth: usize,
+}
+
+#[cfg(feature = "provenance")]
+impl ParamScope {
+    /// Every version of `key` visible from this scope, newest first, with
+    /// where it comes from and the depth of the scope that set it.
+    ///
+    /// The first entry is the effective value. Values set by this scope, if
+    /// it is not entered yet, come first, at one past the current depth.
+    /// Deletions show up as `Value::Empty`.
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// let mut cli = ParamScope::default();
+    /// with_source(Source::Cli, || cli.put("doc.provenance.lr", 0.1));
+    /// with_params! {
+    ///     set doc.provenance.lr = 0.01;
+    ///     with_params! {
+    ///         params cli;
+    ///
+    ///         let chain = ParamScope::Nothing.provenance("doc.provenance.lr");
+    ///         assert_eq!((Source::Cli, 2), (chain[0].source.clone(), chain[0].depth));
+    ///         assert_eq!((Source::Code, 1), (chain[1].source.clone(), chain[1].depth));
+    ///     }
+    /// }
+    /// ```
+    pub fn provenance<K: XXHashable>(&self, key: K) -> Vec<Provenance> {
+        let hkey = key.xxh();
+        THREAD_STORAGE.with(|ts| {
+            let ts = ts.borrow();
+            let mut chain = Vec::new();
+            if let ParamScope::Just(changes) = self {
+                if let Some(e) = changes.get(&hkey) {
+                    chain.push(Provenance {
+                        value: e.clone_value(),
+                        source: e.source().clone(),
+                        depth: ts.depth() + 1,
+                    });
+                }
+            }
+            if let Some(e) = ts.params.get(&hkey) {
+                let mut depths = ts
+                    .history
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .filter(|(_, keys)| keys.contains(&hkey))
+                    .map(|(depth, _)| depth);
+                for (value, source) in e.val.versions().zip(e.sources.iter()) {
+                    chain.push(Provenance {
+                        value: value.clone(),
+                        source: source.clone(),
+                        depth: depths.next().unwrap_or(0),
+                    });
+                }
+            }
+            chain
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{current_source, with_default_source, with_source, Source};
+
+    #[test]
+    fn test_with_source() {
+        assert_eq!(Source::Code, current_source());
+        with_source(Source::Cli, || {
+            assert_eq!(Source::Cli, current_source());
+            with_default_source(Source::Python, || assert_eq!(Source::Cli, current_source()));
+        });
+        with_default_source(Source::Python, || {
+            assert_eq!(Source::Python, current_source())
+        });
+        let _ = std::panic::catch_unwind(|| with_source(Source::Cli, || panic!()));
+        assert_eq!(Source::Code, current_source());
+    }
+
+    #[cfg(feature = "provenance")]
+    #[test]
+    fn test_provenance() {
+        use crate::*;
+
+        let env = with_source(Source::Env("DOC_LR".into()), || {
+            let mut ps = ParamScope::default();
+            ps.put("provenance.lr", 0.5);
+            ps
+        });
+        with_params! {
+            set provenance.lr = 0.1;
+
+            let chain = env.provenance("provenance.lr");
+            assert_eq!(2, chain.len());
+            assert_eq!(Value::Float(0.5), chain[0].value);
+            assert_eq!((Source::Env("DOC_LR".into()), 2), (chain[0].source.clone(), chain[0].depth));
+            assert_eq!((Source::Code, 1), (chain[1].source.clone(), chain[1].depth));
+
+            let mut entered = env.clone();
+            with_params! {
+                params entered;
+
+                assert_eq!(chain, ParamScope::Nothing.provenance("provenance.lr"));
+            }
+        }
+        assert!(ParamScope::Nothing.provenance("provenance.lr").is_empty());
+
+        #[cfg(feature = "json")]
+        {
+            let ps = ParamScope::from_json_str(r#"{"provenance": {"lr": 1}}"#).unwrap();
+            let chain = ps.provenance("provenance.lr");
+            assert_eq!(Source::File("json".into()), chain[0].source);
+        }
+    }
+}
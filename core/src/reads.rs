@@ -0,0 +1,139 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use crate::storage::Entry;
+use crate::value::Value;
+
+/// A parameter read recorded by [`recent_reads`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamRead {
+    /// The key, or its hash in hex if the key was never stored and its name is unknown.
+    pub key: String,
+    /// The stored value, `Value::Empty` if the read fell back to a default.
+    pub value: Value,
+    pub time: SystemTime,
+    /// Nesting depth of the thread scope the read happened in.
+    pub depth: usize,
+}
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static READS: RefCell<VecDeque<ParamRead>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// Keep the last `capacity` parameter reads of each thread, `None` to stop recording.
+///
+/// Recording is off by default.
+pub fn set_read_history(capacity: Option<usize>) {
+    CAPACITY.store(capacity.unwrap_or(0), Ordering::Relaxed);
+    if capacity.is_none() {
+        READS.with(|r| r.borrow_mut().clear());
+    }
+}
+
+/// The time of a read or write. `wasm32-unknown-unknown` has no clock, so
+/// they are stamped with the epoch there.
+pub(crate) fn now() -> SystemTime {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        SystemTime::UNIX_EPOCH
+    } else {
+        SystemTime::now()
+    }
+}
+
+pub(crate) fn record_read(hkey: u64, e: Option<&Entry>, depth: usize) {
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+    let read = ParamRead {
+        key: e
+            .map(|e| e.key.to_string())
+            .unwrap_or_else(|| crate::xxh::describe_key(hkey)),
+        value: e.map(|e| e.clone_value()).unwrap_or(Value::Empty),
+        time: now(),
+        depth,
+    };
+    READS.with(|r| {
+        let mut r = r.borrow_mut();
+        while r.len() >= capacity {
+            r.pop_front();
+        }
+        r.push_back(read);
+    });
+}
+
+/// The parameter reads recorded on the current thread, oldest first.
+pub fn recent_reads() -> Vec<ParamRead> {
+    READS.with(|r| r.borrow().iter().cloned().collect())
+}
+
+/// Format the reads recorded on the current thread, one per line.
+pub fn dump_recent_reads() -> String {
+    let mut out = String::new();
+    for read in recent_reads() {
+        let since = read
+            .time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "{}.{:03} depth={} {} = {:?}",
+            since.as_secs(),
+            since.subsec_millis(),
+            read.depth,
+            read.key,
+            read.value
+        );
+    }
+    out
+}
+
+/// Install a panic hook printing the panicking thread's recent reads to stderr.
+///
+/// The previous hook still runs first.
+pub fn install_read_history_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        let reads = dump_recent_reads();
+        if !reads.is_empty() {
+            eprintln!("recent parameter reads:\n{}", reads);
+        }
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xxh::describe_key;
+    use crate::*;
+
+    #[test]
+    fn test_recent_reads() {
+        // hashing the key at runtime may intern its name, so do it before reading
+        let missing = describe_key("reads.c".xxh());
+        set_read_history(Some(2));
+        with_params! {
+            set reads.a = 1;
+            set reads.b = 2;
+
+            get_param!(reads.a, 0);
+            get_param!(reads.b, 0);
+            get_param!(reads.c, 0);
+
+            let reads = recent_reads();
+            assert_eq!(2, reads.len());
+            assert_eq!("reads.b", reads[0].key);
+            assert_eq!(Value::Int(2), reads[0].value);
+            assert_eq!(1, reads[0].depth);
+            assert_eq!(missing, reads[1].key);
+            assert_eq!(Value::Empty, reads[1].value);
+        }
+        set_read_history(None);
+        assert!(recent_reads().is_empty());
+    }
+}
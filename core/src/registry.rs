@@ -0,0 +1,370 @@
+//! Parameters read by `get_param!`, collected at compile time.
+//!
+//! Every `get_param!` call site, including the `get` statements of
+//! `with_params!`, adds a [`ParamInfo`] to [`PARAM_REGISTRY`]. The registry
+//! lists the parameters a program can read without running it:
+//!
+//! ```
+//! use hyperparameter::*;
+//!
+//! fn batch_size() -> i64 {
+//!     get_param!(doc.registry.batch_size, 32, "Samples per batch")
+//! }
+//!
+//! let info = registered_params()
+//!     .into_iter()
+//!     .find(|p| p.key == "doc.registry.batch_size")
+//!     .unwrap();
+//! assert_eq!("Samples per batch", info.help);
+//! assert_eq!(Some(ValueKind::Int), info.kind());
+//! # assert_eq!(32, batch_size());
+//! ```
+
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+
+use crate::api::ParamScope;
+use crate::strict::closest;
+use crate::validate::ValidationError;
+use crate::value::{Coercion, Value, ValueKind};
+use crate::xxh::xxhash;
+
+/// A parameter read by a `get_param!` call site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamInfo {
+    pub key: &'static str,
+    /// The default as written at the call site.
+    pub default: &'static str,
+    /// The help text, empty if none was given.
+    pub help: &'static str,
+    /// The module of the call site.
+    pub module: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+}
+
+impl ParamInfo {
+    /// The crate of the call site.
+    pub fn crate_name(&self) -> &'static str {
+        self.module.split("::").next().unwrap_or(self.module)
+    }
+
+    /// The kind of the default, if it can be told from how it is written.
+    ///
+    /// Literals are recognized, as are `"..".to_string()` and
+    /// `String::from(..)`; other expressions give `None`.
+    pub fn kind(&self) -> Option<ValueKind> {
+        let src: String = self.default.split_whitespace().collect();
+        let src = src.strip_prefix('-').unwrap_or(&src);
+        if src == "true" || src == "false" {
+            return Some(ValueKind::Boolean);
+        }
+        if src.starts_with('"') || src.starts_with("String::from(") {
+            return Some(ValueKind::Text);
+        }
+        let digits = src.replace('_', "");
+        for suffix in ["f32", "f64"] {
+            if let Some(num) = digits.strip_suffix(suffix) {
+                return num.parse::<f64>().ok().map(|_| ValueKind::Float);
+            }
+        }
+        let num = [
+            "i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize",
+        ]
+        .iter()
+        .find_map(|suffix| digits.strip_suffix(suffix))
+        .unwrap_or(&digits);
+        if num.parse::<i64>().is_ok() {
+            Some(ValueKind::Int)
+        } else if num.parse::<f64>().is_ok() {
+            Some(ValueKind::Float)
+        } else {
+            None
+        }
+    }
+
+    /// The text of a string literal default, without escapes.
+    fn text(&self) -> Option<&'static str> {
+        let src = self.default.trim();
+        let src = src
+            .strip_suffix(".to_string()")
+            .or_else(|| src.strip_prefix("String::from(")?.strip_suffix(')'))
+            .unwrap_or(src)
+            .trim();
+        src.strip_prefix('"')?
+            .strip_suffix('"')
+            .filter(|text| !text.contains('\\'))
+    }
+
+    /// Whether the value set here converts to `kind` when read.
+    fn converts_to(&self, kind: ValueKind) -> bool {
+        match (self.kind(), self.text()) {
+            (_, Some(text)) => Value::from(text).can_convert_to(kind),
+            (Some(from), None) => Coercion::between(from, kind) != Coercion::Never,
+            (None, None) => true,
+        }
+    }
+}
+
+/// The [`ParamInfo`] of every `get_param!` call site in the program.
+#[::linkme::distributed_slice]
+pub static PARAM_REGISTRY: [ParamInfo];
+
+/// The registered parameters, ordered by key.
+///
+/// A key read at several call sites is listed once per call site.
+pub fn registered_params() -> Vec<&'static ParamInfo> {
+    let mut params: Vec<_> = PARAM_REGISTRY.iter().collect();
+    params.sort_by_key(|p| (p.key, p.module, p.line));
+    params
+}
+
+/// The registered key hashing to `hkey`, if any.
+pub(crate) fn registered_key(hkey: u64) -> Option<&'static str> {
+    lazy_static! {
+        static ref KEYS: HashMap<u64, &'static str> = PARAM_REGISTRY
+            .iter()
+            .map(|p| (xxhash(p.key.as_bytes()), p.key))
+            .collect();
+    }
+    KEYS.get(&hkey).copied()
+}
+
+/// The `set` and `@setdefault` statements of every `with_params!` in the
+/// program, with the value as written in [`ParamInfo::default`].
+///
+/// These keys are written rather than read, so they are not in
+/// [`PARAM_REGISTRY`] and [`ParamScope::check_registered`] ignores them.
+#[::linkme::distributed_slice]
+pub static SET_PARAM_REGISTRY: [ParamInfo];
+
+/// Check the keys of [`PARAM_REGISTRY`] and [`SET_PARAM_REGISTRY`] against
+/// each other, for instance once at startup.
+///
+/// Parameters are looked up by the xxhash of their key, so two keys with the
+/// same hash would silently share a value; such collisions are reported.
+/// A key read with defaults of different kinds, like `0` in one crate and
+/// `0.5` in another, is reported too, since one of the call sites will not
+/// see what it expects, as is a key set to a value that does not convert to
+/// the kind it is read as, like `"fast"` for a `0.1` default.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// assert_eq!(Ok(()), verify_keys());
+/// ```
+pub fn verify_keys() -> Result<(), Vec<ValidationError>> {
+    verify(&PARAM_REGISTRY, &SET_PARAM_REGISTRY)
+}
+
+fn verify(reads: &[ParamInfo], sets: &[ParamInfo]) -> Result<(), Vec<ValidationError>> {
+    let mut errors = Vec::new();
+    let mut keys: HashMap<u64, &ParamInfo> = HashMap::new();
+    for info in reads.iter().chain(sets) {
+        let first = keys.entry(xxhash(info.key.as_bytes())).or_insert(info);
+        if first.key != info.key {
+            errors.push(ValidationError {
+                key: Some(info.key.to_string()),
+                message: format!(
+                    "hash collides with `{}` at {}:{}",
+                    first.key, first.file, first.line
+                ),
+            });
+        }
+    }
+    let mut kinds: HashMap<&str, (&ParamInfo, ValueKind)> = HashMap::new();
+    for info in reads {
+        let kind = match info.kind() {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let (first, first_kind) = *kinds.entry(info.key).or_insert((info, kind));
+        if first_kind != kind {
+            errors.push(ValidationError {
+                key: Some(info.key.to_string()),
+                message: format!(
+                    "read as {:?} at {}:{} but as {:?} at {}:{}",
+                    first_kind, first.file, first.line, kind, info.file, info.line
+                ),
+            });
+        }
+    }
+    for info in sets {
+        if let Some((read, kind)) = kinds.get(info.key) {
+            if !info.converts_to(*kind) {
+                errors.push(ValidationError {
+                    key: Some(info.key.to_string()),
+                    message: format!(
+                        "set to `{}` at {}:{} but read as {:?} at {}:{}",
+                        info.default, info.file, info.line, kind, read.file, read.line
+                    ),
+                });
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+impl ParamScope {
+    /// Check the parameters set in this scope against the registry.
+    ///
+    /// Keys that no `get_param!` call site reads are reported, with the
+    /// closest registered key as a suggestion, since they are usually
+    /// typos. Values that do not convert to the kind of a registered default
+    /// are reported too. Keys read only through [`ParamScope::get`] are not
+    /// registered, so only check scopes whose keys are read by `get_param!`.
+    pub fn check_registered(&self) -> Result<(), Vec<ValidationError>> {
+        check(self, &PARAM_REGISTRY)
+    }
+}
+
+fn check(scope: &ParamScope, registry: &[ParamInfo]) -> Result<(), Vec<ValidationError>> {
+    let changes = match scope {
+        ParamScope::Just(changes) => changes,
+        ParamScope::Nothing => return Ok(()),
+    };
+    let mut errors = Vec::new();
+    for entry in changes.values() {
+        let val = entry.value();
+        if matches!(val, Value::Empty) {
+            continue;
+        }
+        let infos: Vec<_> = registry.iter().filter(|p| p.key == &*entry.key).collect();
+        if infos.is_empty() {
+            let message = match closest(&entry.key, registry.iter().map(|p| p.key)) {
+                Some(key) => format!("unknown parameter, did you mean `{}`?", key),
+                None => "unknown parameter".to_string(),
+            };
+            errors.push(ValidationError {
+                key: Some(entry.key.to_string()),
+                message,
+            });
+            continue;
+        }
+        if let Some(info) = infos
+            .iter()
+            .find(|p| matches!(p.kind(), Some(k) if !val.can_convert_to(k)))
+        {
+            errors.push(ValidationError {
+                key: Some(entry.key.to_string()),
+                message: format!(
+                    "expects a {:?} value like the default `{}` at {}:{}, got {:?}",
+                    info.kind().unwrap(),
+                    info.default,
+                    info.file,
+                    info.line,
+                    val
+                ),
+            });
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, verify, ParamInfo};
+    use crate::*;
+
+    fn info(key: &'static str, default: &'static str) -> ParamInfo {
+        ParamInfo {
+            key,
+            default,
+            help: "",
+            module: "app::train",
+            file: "src/train.rs",
+            line: 1,
+        }
+    }
+
+    #[test]
+    fn test_param_info_kind() {
+        let kinds = [
+            ("1", Some(ValueKind::Int)),
+            ("- 1_000i64", Some(ValueKind::Int)),
+            ("0.1", Some(ValueKind::Float)),
+            ("1e-3", Some(ValueKind::Float)),
+            ("2f32", Some(ValueKind::Float)),
+            ("true", Some(ValueKind::Boolean)),
+            ("\"adam\"", Some(ValueKind::Text)),
+            ("\"adam\".to_string()", Some(ValueKind::Text)),
+            ("String::from(\"adam\")", Some(ValueKind::Text)),
+            ("default_lr()", None),
+        ];
+        for (default, kind) in kinds {
+            assert_eq!(kind, info("k", default).kind(), "{}", default);
+        }
+        assert_eq!("app", info("k", "1").crate_name());
+    }
+
+    #[test]
+    fn test_check_registered() {
+        let registry = [info("train.lr", "0.1"), info("train.optimizer", "\"adam\"")];
+
+        let mut ps = ParamScope::default();
+        ps.put("train.lr", "0.01".to_string());
+        ps.put("train.optimizer", "sgd".to_string());
+        assert_eq!(Ok(()), check(&ps, &registry));
+
+        ps.put("train.lr", "fast".to_string());
+        ps.put("train.optimiser", "sgd".to_string());
+        ps.put("eval.steps", 10);
+        let errors = check(&ps, &registry).unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(3, messages.len(), "{:?}", messages);
+        assert!(messages.contains(&"eval.steps: unknown parameter".to_string()));
+        assert!(messages.contains(
+            &"train.optimiser: unknown parameter, did you mean `train.optimizer`?".to_string()
+        ));
+        assert!(messages
+            .iter()
+            .any(|m| m.starts_with("train.lr: expects a Float value")));
+
+        assert_eq!(Ok(()), check(&ParamScope::Nothing, &registry));
+    }
+
+    #[test]
+    fn test_verify_keys() {
+        let reads = [
+            info("train.lr", "0.1"),
+            info("train.lr", "1e-3"),
+            info("train.steps", "100"),
+            info("train.name", "default_name()"),
+        ];
+        let sets = [
+            info("train.lr", "\"1e-4\".to_string()"),
+            info("train.steps", "2.5"),
+            info("train.name", "1"),
+        ];
+        assert_eq!(Ok(()), verify(&reads, &sets));
+
+        let sets = [
+            info("train.lr", "\"fast\""),
+            info("train.lr", "true"),
+            info("train.steps", "String::from(\"many\")"),
+        ];
+        let errors = verify(&reads, &sets).unwrap_err();
+        assert_eq!(3, errors.len());
+        assert_eq!(
+            "train.lr: set to `\"fast\"` at src/train.rs:1 but read as Float at src/train.rs:1",
+            errors[0].to_string()
+        );
+
+        let reads = [info("train.lr", "0.1"), info("train.lr", "0")];
+        let errors = verify(&reads, &[]).unwrap_err();
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            "train.lr: read as Float at src/train.rs:1 but as Int at src/train.rs:1",
+            errors[0].to_string()
+        );
+    }
+}
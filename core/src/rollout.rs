@@ -0,0 +1,60 @@
+//! Percentage-based rollouts controlled by parameters.
+//!
+//! ```
+//! use hyperparameter::*;
+//!
+//! with_params! {
+//!     set features.new_ranker = 100;
+//!
+//!     assert!(rollout::is_in_cohort("features.new_ranker", "user-42"));
+//! }
+//! assert!(!rollout::is_in_cohort("features.new_ranker", "user-42"));
+//! ```
+
+use crate::storage::{GetOrElse, THREAD_STORAGE};
+use crate::xxh::XXHashable;
+
+/// Decide whether `id` is part of the rollout controlled by parameter `key`.
+///
+/// `key` is read from the current thread as a percentage between 0 and 100;
+/// unset or unparsable values count as 0. Each id is hashed into a stable
+/// bucket per key, so raising the percentage only ever adds ids to the cohort
+/// and different keys roll out to independent cohorts.
+pub fn is_in_cohort<K: XXHashable, I: AsRef<[u8]>>(key: K, id: I) -> bool {
+    let hkey = key.xxh();
+    let percent: f64 = THREAD_STORAGE.with(|ts| ts.borrow().get_or_else(hkey, 0.0));
+    bucket(hkey, id.as_ref()) < percent.clamp(0.0, 100.0) * 100.0
+}
+
+/// Map `id` to a bucket in `[0, 10000)`, i.e. a percentage with two decimals.
+fn bucket(hkey: u64, id: &[u8]) -> f64 {
+    (xxhash_rust::const_xxh64::xxh64(id, hkey) % 10000) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_rollout_cohort() {
+        let ids: Vec<String> = (0..1000).map(|i| format!("id-{}", i)).collect();
+        let cohort = |pct: f64| -> Vec<&String> {
+            with_params! {
+                set rollout.test.flag = pct;
+
+                ids.iter()
+                    .filter(|id| rollout::is_in_cohort("rollout.test.flag", id.as_str()))
+                    .collect()
+            }
+        };
+
+        assert!(cohort(0.0).is_empty());
+        assert_eq!(1000, cohort(100.0).len());
+        let small = cohort(10.0);
+        let large = cohort(50.0);
+        assert!((50..150).contains(&small.len()));
+        assert!((400..600).contains(&large.len()));
+        assert!(small.iter().all(|id| large.contains(id)));
+        assert_eq!(small, cohort(10.0));
+    }
+}
@@ -0,0 +1,350 @@
+//! `serde` support, behind the `serde` feature.
+//!
+//! Values serialize to their natural form, a [`ParamScope`] to a map from
+//! keys to values, so a snapshot of the current parameters can be saved and
+//! entered again later:
+//!
+//! ```
+//! use hyperparameter::*;
+//!
+//! let mut saved = String::new();
+//! with_params! {
+//!     set train.lr = 0.01;
+//!     set train.optimizer = "adam".to_string();
+//!
+//!     saved = serde_json::to_string(&ParamScope::capture()).unwrap();
+//! }
+//!
+//! let mut restored: ParamScope = serde_json::from_str(&saved).unwrap();
+//! with_params! {
+//!     params restored;
+//!
+//!     assert_eq!(0.01, get_param!(train.lr, 0.0));
+//!     assert_eq!("adam", get_param!(train.optimizer, String::new()));
+//! }
+//! ```
+//!
+//! `Value::UserDefined` holds a pointer and can not be serialized. Only the
+//! current value of an [`Entry`] is kept, not the values it shadows. `Params`
+//! serializes as a map from hashed keys to entries. A [`ParamSnapshot`]
+//! serializes as its format version and its parameters.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use ::serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use ::serde::ser::{self, Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
+
+use crate::api::ParamScope;
+use crate::snapshot::{ParamSnapshot, SNAPSHOT_VERSION};
+use crate::storage::{Entry, ParamMap, Params};
+use crate::value::Value;
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Empty => serializer.serialize_unit(),
+            Value::Int(v) => serializer.serialize_i64(*v),
+            Value::Float(v) => serializer.serialize_f64(*v),
+            Value::Text(v) => serializer.serialize_str(v),
+            Value::Boolean(v) => serializer.serialize_bool(*v),
+            Value::List(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Value::UserDefined(..) => Err(ser::Error::custom(
+                "user defined values can not be serialized",
+            )),
+        }
+    }
+}
+
+pub(crate) struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a parameter value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Empty)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Empty)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Boolean(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        i64::try_from(v)
+            .map(Value::Int)
+            .map_err(|_| E::custom(format!("integer {} is out of range", v)))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::Text(v.into()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::Text(v.into()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::List(items))
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl Serialize for Entry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entry = serializer.serialize_struct("Entry", 2)?;
+        entry.serialize_field("key", &*self.key)?;
+        entry.serialize_field("value", self.value())?;
+        entry.end()
+    }
+}
+
+struct EntryVisitor;
+
+impl<'de> Visitor<'de> for EntryVisitor {
+    type Value = Entry;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a parameter entry")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Entry, A::Error> {
+        let key: String = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let value: Value = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(Entry::new(key, value))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Entry, A::Error> {
+        let mut key: Option<String> = None;
+        let mut value: Option<Value> = None;
+        while let Some(field) = map.next_key::<String>()? {
+            match field.as_str() {
+                "key" => key = Some(map.next_value()?),
+                "value" => value = Some(map.next_value()?),
+                _ => return Err(de::Error::unknown_field(&field, ENTRY_FIELDS)),
+            }
+        }
+        let key = key.ok_or_else(|| de::Error::missing_field("key"))?;
+        let value = value.ok_or_else(|| de::Error::missing_field("value"))?;
+        Ok(Entry::new(key, value))
+    }
+}
+
+const ENTRY_FIELDS: &[&str] = &["key", "value"];
+
+impl<'de> Deserialize<'de> for Entry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Entry, D::Error> {
+        deserializer.deserialize_struct("Entry", ENTRY_FIELDS, EntryVisitor)
+    }
+}
+
+impl Serialize for Params {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Params {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Params, D::Error> {
+        Ok(ParamMap::deserialize(deserializer)?.into_iter().collect())
+    }
+}
+
+impl Serialize for ParamScope {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let changes = match self {
+            ParamScope::Just(changes) => changes,
+            ParamScope::Nothing => return serializer.serialize_none(),
+        };
+        let mut map = serializer.serialize_map(Some(changes.len()))?;
+        for e in changes.values() {
+            if e.key.is_empty() {
+                return Err(ser::Error::custom(
+                    "parameters put by hashed key can not be serialized",
+                ));
+            }
+            map.serialize_entry(&*e.key, e.value())?;
+        }
+        map.end()
+    }
+}
+
+struct ParamScopeVisitor;
+
+impl<'de> Visitor<'de> for ParamScopeVisitor {
+    type Value = ParamScope;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of parameters")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<ParamScope, E> {
+        Ok(ParamScope::Nothing)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<ParamScope, E> {
+        Ok(ParamScope::Nothing)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<ParamScope, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ParamScope, A::Error> {
+        let mut ps = ParamScope::default();
+        while let Some((key, value)) = map.next_entry::<String, Value>()? {
+            ps.try_put(key, value).map_err(de::Error::custom)?;
+        }
+        Ok(ps)
+    }
+}
+
+impl<'de> Deserialize<'de> for ParamScope {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ParamScope, D::Error> {
+        deserializer.deserialize_option(ParamScopeVisitor)
+    }
+}
+
+impl Serialize for ParamSnapshot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut snapshot = serializer.serialize_struct("ParamSnapshot", 2)?;
+        snapshot.serialize_field("version", &SNAPSHOT_VERSION)?;
+        snapshot.serialize_field("params", &self.params)?;
+        snapshot.end()
+    }
+}
+
+struct ParamSnapshotVisitor;
+
+impl<'de> Visitor<'de> for ParamSnapshotVisitor {
+    type Value = ParamSnapshot;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a parameter snapshot")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ParamSnapshot, A::Error> {
+        let mut version: Option<u32> = None;
+        let mut params: Option<BTreeMap<String, Value>> = None;
+        while let Some(field) = map.next_key::<String>()? {
+            match field.as_str() {
+                "version" => version = Some(map.next_value()?),
+                "params" => params = Some(map.next_value()?),
+                _ => return Err(de::Error::unknown_field(&field, SNAPSHOT_FIELDS)),
+            }
+        }
+        let version = version.ok_or_else(|| de::Error::missing_field("version"))?;
+        if version > SNAPSHOT_VERSION {
+            return Err(de::Error::custom(format!(
+                "snapshot version {} is newer than the supported version {}",
+                version, SNAPSHOT_VERSION
+            )));
+        }
+        let params = params.ok_or_else(|| de::Error::missing_field("params"))?;
+        Ok(ParamSnapshot { params })
+    }
+}
+
+const SNAPSHOT_FIELDS: &[&str] = &["version", "params"];
+
+impl<'de> Deserialize<'de> for ParamSnapshot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ParamSnapshot, D::Error> {
+        deserializer.deserialize_struct("ParamSnapshot", SNAPSHOT_FIELDS, ParamSnapshotVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{Entry, Params};
+    use crate::*;
+
+    #[test]
+    fn test_value_round_trip() {
+        let values = [
+            Value::Empty,
+            Value::Int(-3),
+            Value::Float(1.0),
+            Value::Text("adam".into()),
+            Value::Boolean(true),
+            Value::List(vec![Value::Int(1), Value::Text("a".into())]),
+        ];
+        for v in values {
+            let json = serde_json::to_string(&v).unwrap();
+            assert_eq!(v, serde_json::from_str::<Value>(&json).unwrap(), "{}", json);
+        }
+        assert_eq!(
+            "[1,2.5]",
+            serde_json::to_string(&Value::from(vec![Value::Int(1), Value::Float(2.5)])).unwrap()
+        );
+        assert!(serde_json::to_string(&Value::from(std::ptr::null_mut())).is_err());
+    }
+
+    #[test]
+    fn test_params_round_trip() {
+        let mut params = Params::default();
+        params.insert("serde.a".xxh(), Entry::new("serde.a", 1));
+        let json = serde_json::to_string(&params).unwrap();
+        let restored: Params = serde_json::from_str(&json).unwrap();
+        let e = restored.get(&"serde.a".xxh()).unwrap();
+        assert_eq!("serde.a", &*e.key);
+        assert_eq!(&Value::Int(1), e.value());
+
+        let mut ps = ParamScope::default();
+        ps.put("serde.b", 2.5);
+        ps.put("serde.c", vec![1, 2]);
+        let json = serde_json::to_string(&ps).unwrap();
+        let restored: ParamScope = serde_json::from_str(&json).unwrap();
+        assert_eq!(Value::Float(2.5), restored.get("serde.b"));
+        assert_eq!(Value::from(vec![1, 2]), restored.get("serde.c"));
+
+        let snapshot = restored.snapshot();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.starts_with("{\"version\":1,"), "{}", json);
+        assert_eq!(snapshot, serde_json::from_str(&json).unwrap());
+
+        let json = serde_json::to_string(&ParamScope::Nothing).unwrap();
+        assert_eq!("null", json);
+        assert!(matches!(
+            serde_json::from_str::<ParamScope>(&json).unwrap(),
+            ParamScope::Nothing
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::api::{ParamScope, ParamScopeOps};
+use crate::env::{decode, encode, escape, unescape};
+use crate::mode::defaults_only;
+use crate::storage::THREAD_STORAGE;
+use crate::value::Value;
+
+/// Version of the format written by [`ParamSnapshot::to_text`].
+///
+/// Snapshots written by older versions can always be read; newer ones are
+/// rejected rather than misread.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+const SNAPSHOT_HEADER: &str = "hyperparameter-snapshot";
+
+/// The effective parameters at one point of a program, taken by [`ParamScope::snapshot`].
+///
+/// A snapshot can be saved to a file and restored in another process:
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let path = std::env::temp_dir().join("doc-snapshot.txt");
+/// with_params! {
+///     set train.lr = 0.01;
+///
+///     with_params! {
+///         set train.epochs = 10;
+///
+///         ParamScope::Nothing.snapshot().save(&path).unwrap();
+///     }
+/// }
+///
+/// let mut ps = ParamSnapshot::load(&path).unwrap().restore();
+/// with_params! {
+///     params ps;
+///
+///     assert_eq!(0.01, get_param!(train.lr, 0.0));
+///     assert_eq!(10, get_param!(train.epochs, 0));
+/// }
+/// ```
+///
+/// The file starts with a `hyperparameter-snapshot <version>` line, followed
+/// by one `key=kind:value` line per parameter in key order, the encoding used
+/// by [`ParamScope::to_env_vars`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParamSnapshot {
+    pub(crate) params: BTreeMap<String, Value>,
+}
+
+impl ParamSnapshot {
+    /// The parameters of the snapshot, by key.
+    pub fn params(&self) -> &BTreeMap<String, Value> {
+        &self.params
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.params.get(key)
+    }
+
+    /// A scope that sets every parameter of the snapshot once entered.
+    pub fn restore(&self) -> ParamScope {
+        let mut ps = ParamScope::default();
+        for (key, val) in self.params.iter() {
+            ps.put(key.clone(), val.clone());
+        }
+        ps
+    }
+
+    /// Encode the snapshot in the current format version.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", SNAPSHOT_HEADER, SNAPSHOT_VERSION);
+        for (key, val) in self.params.iter() {
+            if let Some(encoded) = encode(val) {
+                text.push_str(&format!("{}={}\n", escape(key), encoded));
+            }
+        }
+        text
+    }
+
+    /// Decode a snapshot written by [`ParamSnapshot::to_text`].
+    pub fn from_text(text: &str) -> Result<ParamSnapshot, String> {
+        let mut lines = text.lines();
+        let version = lines
+            .next()
+            .and_then(|header| header.strip_prefix(SNAPSHOT_HEADER))
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| "not a parameter snapshot".to_string())?;
+        if version > SNAPSHOT_VERSION {
+            return Err(format!(
+                "snapshot version {} is newer than the supported version {}",
+                version, SNAPSHOT_VERSION
+            ));
+        }
+        let mut params = BTreeMap::new();
+        for line in lines.filter(|l| !l.is_empty()) {
+            let invalid = || format!("invalid parameter in snapshot: {}", line);
+            let (key, encoded) = line.split_once('=').ok_or_else(invalid)?;
+            params.insert(unescape(key), decode(encoded).ok_or_else(invalid)?);
+        }
+        Ok(ParamSnapshot { params })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_text()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ParamSnapshot, String> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        ParamSnapshot::from_text(&text)
+    }
+}
+
+impl ParamScope {
+    /// Snapshot the parameters this scope would see once entered.
+    ///
+    /// The snapshot holds the values inherited from the thread storage,
+    /// overridden by the changes of this scope; `ParamScope::Nothing`
+    /// snapshots the thread storage alone. User-defined values can not cross
+    /// processes and are left out, as are parameters put by hashed key only.
+    pub fn snapshot(&self) -> ParamSnapshot {
+        let mut params = THREAD_STORAGE.with(|ts| ts.borrow().capture());
+        if let ParamScope::Just(changes) = self {
+            for (hkey, e) in changes.iter() {
+                params.insert(*hkey, e.shallow());
+            }
+        }
+        let params = params
+            .values()
+            .filter(|e| !e.key.is_empty() && !defaults_only(&e.key))
+            .filter(|e| !matches!(e.value(), Value::Empty | Value::UserDefined(..)))
+            .map(|e| (e.key.to_string(), e.clone_value()))
+            .collect();
+        ParamSnapshot { params }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_snapshot_text_round_trip() {
+        with_params! {
+            set snapshot.a = 1;
+            set snapshot.b = "x=1\ny".to_string();
+
+            let mut inner = ParamScope::default();
+            inner.put("snapshot.a", 2);
+            inner.put("snapshot.c", vec![0.5, 1.5]);
+            let snapshot = inner.snapshot();
+            assert_eq!(Some(&Value::Int(2)), snapshot.get("snapshot.a"));
+
+            let text = snapshot.to_text();
+            assert!(text.starts_with("hyperparameter-snapshot 1\n"));
+            let restored = ParamSnapshot::from_text(&text).unwrap();
+            assert_eq!(snapshot, restored);
+            let b = restored.restore().get("snapshot.b");
+            assert_eq!(Value::Text("x=1\ny".into()), b);
+        }
+
+        assert!(ParamSnapshot::from_text("snapshot.a=i:1").is_err());
+        let newer = format!("hyperparameter-snapshot {}\n", SNAPSHOT_VERSION + 1);
+        assert!(ParamSnapshot::from_text(&newer).is_err());
+    }
+}
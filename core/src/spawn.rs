@@ -0,0 +1,109 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread::JoinHandle;
+
+use crate::api::ParamScope;
+use crate::storage::THREAD_STORAGE;
+
+/// Exits the scope entered by [`EnteredScope::enter`] when dropped, even on panic,
+/// together with any scope entered after it and not exited yet.
+#[doc(hidden)]
+pub struct EnteredScope {
+    depth: usize,
+}
+
+impl EnteredScope {
+    pub(crate) fn enter(mut scope: ParamScope) -> EnteredScope {
+        let guard = EnteredScope::mark();
+        scope.enter();
+        guard
+    }
+
+    /// Guard the scopes entered from now on, so that `@foreach` leaves none
+    /// of them entered when an iteration ends with `continue` or `break`.
+    pub fn mark() -> EnteredScope {
+        EnteredScope {
+            depth: THREAD_STORAGE.with(|ts| ts.borrow().depth()),
+        }
+    }
+}
+
+impl Drop for EnteredScope {
+    fn drop(&mut self) {
+        while THREAD_STORAGE.with(|ts| ts.borrow().depth()) > self.depth {
+            ParamScope::Nothing.exit();
+        }
+    }
+}
+
+impl ParamScope {
+    /// Wrap `f` so that it runs inside this scope, on whichever thread calls it.
+    ///
+    /// The scope is exited when `f` returns or panics, so threads reused by a
+    /// pool such as rayon keep no parameters from one task to the next:
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// with_params! {
+    ///     set worker.batch = 32;
+    ///
+    ///     let task = ParamScope::capture().apply_to_thread(|| get_param!(worker.batch, 0));
+    ///     // e.g. rayon::spawn(task)
+    ///     assert_eq!(32, std::thread::spawn(task).join().unwrap());
+    /// }
+    /// ```
+    pub fn apply_to_thread<F, R>(self, f: F) -> impl FnOnce() -> R + Send
+    where
+        F: FnOnce() -> R + Send,
+    {
+        move || {
+            let _entered = EnteredScope::enter(self);
+            f()
+        }
+    }
+}
+
+/// Spawn a thread that sees the parameters of the current thread.
+///
+/// Like [`std::thread::spawn`], with the parameters visible at the call
+/// captured and entered on the new thread.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set worker.batch = 32;
+///
+///     let batch = spawn_with_params(|| get_param!(worker.batch, 0)).join().unwrap();
+///     assert_eq!(32, batch);
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn_with_params<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    std::thread::spawn(ParamScope::capture().apply_to_thread(f))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_apply_to_thread_exits_scope() {
+        let mut ps = ParamScope::default();
+        ps.put("spawn.a", 1);
+        let task = ps.clone().apply_to_thread(|| get_param!(spawn.a, 0));
+        assert_eq!(1, task());
+        assert_eq!(0, get_param!(spawn.a, 0));
+
+        let failing = ps.apply_to_thread(|| {
+            assert_eq!(1, get_param!(spawn.a, 0));
+            panic!("task failed");
+        });
+        assert!(std::panic::catch_unwind(failing).is_err());
+        assert_eq!(0, get_param!(spawn.a, 0));
+    }
+}
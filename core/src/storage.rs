@@ -1,26 +1,48 @@
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::collections::HashSet;
-use std::sync::Mutex;
+#[cfg(feature = "provenance")]
+use std::collections::LinkedList;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
 
 use lazy_static::lazy_static;
 
+#[cfg(feature = "provenance")]
+use crate::provenance::{current_source, Source};
+use crate::subscribe::Subscribers;
 use crate::value::Value;
+use crate::value::ValueError;
 use crate::value::VersionedValue;
 use crate::value::EMPTY;
 use crate::xxh::XXHashable;
 
 #[derive(Debug, Clone)]
 pub struct Entry {
-    pub key: String,
+    pub key: Arc<str>,
     pub val: VersionedValue,
+    /// Where each version of `val` comes from, newest first.
+    #[cfg(feature = "provenance")]
+    pub sources: LinkedList<Source>,
+    /// Whether the oldest version is a library default or a hardware fact.
+    pub(crate) default: bool,
 }
 
 impl Entry {
-    pub fn new<T: Into<String>, V: Into<Value>>(key: T, val: V) -> Entry {
+    pub fn new<T: Into<Arc<str>>, V: Into<Value>>(key: T, val: V) -> Entry {
         Entry {
             key: key.into(),
             val: VersionedValue::from(val.into()),
+            #[cfg(feature = "provenance")]
+            sources: LinkedList::from([current_source()]),
+            default: false,
         }
     }
 
@@ -32,15 +54,107 @@ impl Entry {
         self.val.value().clone()
     }
 
+    /// Whether the current version is a library default or a hardware fact.
+    pub fn is_default(&self) -> bool {
+        self.default && self.val.depth() == 1
+    }
+
+    /// Where the current version comes from.
+    #[cfg(feature = "provenance")]
+    pub fn source(&self) -> &Source {
+        self.sources.front().unwrap_or(&Source::Code)
+    }
+
     pub fn shallow(&self) -> Entry {
         Entry {
             key: self.key.clone(),
             val: self.val.shallow(),
+            #[cfg(feature = "provenance")]
+            sources: LinkedList::from([self.source().clone()]),
+            default: self.is_default(),
+        }
+    }
+}
+
+/// Hasher for keys that are already xxhash values, which it passes through.
+#[derive(Default)]
+pub struct KeyHasher(u64);
+
+impl Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = self.0.rotate_left(8) ^ *b as u64;
         }
     }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n;
+    }
+}
+
+/// The map behind [`Params`]. Iteration order is unspecified; sort by key
+/// name where order matters.
+pub type ParamMap = HashMap<u64, Entry, BuildHasherDefault<KeyHasher>>;
+
+/// Parameters by hashed key, shared copy-on-write.
+///
+/// Clones share one map until either side is written, which copies it, so
+/// capturing the parameters of a thread for every task it spawns costs no
+/// more than capturing them once.
+///
+/// Empty parameters allocate nothing, which keeps scopes that only read free.
+#[derive(Debug, Clone, Default)]
+pub struct Params(Option<Arc<ParamMap>>);
+
+lazy_static! {
+    static ref NO_PARAMS: ParamMap = ParamMap::default();
+}
+
+impl Deref for Params {
+    type Target = ParamMap;
+
+    fn deref(&self) -> &ParamMap {
+        self.0.as_deref().unwrap_or(&NO_PARAMS)
+    }
+}
+
+impl DerefMut for Params {
+    fn deref_mut(&mut self) -> &mut ParamMap {
+        Arc::make_mut(self.0.get_or_insert_with(Default::default))
+    }
+}
+
+impl FromIterator<(u64, Entry)> for Params {
+    fn from_iter<I: IntoIterator<Item = (u64, Entry)>>(iter: I) -> Self {
+        let map: ParamMap = iter.into_iter().collect();
+        Params((!map.is_empty()).then(|| Arc::new(map)))
+    }
+}
+
+impl IntoIterator for Params {
+    type Item = (u64, Entry);
+    type IntoIter = std::collections::hash_map::IntoIter<u64, Entry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0
+            .map(Arc::unwrap_or_clone)
+            .unwrap_or_default()
+            .into_iter()
+    }
 }
 
-pub type Params = BTreeMap<u64, Entry>;
+impl<'a> IntoIterator for &'a Params {
+    type Item = (&'a u64, &'a Entry);
+    type IntoIter = std::collections::hash_map::Iter<'a, u64, Entry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        (**self).iter()
+    }
+}
 
 pub trait MultipleVersion<K> {
     fn update<V: Into<Value>>(&mut self, key: K, val: V);
@@ -51,18 +165,28 @@ pub trait MultipleVersion<K> {
 impl MultipleVersion<u64> for Params {
     fn update<V: Into<Value>>(&mut self, key: u64, val: V) {
         if let Some(e) = self.get_mut(&key) {
+            e.default &= e.val.depth() > 1;
             e.val.update(val);
+            #[cfg(feature = "provenance")]
+            match e.sources.front_mut() {
+                Some(source) => *source = current_source(),
+                None => e.sources.push_front(current_source()),
+            }
         }
     }
 
     fn revision<V: Into<Value>>(&mut self, key: u64, val: V) {
         if let Some(e) = self.get_mut(&key) {
             e.val.revision(val);
+            #[cfg(feature = "provenance")]
+            e.sources.push_front(current_source());
         }
     }
 
     fn rollback(&mut self, key: u64) {
         if let Some(e) = self.get_mut(&key) {
+            #[cfg(feature = "provenance")]
+            e.sources.pop_front();
             if !e.val.rollback() {
                 self.remove(&key);
             }
@@ -75,116 +199,823 @@ thread_local! {
 }
 
 fn create_thread_storage() -> RefCell<Storage> {
+    #[cfg(debug_assertions)]
+    if let Err(e) = crate::xxh::verify_hash_consistency() {
+        panic!("{}", e);
+    }
+    #[cfg(feature = "defaults")]
+    if crate::defaults::collecting() {
+        return RefCell::new(Storage::default());
+    }
+    let global = GLOBAL_PARAMS.load_full();
     let ts = RefCell::new(Storage::default());
-    ts.borrow_mut()
-        .params
-        .clone_from(&GLOBAL_STORAGE.lock().unwrap().params);
+    ts.borrow_mut().params.clone_from(&global);
     ts
 }
 
 lazy_static! {
-    static ref GLOBAL_STORAGE: Mutex<Storage> = Mutex::new(Storage::default());
+    /// The frozen global layer, an immutable map swapped atomically on update.
+    ///
+    /// Readers only load the current pointer, so they never contend with each
+    /// other or wait for a writer.
+    static ref GLOBAL_PARAMS: ArcSwap<Params> = ArcSwap::from_pointee(create_global_params());
+}
+
+#[cfg(feature = "defaults")]
+fn create_global_params() -> Params {
+    crate::defaults::library_defaults()
+}
+
+#[cfg(not(feature = "defaults"))]
+fn create_global_params() -> Params {
+    Params::default()
 }
 
 pub fn frozen_global_storage() {
+    if rejected_after_hard_freeze(|| "update of the frozen global storage".to_string()) {
+        return;
+    }
+    THREAD_STORAGE.with(|ts| GLOBAL_PARAMS.store(Arc::new(ts.borrow().params.clone())));
+}
+
+static HARD_FROZEN: AtomicBool = AtomicBool::new(false);
+
+/// Publish the current thread's parameters like [`frozen_global_storage`],
+/// then reject every later write to any storage.
+pub fn frozen_global_storage_hard() {
+    frozen_global_storage();
+    HARD_FROZEN.store(true, Ordering::Release);
+}
+
+/// Whether storages are hard frozen, in which case `what` is rejected: it
+/// panics in debug builds and is ignored with a warning in release builds.
+fn rejected_after_hard_freeze<F: FnOnce() -> String>(what: F) -> bool {
+    if !HARD_FROZEN.load(Ordering::Acquire) {
+        return false;
+    }
+    if cfg!(debug_assertions) {
+        panic!("{} after freeze_hard()", what());
+    }
+    println!(
+        "hyperparameter warning: ignored {} after freeze_hard()",
+        what()
+    );
+    true
+}
+
+/// Publish the current thread's parameters under `prefix` to the frozen global storage.
+///
+/// Frozen parameters under `prefix` that are no longer set on this thread are
+/// dropped; parameters outside `prefix` are left untouched.
+pub fn frozen_global_prefix(prefix: &str) {
+    if rejected_after_hard_freeze(|| format!("freeze of prefix `{}`", prefix)) {
+        return;
+    }
     THREAD_STORAGE.with(|ts| {
-        GLOBAL_STORAGE
-            .lock()
-            .unwrap()
-            .params
-            .clone_from(&ts.borrow().params);
+        let ts = ts.borrow();
+        GLOBAL_PARAMS.rcu(|global| {
+            let mut params: Params = global
+                .iter()
+                .filter(|(k, e)| !has_prefix(**k, e, prefix))
+                .map(|(k, e)| (*k, e.clone()))
+                .collect();
+            for (hkey, e) in ts.params.iter() {
+                if has_prefix(*hkey, e, prefix) && !matches!(e.value(), Value::Empty) {
+                    params.insert(*hkey, e.shallow());
+                }
+            }
+            params
+        });
+    });
+}
+
+/// Whether the key of an entry starts with `prefix`, looking up the name of
+/// entries written by hash only.
+fn has_prefix(hkey: u64, e: &Entry, prefix: &str) -> bool {
+    let name = match &*e.key {
+        "" => crate::xxh::key_name(hkey),
+        key => Some(key),
+    };
+    name.is_some_and(|name| name.starts_with(prefix))
+}
+
+/// Drop the frozen parameters under `prefix`, restoring their library defaults.
+pub fn unfrozen_global_prefix(prefix: &str) {
+    if rejected_after_hard_freeze(|| format!("unfreeze of prefix `{}`", prefix)) {
+        return;
+    }
+    let defaults = create_global_params();
+    GLOBAL_PARAMS.rcu(|global| {
+        let mut params: Params = global
+            .iter()
+            .filter(|(k, e)| !has_prefix(**k, e, prefix))
+            .map(|(k, e)| (*k, e.clone()))
+            .collect();
+        for (hkey, e) in defaults.iter() {
+            if has_prefix(*hkey, e, prefix) {
+                params.insert(*hkey, e.clone());
+            }
+        }
+        params
+    });
+}
+
+/// Atomically replace the frozen global storage with a modified copy.
+///
+/// `update` may be called again if another update raced with it.
+pub(crate) fn update_global_params<F: FnMut(&mut Params)>(mut update: F) {
+    if rejected_after_hard_freeze(|| "update of the frozen global storage".to_string()) {
+        return;
+    }
+    GLOBAL_PARAMS.rcu(|global| {
+        let mut params = (**global).clone();
+        update(&mut params);
+        params
     });
 }
 
+/// Take a copy of the parameters in the frozen global storage.
+pub fn frozen_params() -> Params {
+    GLOBAL_PARAMS.load().as_ref().clone()
+}
+
+static HISTORY_LIMIT: AtomicUsize = AtomicUsize::new(0);
+
+/// Limit how many versions of a single parameter a storage keeps.
+///
+/// Once a parameter reaches the limit, the scope writing it falls back to
+/// snapshotting the whole parameter map on its first overflowing write and
+/// updating values in place afterwards; exiting the scope restores the
+/// snapshot. This bounds per-key memory for deep scope nesting at the cost of
+/// one map copy per overflowing scope. `None` removes the limit.
+///
+/// The limit applies to the current thread and to threads created afterwards.
+pub fn set_history_limit(limit: Option<usize>) {
+    let limit = limit.map(|n| n.max(1));
+    HISTORY_LIMIT.store(limit.unwrap_or(0), Ordering::Relaxed);
+    THREAD_STORAGE.with(|ts| ts.borrow_mut().history_limit = limit);
+}
+
+static TEXT_LIMIT: AtomicUsize = AtomicUsize::new(0);
+static USER_DEFINED_LIMIT: AtomicUsize = AtomicUsize::new(0);
+static REJECTED_TEXT: AtomicU64 = AtomicU64::new(0);
+static REJECTED_USER_DEFINED: AtomicU64 = AtomicU64::new(0);
+
+/// Limit the length in bytes of `Text` values. `None` removes the limit.
+///
+/// Writes exceeding the limit are rejected: `try_put` returns an error and
+/// `put` prints a warning and leaves the parameter unchanged.
+pub fn set_text_limit(limit: Option<usize>) {
+    TEXT_LIMIT.store(limit.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Limit how many `UserDefined` values a single storage holds. `None` removes the limit.
+///
+/// Replacing an existing `UserDefined` value does not count as a new one.
+pub fn set_user_defined_limit(limit: Option<usize>) {
+    USER_DEFINED_LIMIT.store(limit.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Number of writes rejected by the size limits since the process started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GuardrailStats {
+    pub rejected_text: u64,
+    pub rejected_user_defined: u64,
+}
+
+pub fn guardrail_stats() -> GuardrailStats {
+    GuardrailStats {
+        rejected_text: REJECTED_TEXT.load(Ordering::Relaxed),
+        rejected_user_defined: REJECTED_USER_DEFINED.load(Ordering::Relaxed),
+    }
+}
+
+pub(crate) fn check_text_limit(val: &Value) -> Result<(), String> {
+    let limit = TEXT_LIMIT.load(Ordering::Relaxed);
+    match val {
+        Value::Text(s) if limit > 0 && s.len() > limit => {
+            REJECTED_TEXT.fetch_add(1, Ordering::Relaxed);
+            Err(format!(
+                "text value of {} bytes exceeds the limit of {} bytes",
+                s.len(),
+                limit
+            ))
+        }
+        Value::List(vals) => vals.iter().try_for_each(check_text_limit),
+        _ => Ok(()),
+    }
+}
+
 #[derive(Debug)]
 pub struct Storage {
-    pub params: Params,
+    /// Only written through the methods below, which keep `captured` in sync.
+    pub(crate) params: Params,
     pub history: Vec<HashSet<u64>>,
+    snapshots: Vec<Option<Params>>,
+    history_limit: Option<usize>,
+    /// Scopes entered on top of `history` that have not written anything yet.
+    lazy_frames: usize,
+    pub(crate) subscribers: Subscribers,
+    /// The last result of `capture`, dropped by every write.
+    captured: RefCell<Option<Params>>,
 }
 
 unsafe impl Send for Storage {}
 
 impl Default for Storage {
     fn default() -> Self {
+        let limit = HISTORY_LIMIT.load(Ordering::Relaxed);
         Storage {
-            params: Params::new(),
+            params: Params::default(),
             history: vec![HashSet::new()],
+            snapshots: vec![None],
+            history_limit: if limit == 0 { None } else { Some(limit) },
+            lazy_frames: 0,
+            subscribers: Subscribers::default(),
+            captured: RefCell::new(None),
         }
     }
 }
 
 impl Storage {
+    /// Enter a new scope.
+    ///
+    /// The scope is only recorded once it writes its first parameter, so
+    /// entering and exiting scopes that only read is nearly free.
     pub fn enter(&mut self) {
-        self.history.push(HashSet::new());
+        self.lazy_frames += 1;
     }
 
     pub fn exit(&mut self) -> Params {
-        let mut changes = Params::new();
-        for key in self.history.pop().unwrap() {
-            changes.insert(key, self.params.get(&key).unwrap().shallow());
-            self.params.rollback(key);
+        if self.lazy_frames > 0 {
+            self.lazy_frames -= 1;
+            return Params::default();
+        }
+        self.captured.take();
+        let mut changes = Params::default();
+        let keys = self.history.pop().unwrap();
+        for key in keys.iter() {
+            if let Some(e) = self.params.get(key) {
+                changes.insert(*key, e.shallow());
+            }
+        }
+        let watched: Vec<(u64, Value)> = keys
+            .iter()
+            .filter(|key| self.subscribers.watches(**key))
+            .map(|key| (*key, self.current(*key)))
+            .collect();
+        match self.snapshots.pop().unwrap() {
+            Some(snapshot) => self.params = snapshot,
+            None => keys.into_iter().for_each(|key| self.params.rollback(key)),
+        }
+        for (key, old) in watched {
+            let new = self.current(key);
+            self.subscribers.changed(key, old, new);
         }
         changes
     }
 
+    /// Number of scopes entered and not yet exited.
+    pub fn depth(&self) -> usize {
+        self.history.len() - 1 + self.lazy_frames
+    }
+
+    /// The current value of `hkey`, without recording a read.
+    fn current(&self, hkey: u64) -> Value {
+        self.params
+            .get(&hkey)
+            .map(|e| e.clone_value())
+            .unwrap_or(Value::Empty)
+    }
+
+    /// Write a new version of `hkey` into the current scope, queueing notifications for its subscribers
+    /// and recording it in the audit log.
+    fn write<V: Into<Value>>(&mut self, hkey: u64, key: Option<Arc<str>>, val: V) {
+        let rejected = rejected_after_hard_freeze(|| {
+            let name = key
+                .as_deref()
+                .map(str::to_string)
+                .or_else(|| self.key_name(hkey).map(str::to_string))
+                .unwrap_or_else(|| crate::xxh::describe_key(hkey));
+            format!("write to parameter `{}`", name)
+        });
+        if rejected {
+            return;
+        }
+        let audited = crate::audit::enabled();
+        let watched = self.subscribers.watches(hkey);
+        if !audited && !watched {
+            return self.write_unwatched(hkey, key, val);
+        }
+        let old = self.current(hkey);
+        self.write_unwatched(hkey, key, val);
+        let new = self.current(hkey);
+        if audited {
+            // deleting a missing parameter writes nothing
+            if self.params.contains_key(&hkey) {
+                let name = self
+                    .key_name(hkey)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| crate::xxh::describe_key(hkey));
+                crate::audit::record_mutation(hkey, name, &old, &new, self.depth());
+            }
+        }
+        if watched {
+            self.subscribers.changed(hkey, old, new);
+        }
+    }
+
+    /// Write a new version of `hkey` into the current scope.
+    ///
+    /// `key` is only used when the parameter does not exist yet; without it
+    /// nothing is written for missing parameters.
+    fn write_unwatched<V: Into<Value>>(&mut self, hkey: u64, key: Option<Arc<str>>, val: V) {
+        self.captured.take();
+        for _ in 0..std::mem::take(&mut self.lazy_frames) {
+            self.history.push(HashSet::new());
+            self.snapshots.push(None);
+        }
+        if self.history.last().unwrap().contains(&hkey) {
+            self.params.update(hkey, val);
+            return;
+        }
+        let overflow = match self.params.get(&hkey) {
+            None => None,
+            Some(e) => Some(
+                self.snapshots.last().unwrap().is_some()
+                    || self.history_limit.is_some_and(|n| e.val.depth() >= n),
+            ),
+        };
+        match (overflow, key) {
+            (None, Some(key)) => {
+                self.params.insert(hkey, Entry::new(key, val));
+            }
+            (None, None) => return,
+            (Some(true), _) => {
+                self.snapshot_scope();
+                self.params.update(hkey, val);
+            }
+            (Some(false), _) => self.params.revision(hkey, val),
+        }
+        self.history.last_mut().unwrap().insert(hkey);
+    }
+
+    /// Save the parameters as they were before the current scope was entered.
+    fn snapshot_scope(&mut self) {
+        if self.history.len() <= 1 || self.snapshots.last().unwrap().is_some() {
+            return;
+        }
+        let mut snapshot = self.params.clone();
+        for key in self.history.last().unwrap().iter() {
+            snapshot.rollback(*key);
+        }
+        *self.snapshots.last_mut().unwrap() = Some(snapshot);
+    }
+
     pub fn get_entry(&self, key: u64) -> Option<&Entry> {
-        self.params.get(&key)
+        let e = self.params.get(&key);
+        #[cfg(feature = "hardware")]
+        let e = e.or_else(|| crate::hardware::hardware_fact(key));
+        #[cfg(feature = "tracking")]
+        crate::tracking::record_read(key, e.map(|e| &*e.key));
+        crate::reads::record_read(key, e, self.depth());
+        match e {
+            Some(e) if !matches!(e.value(), Value::Empty) => {}
+            _ => crate::strict::unknown_read(key, self),
+        }
+        e
+    }
+
+    /// The name of a hashed key, for diagnostics.
+    ///
+    /// Keys stored here are always known. Other keys are known if they were
+    /// interned, see the `interning` feature, or registered by `get_param!`.
+    pub fn key_name(&self, hkey: u64) -> Option<&str> {
+        self.params
+            .get(&hkey)
+            .map(|e| &*e.key)
+            .filter(|k| !k.is_empty())
+            .or_else(|| crate::xxh::key_name(hkey))
     }
 
     pub fn put_entry(&mut self, key: u64, entry: Entry) -> Option<Entry> {
+        self.captured.take();
         self.params.insert(key, entry)
     }
 
     pub fn del_entry(&mut self, key: u64) {
+        self.captured.take();
         self.params.remove(&key);
     }
 
     pub fn get<T: XXHashable>(&self, key: T) -> &Value {
         let hkey = key.xxh();
-        if let Some(e) = self.params.get(&hkey) {
+        if let Some(e) = self.get_entry(hkey) {
             e.value()
         } else {
             &EMPTY
         }
     }
 
+    /// Get a parameter, or `None` if it is not set.
+    pub fn try_get<T: XXHashable>(&self, key: T) -> Option<&Value> {
+        self.get_entry(key.xxh())
+            .map(|e| e.value())
+            .filter(|v| !matches!(v, Value::Empty))
+    }
+
+    /// The value a typed read of `hkey` converts, if the parameter is set.
+    fn effective_value(&self, hkey: u64) -> Option<Cow<'_, Value>> {
+        let e = self
+            .get_entry(hkey)
+            .filter(|e| !matches!(e.value(), Value::Empty))
+            .filter(|e| !crate::mode::defaults_only(&e.key))?;
+        #[cfg(feature = "chaos")]
+        if let Some(v) = crate::chaos::perturb(hkey, e) {
+            return Some(Cow::Owned(v));
+        }
+        Some(Cow::Borrowed(e.value()))
+    }
+
+    /// Get a parameter that has no sensible default.
+    ///
+    /// Fails with [`MissingParamError::Missing`] if the parameter is not set,
+    /// or [`MissingParamError::Mismatch`] if its value does not convert to `V`.
+    pub fn get_required<V>(&self, key: &str) -> Result<V, MissingParamError>
+    where
+        V: for<'a> TryFrom<&'a Value, Error = ValueError>,
+    {
+        let val = self
+            .effective_value(key.xxh())
+            .ok_or_else(|| MissingParamError::missing(key))?;
+        V::try_from(&val).map_err(|err| MissingParamError::mismatch(key, &val, err))
+    }
+
+    /// Get a parameter, or `None` if it is not set.
+    ///
+    /// A value that does not convert to `V` panics in debug builds, and is
+    /// ignored with a warning in release builds.
+    pub fn get_opt<K: XXHashable, V>(&self, key: K) -> Option<V>
+    where
+        V: for<'a> TryFrom<&'a Value, Error = ValueError>,
+    {
+        self.get_opt_with_hash(key.xxh())
+    }
+
+    /// Get a parameter by hashed key, see [`Storage::get_opt`].
+    pub fn get_opt_with_hash<V>(&self, hkey: u64) -> Option<V>
+    where
+        V: for<'a> TryFrom<&'a Value, Error = ValueError>,
+    {
+        let val = self.effective_value(hkey)?;
+        let name = || {
+            self.key_name(hkey)
+                .map(str::to_string)
+                .unwrap_or_else(|| crate::xxh::describe_key(hkey))
+        };
+        convert_opt(name, &val)
+    }
+
+    /// Get the first set parameter among `hkeys`, see [`get_param_hier!`](crate::get_param_hier).
+    ///
+    /// `None` if none is set, or if the first set one does not convert to `V`.
+    pub fn get_hier_with_hashes<V>(&self, hkeys: &[u64]) -> Option<V>
+    where
+        V: for<'a> TryFrom<&'a Value, Error = ValueError>,
+    {
+        let val = hkeys.iter().find_map(|hkey| self.effective_value(*hkey))?;
+        V::try_from(&val).ok()
+    }
+
+    /// Call `f` with a text parameter, borrowed rather than cloned.
+    ///
+    /// `f` gets `default` if the parameter is not set or is not text.
+    pub fn with_str<K: XXHashable, R>(
+        &self,
+        key: K,
+        default: &str,
+        f: impl FnOnce(&str) -> R,
+    ) -> R {
+        match self.effective_value(key.xxh()).as_deref() {
+            Some(Value::Text(s)) => f(s),
+            _ => f(default),
+        }
+    }
+
+    /// Put a parameter. Putting `Value::Empty` deletes the parameter instead.
     pub fn put<T: Into<String> + XXHashable, V: Into<Value> + Clone>(&mut self, key: T, val: V) {
+        if let Err(e) = self.try_put(key, val) {
+            println!("hyperparameter warning: {}", e);
+        }
+    }
+
+    /// Put a parameter, failing if the value exceeds the configured size limits.
+    pub fn try_put<T: Into<String> + XXHashable, V: Into<Value> + Clone>(
+        &mut self,
+        key: T,
+        val: V,
+    ) -> Result<(), String> {
         let hkey = key.xxh();
         let key: String = key.into();
-        if self.history.last().unwrap().contains(&hkey) {
-            self.params.update(hkey, val);
-        } else {
-            if let std::collections::btree_map::Entry::Vacant(e) = self.params.entry(hkey) {
-                e.insert(Entry {
-                    key,
-                    val: VersionedValue::from(val.into()),
-                });
-            } else {
-                self.params.revision(hkey, val);
+        self.try_put_with_hash(hkey, key.into(), val.into())
+    }
+
+    /// Put a parameter by hashed key, sharing `key` with the entry it comes from.
+    pub(crate) fn try_put_with_hash(
+        &mut self,
+        hkey: u64,
+        key: Arc<str>,
+        val: Value,
+    ) -> Result<(), String> {
+        if matches!(val, Value::Empty) {
+            self.del_with_hash(hkey);
+            return Ok(());
+        }
+        check_text_limit(&val)?;
+        self.check_user_defined_limit(hkey, &val)?;
+        #[cfg(feature = "tracking")]
+        crate::tracking::record_write(hkey, &key);
+        self.write(hkey, Some(key), val);
+        Ok(())
+    }
+
+    fn check_user_defined_limit(&self, hkey: u64, val: &Value) -> Result<(), String> {
+        let limit = USER_DEFINED_LIMIT.load(Ordering::Relaxed);
+        let is_user_defined = |v: &Value| matches!(v, Value::UserDefined(..));
+        let replaces = |e: &Entry| is_user_defined(e.value());
+        if limit == 0 || !is_user_defined(val) || self.params.get(&hkey).is_some_and(replaces) {
+            return Ok(());
+        }
+        let count = self
+            .params
+            .values()
+            .filter(|e| is_user_defined(e.value()))
+            .count();
+        if count >= limit {
+            REJECTED_USER_DEFINED.fetch_add(1, Ordering::Relaxed);
+            return Err(format!(
+                "storage already holds {} user defined values, the limit is {}",
+                count, limit
+            ));
+        }
+        Ok(())
+    }
+
+    /// Put a parameter only if it is currently unset.
+    ///
+    /// Returns `true` if the value was written.
+    pub fn put_default<T: Into<String> + XXHashable, V: Into<Value> + Clone>(
+        &mut self,
+        key: T,
+        val: V,
+    ) -> bool {
+        let hkey = key.xxh();
+        match self.params.get(&hkey).map(|e| e.value()) {
+            None | Some(Value::Empty) => {
+                self.put(key, val);
+                true
             }
-            self.history.last_mut().unwrap().insert(hkey);
+            Some(_) => false,
         }
     }
 
+    /// Delete a parameter until the current scope exits.
+    ///
+    /// The deletion is kept as an internal `Value::Empty` version so that
+    /// exiting the scope restores the shadowed value; such versions are never
+    /// returned by `try_get`, `keys` or `capture`.
     pub fn del<T: XXHashable>(&mut self, key: T) {
-        let hkey = key.xxh();
-        if self.history.last().unwrap().contains(&hkey) {
-            self.params.update(hkey, None::<i32>);
-        } else {
-            self.params.revision(hkey, None::<i32>);
-            self.history.last_mut().unwrap().insert(hkey);
+        self.del_with_hash(key.xxh());
+    }
+
+    /// Delete a parameter by hashed key, see [`Storage::del`].
+    pub fn del_with_hash(&mut self, hkey: u64) {
+        #[cfg(feature = "tracking")]
+        if let Some(e) = self.params.get(&hkey) {
+            crate::tracking::record_write(hkey, &e.key);
         }
+        self.write(hkey, None, None::<i32>);
+    }
+
+    /// Move the current thread's storage out of the thread-local.
+    ///
+    /// A flattened copy of the visible parameters is left in its place, so
+    /// callbacks that re-enter during a blocking call still read the same
+    /// values. Anything they write is discarded when the returned token is
+    /// reattached, either explicitly with [`DetachedStorage::reattach`] or
+    /// when it is dropped.
+    pub fn detach() -> DetachedStorage {
+        THREAD_STORAGE.with(|ts| {
+            let mut ts = ts.borrow_mut();
+            let sparse = Storage {
+                params: ts.capture(),
+                history_limit: ts.history_limit,
+                ..Storage::default()
+            };
+            DetachedStorage {
+                storage: Some(std::mem::replace(&mut *ts, sparse)),
+                _thread: PhantomData,
+            }
+        })
+    }
+
+    /// The parameters of this storage, including the deleted ones, which hold
+    /// [`Value::Empty`].
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// let mut s = Storage::default();
+    /// s.put("storage.params", 1);
+    /// assert_eq!(1, s.params().len());
+    /// ```
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Take a flattened copy of the parameters currently visible in this storage.
+    ///
+    /// The copy is kept until the next write, so capturing again in between
+    /// shares it instead of building a new one.
+    pub fn capture(&self) -> Params {
+        let mut captured = self.captured.borrow_mut();
+        let params = captured.get_or_insert_with(|| {
+            self.params
+                .iter()
+                .filter(|(_, e)| !matches!(e.value(), Value::Empty))
+                .map(|(k, e)| (*k, e.shallow()))
+                .collect()
+        });
+        params.clone()
+    }
+
+    /// The parameters whose key is under the dotted `prefix`, keyed by the
+    /// rest of their key.
+    pub fn namespace(&self, prefix: &str) -> BTreeMap<String, Value> {
+        self.select(|key| strip_namespace(key, prefix))
+    }
+
+    /// The parameters whose key matches the glob `pattern`, see [`key_matches`].
+    pub fn query(&self, pattern: &str) -> BTreeMap<String, Value> {
+        self.select(|key| key_matches(pattern, key).then_some(key))
+    }
+
+    /// The parameters for which `name` gives a name, keyed by that name.
+    fn select(&self, name: impl Fn(&str) -> Option<&str>) -> BTreeMap<String, Value> {
+        self.params
+            .iter()
+            .filter_map(|(hkey, e)| {
+                let name = name(&e.key)?;
+                let val = self.effective_value(*hkey)?;
+                Some((name.to_string(), val.into_owned()))
+            })
+            .collect()
     }
 
     pub fn keys(&self) -> Vec<String> {
         self.params
             .values()
             .filter(|x| !matches!(x.value(), Value::Empty))
-            .map(|x| x.key.clone())
+            .map(|x| x.key.to_string())
             .collect()
     }
 }
 
+/// The rest of `key` after the dotted `prefix`, or `None` if `key` is not under it.
+///
+/// `"train"` and `"train."` both cover `"train.lr"` but not `"trainer.lr"`;
+/// an empty prefix covers every key.
+pub(crate) fn strip_namespace<'a>(key: &'a str, prefix: &str) -> Option<&'a str> {
+    let prefix = prefix.trim_end_matches('.');
+    if prefix.is_empty() {
+        return Some(key);
+    }
+    key.strip_prefix(prefix)?.strip_prefix('.')
+}
+
+/// Whether the dotted `key` matches the glob `pattern`.
+///
+/// Patterns are matched segment by segment: `*` within a segment matches
+/// any part of one segment, and a `**` segment matches any number of
+/// segments, so `model.*.dropout` matches `model.layer1.dropout` and
+/// `model.**` matches every key under `model`.
+pub fn key_matches(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('.').collect();
+    let key: Vec<&str> = key.split('.').collect();
+    segments_match(&pattern, &key)
+}
+
+fn segments_match(pattern: &[&str], key: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => key.is_empty(),
+        Some((&"**", rest)) => (0..=key.len()).any(|i| segments_match(rest, &key[i..])),
+        Some((p, rest)) => match key.split_first() {
+            Some((k, key_rest)) => segment_matches(p, k) && segments_match(rest, key_rest),
+            None => false,
+        },
+    }
+}
+
+fn segment_matches(pattern: &str, segment: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let mut rest = match segment.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let last = match parts.pop() {
+        Some(last) => last,
+        None => return rest.is_empty(),
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// A thread storage moved out by [`Storage::detach`].
+///
+/// The token can not leave the thread it was detached on, and puts the
+/// storage back when dropped if it was not reattached explicitly.
+#[must_use = "the storage is reattached as soon as the token is dropped"]
+#[derive(Debug)]
+pub struct DetachedStorage {
+    storage: Option<Storage>,
+    _thread: PhantomData<*const ()>,
+}
+
+impl DetachedStorage {
+    /// Put the detached storage back into the thread-local.
+    pub fn reattach(mut self) {
+        self.restore();
+    }
+
+    fn restore(&mut self) {
+        if let Some(storage) = self.storage.take() {
+            THREAD_STORAGE.with(|ts| *ts.borrow_mut() = storage);
+        }
+    }
+}
+
+impl Drop for DetachedStorage {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+/// A required parameter that could not be read, see [`Storage::get_required`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MissingParamError {
+    /// The parameter is not set.
+    Missing { key: String },
+    /// The parameter is set, but its value does not convert to the requested type.
+    Mismatch {
+        key: String,
+        value: Value,
+        error: ValueError,
+    },
+}
+
+impl MissingParamError {
+    pub(crate) fn missing(key: &str) -> MissingParamError {
+        MissingParamError::Missing {
+            key: key.to_string(),
+        }
+    }
+
+    pub(crate) fn mismatch(key: &str, value: &Value, error: ValueError) -> MissingParamError {
+        MissingParamError::Mismatch {
+            key: key.to_string(),
+            value: value.clone(),
+            error,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        match self {
+            MissingParamError::Missing { key } => key,
+            MissingParamError::Mismatch { key, .. } => key,
+        }
+    }
+}
+
+impl std::fmt::Display for MissingParamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MissingParamError::Missing { key } => {
+                write!(f, "required parameter `{}` is not set", key)
+            }
+            MissingParamError::Mismatch { key, error, .. } => {
+                write!(f, "required parameter `{}` is invalid: {}", key, error)
+            }
+        }
+    }
+}
+
 pub trait Hashable {}
 
 impl Hashable for String {}
@@ -195,16 +1026,42 @@ impl Hashable for &str {}
 
 impl Hashable for str {}
 
+/// Convert the value of an optional parameter, see [`Storage::get_opt`].
+pub(crate) fn convert_opt<V, F>(name: F, val: &Value) -> Option<V>
+where
+    V: for<'a> TryFrom<&'a Value, Error = ValueError>,
+    F: FnOnce() -> String,
+{
+    match V::try_from(val) {
+        Ok(v) => Some(v),
+        Err(err) => {
+            let message = format!("optional parameter `{}` is invalid: {}", name(), err);
+            if cfg!(debug_assertions) {
+                panic!("{}", message);
+            }
+            println!("hyperparameter warning: {}, ignored", message);
+            None
+        }
+    }
+}
+
 pub trait GetOrElse<K, T> {
     fn get_or_else(&self, key: K, dval: T) -> T;
 }
 
 impl<T> GetOrElse<u64, T> for Storage
 where
-    T: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value>,
+    T: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value, Error = ValueError>,
 {
     fn get_or_else(&self, key: u64, dval: T) -> T {
-        if let Some(val) = self.params.get(&key) {
+        if let Some(val) = self.get_entry(key) {
+            if crate::mode::defaults_only(&val.key) {
+                return dval;
+            }
+            #[cfg(feature = "chaos")]
+            if let Some(v) = crate::chaos::perturb(key, val) {
+                return (&v).try_into().unwrap_or(dval);
+            }
             match val.value().try_into() {
                 Ok(v) => v,
                 Err(_) => dval,
@@ -217,8 +1074,8 @@ where
 
 impl<K, T> GetOrElse<K, T> for Storage
 where
-    K: Into<String> + XXHashable,
-    T: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value>,
+    K: XXHashable,
+    T: Into<Value> + TryFrom<Value> + for<'a> TryFrom<&'a Value, Error = ValueError>,
 {
     fn get_or_else(&self, key: K, dval: T) -> T {
         let hkey = key.xxh();
@@ -226,16 +1083,96 @@ where
     }
 }
 
+/// Run the test `name` of this crate again in a child process, for tests
+/// that change process-wide state such as [`frozen_global_storage_hard`].
+///
+/// Returns true in the child, which runs the test body, and false in the
+/// parent once the child passed.
+#[cfg(test)]
+pub(crate) fn in_child_process(name: &str) -> bool {
+    const CHILD: &str = "HYPERPARAMETER_TEST_CHILD";
+    if std::env::var_os(CHILD).is_some() {
+        return true;
+    }
+    let name = name.trim_start_matches(concat!(env!("CARGO_CRATE_NAME"), "::"));
+    let status = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([name, "--exact", "--nocapture"])
+        .env(CHILD, "1")
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "test `{}` failed in a child process",
+        name
+    );
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::GetOrElse;
     use super::Storage;
+    use crate::value::Value;
+    use crate::xxh::XXHashable;
 
     #[test]
     fn test_storage_create() {
         let _ = Storage::default();
     }
 
+    #[test]
+    fn test_storage_with_str() {
+        let mut s = Storage::default();
+        s.put("storage.text", "adam".to_string());
+        s.put("storage.int", 1);
+        assert_eq!(4, s.with_str("storage.text", "", str::len));
+        assert_eq!("sgd", s.with_str("storage.int", "sgd", str::to_string));
+        assert_eq!("sgd", s.with_str("storage.missing", "sgd", str::to_string));
+    }
+
+    #[test]
+    fn test_storage_capture_shares_text() {
+        let mut s = Storage::default();
+        s.put("storage.text", "adam".to_string());
+        let captured = s.capture();
+        let (a, b) = (
+            &s.params[&"storage.text".xxh()],
+            &captured[&"storage.text".xxh()],
+        );
+        assert!(std::sync::Arc::ptr_eq(&a.key, &b.key));
+        match (a.value(), b.value()) {
+            (Value::Text(x), Value::Text(y)) => assert!(std::sync::Arc::ptr_eq(x, y)),
+            other => panic!("unexpected values: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_storage_capture_until_write() {
+        let mut s = Storage::default();
+        s.put("storage.a", 1);
+        let first = s.capture();
+        assert!(std::ptr::eq(&*first, &*s.capture()));
+
+        s.enter();
+        s.put("storage.a", 2);
+        let second = s.capture();
+        assert_eq!(&Value::Int(1), first[&"storage.a".xxh()].value());
+        assert_eq!(&Value::Int(2), second[&"storage.a".xxh()].value());
+        s.exit();
+        assert_eq!(&Value::Int(1), s.capture()[&"storage.a".xxh()].value());
+    }
+
+    #[test]
+    fn test_storage_key_name() {
+        let mut s = Storage::default();
+        s.put("storage.named", 1);
+        assert_eq!(Some("storage.named"), s.key_name("storage.named".xxh()));
+
+        let hkey = "storage.interned".xxh();
+        crate::xxh::intern(hkey, "storage.interned");
+        assert_eq!(Some("storage.interned"), s.key_name(hkey));
+    }
+
     #[test]
     fn test_storage_put_get() {
         let mut s = Storage::default();
@@ -268,6 +1205,18 @@ mod tests {
         assert_eq!(true, s.get_or_else("bool", false));
     }
 
+    #[test]
+    fn test_storage_get_or_else_borrowed_keys() {
+        let mut s = Storage::default();
+        s.put("a.b", 1);
+
+        let line = "a.b=2";
+        let (key, _) = line.split_once('=').unwrap();
+        assert_eq!(1, s.get_or_else(key, 0));
+        assert_eq!(1, s.get_or_else(std::borrow::Cow::Borrowed(key), 0));
+        assert_eq!(1, s.get_or_else(Box::<str>::from(key), 0));
+    }
+
     #[test]
     fn test_storage_enter_exit() {
         let mut s0 = Storage::default();
@@ -298,4 +1247,229 @@ mod tests {
         let v: f64 = s0.get("b").clone().try_into().unwrap();
         assert_eq!(2.0, v);
     }
+
+    #[test]
+    fn test_storage_lazy_enter() {
+        let mut s = Storage::default();
+        s.put("a", 1);
+        s.enter();
+        s.enter();
+        assert_eq!(2, s.depth());
+        assert_eq!(1, s.history.len());
+
+        s.put("a", 2);
+        assert_eq!(3, s.history.len());
+        s.enter();
+        assert_eq!(3, s.depth());
+        assert!(s.exit().is_empty());
+        assert_eq!(2, s.get_or_else("a", 0));
+        assert_eq!(1, s.exit().len());
+        assert_eq!(1, s.get_or_else("a", 0));
+        assert!(s.exit().is_empty());
+        assert_eq!(0, s.depth());
+    }
+
+    #[test]
+    fn test_storage_bounded_history() {
+        let mut s = Storage {
+            history_limit: Some(2),
+            ..Storage::default()
+        };
+        s.put("a", 0);
+        s.put("b", 0);
+        for i in 1..=5 {
+            s.enter();
+            s.put("a", i);
+            assert!(s.params.get(&"a".xxh()).unwrap().val.depth() <= 2);
+        }
+        s.put("b", 5);
+        assert_eq!(5, s.get_or_else("a", -1));
+
+        for i in (0..5).rev() {
+            s.exit();
+            assert_eq!(i, s.get_or_else("a", -1));
+            assert_eq!(0, s.get_or_else("b", -1));
+        }
+    }
+
+    #[test]
+    fn test_storage_del() {
+        let mut s = Storage::default();
+        s.put("a", 1);
+        assert_eq!(None, s.try_get("b"));
+
+        s.enter();
+        s.del("a");
+        assert_eq!(None, s.try_get("a"));
+        assert!(s.keys().is_empty());
+        s.put("b", Value::Empty);
+        assert_eq!(None, s.try_get("b"));
+        assert!(!s.params.contains_key(&"b".xxh()));
+        s.exit();
+
+        assert_eq!(Some(&Value::Int(1)), s.try_get("a"));
+    }
+
+    #[test]
+    fn test_storage_size_limits() {
+        use std::ffi::c_void;
+
+        // the limits and counters are global, keep them away from the other tests
+        if !super::in_child_process(concat!(module_path!(), "::test_storage_size_limits")) {
+            return;
+        }
+        let before = super::guardrail_stats();
+        super::set_text_limit(Some(1024));
+        super::set_user_defined_limit(Some(1));
+
+        let mut s = Storage::default();
+        assert!(s.try_put("text", "x".repeat(1024)).is_ok());
+        assert!(s.try_put("text", "x".repeat(1025)).is_err());
+        s.put("text", "y".repeat(2048));
+        assert_eq!(1024, s.get_or_else("text", String::new()).len());
+
+        assert!(s.try_put("obj.a", 0x1234 as *mut c_void).is_ok());
+        assert!(s.try_put("obj.a", 0x1235 as *mut c_void).is_ok());
+        assert!(s.try_put("obj.b", 0x1236 as *mut c_void).is_err());
+        assert_eq!(Value::Empty, *s.get("obj.b"));
+
+        super::set_text_limit(None);
+        super::set_user_defined_limit(None);
+        let after = super::guardrail_stats();
+        assert_eq!(before.rejected_text + 2, after.rejected_text);
+        assert_eq!(
+            before.rejected_user_defined + 1,
+            after.rejected_user_defined
+        );
+    }
+
+    #[test]
+    fn test_storage_detach() {
+        use super::THREAD_STORAGE;
+
+        let read = || THREAD_STORAGE.with(|ts| ts.borrow().get_or_else("detach.a", 0));
+        let put = |v: i64| THREAD_STORAGE.with(|ts| ts.borrow_mut().put("detach.a", v));
+        let enter = || THREAD_STORAGE.with(|ts| ts.borrow_mut().enter());
+        let exit = || THREAD_STORAGE.with(|ts| ts.borrow_mut().exit());
+
+        put(1);
+        enter();
+        put(2);
+
+        let detached = Storage::detach();
+        assert_eq!(2, read());
+        put(3);
+        assert_eq!(3, read());
+        detached.reattach();
+
+        assert_eq!(2, read());
+        exit();
+        assert_eq!(1, read());
+
+        {
+            let _detached = Storage::detach();
+            put(4);
+        }
+        assert_eq!(1, read());
+    }
+
+    #[test]
+    fn test_storage_put_default() {
+        let mut s = Storage::default();
+        assert!(s.put_default("a", 1));
+        assert!(!s.put_default("a", 2));
+        assert_eq!(1, s.get_or_else("a", 0));
+
+        s.enter();
+        s.del("a");
+        assert!(s.put_default("a", 3));
+        assert_eq!(3, s.get_or_else("a", 0));
+        s.exit();
+        assert_eq!(1, s.get_or_else("a", 0));
+    }
+
+    /// Set `hard.a` on this thread and hard freeze it, then check that
+    /// `mutate` is rejected and leaves the frozen global storage untouched.
+    fn assert_rejected_after_hard_freeze<F: FnOnce() + std::panic::UnwindSafe>(mutate: F) {
+        super::THREAD_STORAGE.with(|ts| ts.borrow_mut().put("hard.a", 1));
+        super::frozen_global_storage_hard();
+        let rejected = std::panic::catch_unwind(mutate).is_err();
+        assert_eq!(cfg!(debug_assertions), rejected);
+        let frozen = super::frozen_params();
+        assert_eq!(
+            Some(Value::Int(1)),
+            frozen.get(&"hard.a".xxh()).map(|e| e.clone_value())
+        );
+        assert!(frozen.get(&"hard.b".xxh()).is_none());
+    }
+
+    #[test]
+    fn test_hard_freeze_rejects_frozen() {
+        if !super::in_child_process(concat!(module_path!(), "::test_hard_freeze_rejects_frozen")) {
+            return;
+        }
+        assert_rejected_after_hard_freeze(crate::frozen);
+    }
+
+    #[test]
+    fn test_freeze_prefix_hash_only() {
+        use super::THREAD_STORAGE;
+
+        if !super::in_child_process(concat!(module_path!(), "::test_freeze_prefix_hash_only")) {
+            return;
+        }
+        // written by hash only, as through the C API, with a registered name
+        let hkey = "prefix.hash.a".xxh();
+        crate::xxh::intern(hkey, "prefix.hash.a");
+        crate::with_params! {
+            THREAD_STORAGE.with(|ts| ts.borrow_mut().try_put_with_hash(hkey, "".into(), Value::Int(1))).unwrap();
+            crate::freeze_prefix("prefix.hash.");
+        }
+        let read = move || {
+            std::thread::spawn(move || crate::ParamScope::Nothing.get_with_hash(hkey))
+                .join()
+                .unwrap()
+        };
+        assert_eq!(Value::Int(1), read());
+        crate::unfreeze_prefix("prefix.hash.");
+        assert_eq!(Value::Empty, read());
+    }
+
+    #[test]
+    fn test_hard_freeze_rejects_freeze_prefix() {
+        if !super::in_child_process(concat!(
+            module_path!(),
+            "::test_hard_freeze_rejects_freeze_prefix"
+        )) {
+            return;
+        }
+        assert_rejected_after_hard_freeze(|| crate::freeze_prefix("hard."));
+    }
+
+    #[test]
+    fn test_hard_freeze_rejects_unfreeze_prefix() {
+        if !super::in_child_process(concat!(
+            module_path!(),
+            "::test_hard_freeze_rejects_unfreeze_prefix"
+        )) {
+            return;
+        }
+        assert_rejected_after_hard_freeze(|| crate::unfreeze_prefix("hard."));
+    }
+
+    #[test]
+    fn test_hard_freeze_rejects_update_global_params() {
+        if !super::in_child_process(concat!(
+            module_path!(),
+            "::test_hard_freeze_rejects_update_global_params"
+        )) {
+            return;
+        }
+        assert_rejected_after_hard_freeze(|| {
+            super::update_global_params(|global| {
+                global.clear();
+                global.insert("hard.b".xxh(), super::Entry::new("hard.b", 2));
+            })
+        });
+    }
 }
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::storage::Storage;
+use crate::xxh::{key_name, registered_key};
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref WARNED: Mutex<HashSet<u64>> = Mutex::new(HashSet::new());
+}
+
+/// Warn about reads of parameters that are neither set nor registered.
+///
+/// In strict mode, the first read of such a parameter prints a warning
+/// naming the closest known key, which is usually what a typo meant. Keys
+/// read by `get_param!` count as registered when the `registry` feature is
+/// enabled. Strict mode is off by default, as it interns the names of the
+/// keys hashed at runtime to report them, like the `interning` feature.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// set_strict(true);
+/// with_params! {
+///     set doc.strict.learning_rate = 0.1;
+///
+///     // hyperparameter warning: read parameter `doc.strict.learning_rat` that
+///     // is neither set nor registered, did you mean `doc.strict.learning_rate`?
+///     let lr: f64 = ParamScope::Nothing.get_or_else("doc.strict.learning_rat", 0.01);
+///     assert_eq!(0.01, lr);
+/// }
+/// set_strict(false);
+/// ```
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
+}
+
+pub(crate) fn is_strict() -> bool {
+    STRICT.load(Ordering::Relaxed)
+}
+
+#[cfg(feature = "registry")]
+fn registered_keys() -> Vec<&'static str> {
+    crate::PARAM_REGISTRY.iter().map(|p| p.key).collect()
+}
+
+#[cfg(not(feature = "registry"))]
+fn registered_keys() -> Vec<&'static str> {
+    Vec::new()
+}
+
+/// Report a read of `hkey` that found no value in `storage`, once per key.
+pub(crate) fn unknown_read(hkey: u64, storage: &Storage) {
+    if !is_strict() || registered_key(hkey).is_some() {
+        return;
+    }
+    if WARNED.lock().unwrap().insert(hkey) {
+        println!("hyperparameter warning: {}", warning(hkey, storage));
+    }
+}
+
+fn warning(hkey: u64, storage: &Storage) -> String {
+    let name = match key_name(hkey) {
+        Some(name) => name,
+        None => {
+            return format!(
+                "read parameter {:#018x} that is neither set nor registered",
+                hkey
+            )
+        }
+    };
+    let keys = storage.keys();
+    let known = keys.iter().map(String::as_str).chain(registered_keys());
+    match closest(name, known) {
+        Some(key) => format!(
+            "read parameter `{}` that is neither set nor registered, did you mean `{}`?",
+            name, key
+        ),
+        None => format!(
+            "read parameter `{}` that is neither set nor registered",
+            name
+        ),
+    }
+}
+
+/// The candidate closest to `key`, if it is close enough to be a typo.
+pub(crate) fn closest<'a, I>(key: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    candidates
+        .into_iter()
+        .filter(|c| *c != key)
+        .map(|c| (distance(key, c), c))
+        .filter(|(d, _)| *d <= 2.max(key.len() / 5))
+        .min()
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(cur).min(row[j])
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest, set_strict, warning, WARNED};
+    use crate::storage::Storage;
+    use crate::xxh::XXHashable;
+
+    #[test]
+    fn test_strict_warning() {
+        let mut s = Storage::default();
+        s.put("strict.batch_size", 32);
+
+        let hkey = "strict.bacth_size".xxh();
+        crate::xxh::intern(hkey, "strict.bacth_size");
+        assert_eq!(
+            "read parameter `strict.bacth_size` that is neither set nor registered, did you mean `strict.batch_size`?",
+            warning(hkey, &s)
+        );
+        if !cfg!(feature = "interning") {
+            assert_eq!(
+                format!(
+                    "read parameter {:#018x} that is neither set nor registered",
+                    "strict.unnamed".xxh()
+                ),
+                warning("strict.unnamed".xxh(), &s)
+            );
+        }
+
+        assert_eq!(None, closest("train.lr", ["eval.steps"]));
+        assert_eq!(
+            Some("train.lr"),
+            closest("train.lt", ["train.lr", "train.epochs"])
+        );
+    }
+
+    #[test]
+    fn test_strict_mode_records_unknown_reads() {
+        // strict mode is global, keep it away from the other tests
+        if !crate::storage::in_child_process(concat!(
+            module_path!(),
+            "::test_strict_mode_records_unknown_reads"
+        )) {
+            return;
+        }
+        set_strict(true);
+        let mut s = Storage::default();
+        s.put("strict.mode.batch_size", 32);
+        s.get("strict.mode.batch_size");
+        s.get("strict.mode.bacth_size");
+
+        let warned = WARNED.lock().unwrap();
+        assert!(warned.contains(&"strict.mode.bacth_size".xxh()));
+        assert!(!warned.contains(&"strict.mode.batch_size".xxh()));
+        assert_eq!(
+            Some("strict.mode.bacth_size"),
+            crate::xxh::key_name("strict.mode.bacth_size".xxh())
+        );
+    }
+}
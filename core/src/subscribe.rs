@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::storage::THREAD_STORAGE;
+use crate::value::Value;
+use crate::xxh::XXHashable;
+
+type Callback = Rc<dyn Fn(&Value, &Value)>;
+
+/// The keys watched on a thread storage, and the changes waiting to be delivered.
+///
+/// The callbacks themselves are kept apart in [`CALLBACKS`], as they are
+/// not `Send` while the storage is.
+#[derive(Debug, Default)]
+pub(crate) struct Subscribers {
+    watched: HashSet<u64>,
+    pending: Vec<(u64, Value, Value)>,
+}
+
+impl Subscribers {
+    pub(crate) fn watches(&self, hkey: u64) -> bool {
+        self.watched.contains(&hkey)
+    }
+
+    /// Queue a notification for the subscribers of `hkey` if the value changed.
+    pub(crate) fn changed(&mut self, hkey: u64, old: Value, new: Value) {
+        if self.watches(hkey) && !old.same(&new) {
+            self.pending.push((hkey, old, new));
+        }
+    }
+}
+
+#[derive(Default)]
+struct Callbacks {
+    next_id: u64,
+    by_key: BTreeMap<u64, Vec<(u64, Callback)>>,
+}
+
+thread_local! {
+    static CALLBACKS: RefCell<Callbacks> = RefCell::new(Callbacks::default());
+}
+
+/// Deliver the notifications queued on the current thread.
+///
+/// Called once the thread storage is no longer borrowed, so callbacks can
+/// read and write parameters.
+pub(crate) fn notify_subscribers() {
+    let pending =
+        THREAD_STORAGE.with(|ts| std::mem::take(&mut ts.borrow_mut().subscribers.pending));
+    for (hkey, old, new) in pending {
+        let callbacks: Vec<Callback> = CALLBACKS.with(|c| {
+            c.borrow()
+                .by_key
+                .get(&hkey)
+                .map(|callbacks| callbacks.iter().map(|(_, f)| f.clone()).collect())
+                .unwrap_or_default()
+        });
+        for callback in callbacks {
+            callback(&old, &new);
+        }
+    }
+}
+
+/// Call `callback` with the old and new value whenever `key` changes on the current thread.
+///
+/// Changes come from puts and deletes as well as from entering and exiting
+/// scopes; `Value::Empty` stands for an unset parameter. Callbacks run once
+/// the change is complete, so they may read and write parameters. The
+/// subscription lasts until the returned guard is dropped.
+///
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// use hyperparameter::*;
+///
+/// let seen = Rc::new(RefCell::new(Vec::new()));
+/// let _sub = {
+///     let seen = seen.clone();
+///     subscribe("model.dropout", move |old, new| {
+///         seen.borrow_mut().push((old.clone(), new.clone()));
+///     })
+/// };
+///
+/// with_params! {
+///     set model.dropout = 0.1;
+/// }
+/// assert_eq!(
+///     vec![
+///         (Value::Empty, Value::Float(0.1)),
+///         (Value::Float(0.1), Value::Empty),
+///     ],
+///     *seen.borrow()
+/// );
+/// ```
+pub fn subscribe<K, F>(key: K, callback: F) -> Subscription
+where
+    K: XXHashable,
+    F: Fn(&Value, &Value) + 'static,
+{
+    let hkey = key.xxh();
+    let id = CALLBACKS.with(|c| {
+        let mut c = c.borrow_mut();
+        c.next_id += 1;
+        let id = c.next_id;
+        c.by_key
+            .entry(hkey)
+            .or_default()
+            .push((id, Rc::new(callback)));
+        id
+    });
+    THREAD_STORAGE.with(|ts| ts.borrow_mut().subscribers.watched.insert(hkey));
+    Subscription {
+        hkey,
+        id,
+        _thread: PhantomData,
+    }
+}
+
+/// A subscription created by [`subscribe`], cancelled when dropped.
+#[must_use = "the subscription is cancelled as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct Subscription {
+    hkey: u64,
+    id: u64,
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let unwatched = CALLBACKS.try_with(|c| {
+            let by_key = &mut c.borrow_mut().by_key;
+            let Some(callbacks) = by_key.get_mut(&self.hkey) else {
+                return false;
+            };
+            callbacks.retain(|(id, _)| *id != self.id);
+            callbacks.is_empty() && by_key.remove(&self.hkey).is_some()
+        });
+        if unwatched == Ok(true) {
+            let _ = THREAD_STORAGE.try_with(|ts| {
+                ts.borrow_mut().subscribers.watched.remove(&self.hkey);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::*;
+
+    #[test]
+    fn test_subscribe_nested_scopes() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sub = {
+            let seen = seen.clone();
+            subscribe("subscribe.a", move |_, new| {
+                // callbacks can read parameters
+                let other = get_param!(subscribe.b, 0);
+                seen.borrow_mut().push((new.clone(), other));
+            })
+        };
+
+        with_params! {
+            set subscribe.a = 1;
+            set subscribe.b = 2;
+
+            with_params! {
+                set subscribe.a = 1;
+            }
+            with_params! {
+                set subscribe.a = 3;
+
+                ParamScope::Nothing.del("subscribe.a");
+            }
+        }
+        assert_eq!(
+            vec![
+                (Value::Int(1), 2),
+                (Value::Int(3), 2),
+                (Value::Empty, 2),
+                (Value::Int(1), 2),
+                (Value::Empty, 0),
+            ],
+            *seen.borrow()
+        );
+
+        drop(sub);
+        with_params! {
+            set subscribe.a = 4;
+        }
+        assert_eq!(5, seen.borrow().len());
+    }
+
+    #[test]
+    fn test_subscribers_are_send() {
+        // the storage is `Send`, so it must not hold the callbacks
+        fn assert_send<T: Send>() {}
+        assert_send::<super::Subscribers>();
+    }
+}
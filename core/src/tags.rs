@@ -0,0 +1,89 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::api::ParamScope;
+use crate::xxh::XXHashable;
+
+lazy_static! {
+    static ref TAGS: Mutex<HashMap<u64, BTreeSet<String>>> = Mutex::new(HashMap::new());
+}
+
+/// Attach labels such as `"tunable"`, `"infra"` or `"secret"` to a key.
+///
+/// Tags belong to the key, not to a value or a scope, and are shared by all
+/// threads.
+pub fn tag_key<K: XXHashable>(key: K, tags: &[&str]) {
+    TAGS.lock()
+        .unwrap()
+        .entry(key.xxh())
+        .or_default()
+        .extend(tags.iter().map(|t| t.to_string()));
+}
+
+/// The tags attached to a key, sorted.
+pub fn key_tags<K: XXHashable>(key: K) -> Vec<String> {
+    TAGS.lock()
+        .unwrap()
+        .get(&key.xxh())
+        .map(|tags| tags.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Whether a hashed key carries any of `tags`.
+pub(crate) fn has_any_tag(hkey: u64, tags: &[String]) -> bool {
+    TAGS.lock()
+        .unwrap()
+        .get(&hkey)
+        .is_some_and(|t| tags.iter().any(|tag| t.contains(tag)))
+}
+
+/// Keys visible on the current thread that carry `tag`, sorted.
+pub fn keys_with_tag(tag: &str) -> Vec<String> {
+    let tag = [tag.to_string()];
+    let mut keys: Vec<String> = ParamScope::Nothing
+        .keys()
+        .into_iter()
+        .filter(|k| has_any_tag(k.xxh(), &tag))
+        .collect();
+    keys.sort();
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_tags() {
+        with_params! {
+            set tags.test.lr = 0.1;
+            set tags.test.workers = 8;
+
+            tag_key("tags.test.lr", &["tunable"]);
+            let mut ps = ParamScope::Nothing;
+            ps.put_tagged("tags.test.host", "db".to_string(), &["infra", "secret"]);
+
+            assert_eq!(vec!["infra", "secret"], key_tags("tags.test.host"));
+            assert_eq!(vec!["tags.test.lr"], keys_with_tag("tunable"));
+            assert!(keys_with_tag("infra").contains(&"tags.test.host".to_string()));
+
+            let options = ExportOptions {
+                exclude_tags: vec!["infra".to_string()],
+                ..ExportOptions::default()
+            };
+            let toml = export_config(ConfigFormat::Toml, &options);
+            assert!(toml.contains("lr = 0.1"));
+            assert!(!toml.contains("host"));
+
+            let options = ExportOptions {
+                tags: vec!["tunable".to_string()],
+                ..ExportOptions::default()
+            };
+            let toml = export_config(ConfigFormat::Toml, &options);
+            assert!(toml.contains("lr = 0.1"));
+            assert!(!toml.contains("workers"));
+        }
+    }
+}
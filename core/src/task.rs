@@ -0,0 +1,176 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::api::ParamScope;
+
+/// Future returned by [`ParamScope::apply_to_future`] and [`InScope::in_scope`].
+pub struct Scoped<F> {
+    inner: Pin<Box<F>>,
+    scope: ParamScope,
+}
+
+/// Exits the scope entered for one poll and keeps the changes made during it.
+struct PollGuard<'a>(&'a mut ParamScope);
+
+impl Drop for PollGuard<'_> {
+    fn drop(&mut self) {
+        self.0.exit();
+    }
+}
+
+impl<F: Future> Future for Scoped<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        this.scope.enter();
+        let _guard = PollGuard(&mut this.scope);
+        this.inner.as_mut().poll(cx)
+    }
+}
+
+impl ParamScope {
+    /// Wrap `fut` so that this scope is entered each time it is polled.
+    ///
+    /// The scope is exited before `poll` returns, so the parameters follow
+    /// the task rather than the thread, whichever executor runs it
+    /// (async-std, smol, tokio or a hand-written one). Parameters set inside
+    /// the task are kept from one poll to the next:
+    ///
+    /// ```
+    /// # use std::future::Future;
+    /// # use std::sync::Arc;
+    /// # use std::task::{Context, Poll, Wake};
+    /// use hyperparameter::*;
+    ///
+    /// let mut ps = ParamScope::default();
+    /// ps.put("worker.batch", 32);
+    /// let task = ps.apply_to_future(async { get_param!(worker.batch, 0) });
+    /// // e.g. smol::spawn(task) or async_std::task::spawn(task)
+    /// # struct NoopWaker;
+    /// # impl Wake for NoopWaker { fn wake(self: Arc<Self>) {} }
+    /// # let waker = Arc::new(NoopWaker).into();
+    /// # let poll = std::pin::pin!(task).poll(&mut Context::from_waker(&waker));
+    /// # assert_eq!(Poll::Ready(32), poll);
+    /// ```
+    pub fn apply_to_future<F: Future>(self, fut: F) -> Scoped<F> {
+        Scoped {
+            inner: Box::pin(fut),
+            scope: self,
+        }
+    }
+}
+
+/// Extension trait that attaches a parameter scope to a future, like
+/// `tracing::Instrument` does for spans.
+///
+/// ```
+/// # use std::future::Future;
+/// # use std::sync::Arc;
+/// # use std::task::{Context, Poll, Wake};
+/// use hyperparameter::*;
+///
+/// async fn train() -> f64 {
+///     get_param!(train.lr, 0.1)
+/// }
+///
+/// let mut ps = ParamScope::default();
+/// ps.put("train.lr", 0.01);
+/// let task = train().in_scope(ps);
+/// # struct NoopWaker;
+/// # impl Wake for NoopWaker { fn wake(self: Arc<Self>) {} }
+/// # let waker = Arc::new(NoopWaker).into();
+/// # let poll = std::pin::pin!(task).poll(&mut Context::from_waker(&waker));
+/// # assert_eq!(Poll::Ready(0.01), poll);
+/// ```
+pub trait InScope: Future + Sized {
+    /// Enter `scope` each time this future is polled, see [`ParamScope::apply_to_future`].
+    fn in_scope(self, scope: ParamScope) -> Scoped<Self> {
+        scope.apply_to_future(self)
+    }
+}
+
+impl<F: Future> InScope for F {}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use crate::storage::THREAD_STORAGE;
+    use crate::*;
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Pending on the first poll, ready on the second.
+    struct YieldNow(bool);
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn test_apply_to_future_follows_task() {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut ps = ParamScope::default();
+        ps.put("task.a", 1);
+        let mut first = Box::pin(ps.apply_to_future(async {
+            let a: i64 = get_param!(task.a, 0);
+            THREAD_STORAGE.with(|ts| ts.borrow_mut().put("task.b", a + 1));
+            YieldNow(false).await;
+            (get_param!(task.a, 0), get_param!(task.b, 0))
+        }));
+        let mut ps = ParamScope::default();
+        ps.put("task.a", 10);
+        let mut second = Box::pin(ps.apply_to_future(async {
+            YieldNow(false).await;
+            get_param!(task.a, 0)
+        }));
+
+        assert_eq!(Poll::Pending, first.as_mut().poll(&mut cx));
+        assert_eq!(Poll::Pending, second.as_mut().poll(&mut cx));
+        assert_eq!(0, get_param!(task.a, 0));
+        assert_eq!(Poll::Ready(10), second.as_mut().poll(&mut cx));
+        assert_eq!(Poll::Ready((1, 2)), first.as_mut().poll(&mut cx));
+        assert_eq!(0, get_param!(task.b, 0));
+    }
+
+    #[test]
+    fn test_in_scope_across_threads() {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut ps = ParamScope::default();
+        ps.put("task.a", 3);
+        let mut task = Box::pin(
+            async {
+                YieldNow(false).await;
+                get_param!(task.a, 0)
+            }
+            .in_scope(ps),
+        );
+
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Poll::Pending, task.as_mut().poll(&mut cx));
+        let polled = std::thread::spawn(move || {
+            let mut cx = Context::from_waker(&waker);
+            task.as_mut().poll(&mut cx)
+        });
+        assert_eq!(Poll::Ready(3), polled.join().unwrap());
+    }
+}
@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::ThreadId;
+use std::time::Duration;
+
+use crate::api::ParamScope;
+use crate::storage::THREAD_STORAGE;
+
+/// Number of tickets issued by one scope that are still alive.
+#[derive(Debug, Default)]
+struct Outstanding {
+    count: Mutex<usize>,
+    released: Condvar,
+    /// The threads where one of the tickets is entered, once per guard.
+    entered: Mutex<Vec<ThreadId>>,
+}
+
+impl Outstanding {
+    fn entered_on(&self, thread: ThreadId) -> bool {
+        self.entered.lock().unwrap().contains(&thread)
+    }
+}
+
+thread_local! {
+    /// Tickets issued on this thread, by the depth of the issuing scope.
+    static ISSUED: RefCell<Vec<(usize, Arc<Outstanding>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Milliseconds a scope waits for its tickets on exit, 0 meaning forever.
+static TICKET_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+/// Bound how long exiting a scope waits for its outstanding tickets.
+///
+/// When the timeout elapses the exit panics instead of blocking further.
+/// `None`, the default, waits forever.
+pub fn set_ticket_timeout(timeout: Option<Duration>) {
+    let millis = timeout.map(|t| t.as_millis().max(1) as u64).unwrap_or(0);
+    TICKET_TIMEOUT.store(millis, Ordering::Relaxed);
+}
+
+/// Block until every ticket issued at `depth` or deeper has been dropped.
+///
+/// Called by [`ParamScope::exit`] before the scope at `depth` is unwound.
+/// Panics instead if one of the tickets is entered on this thread, which
+/// could never be released while it waits.
+pub(crate) fn wait_for_tickets(depth: usize) {
+    let thread = std::thread::current().id();
+    let pending: Vec<Arc<Outstanding>> = ISSUED.with(|issued| {
+        let mut issued = issued.borrow_mut();
+        let keep = issued.iter().take_while(|(d, _)| *d < depth).count();
+        issued.drain(keep..).map(|(_, o)| o).collect()
+    });
+    if pending.iter().any(|o| o.entered_on(thread)) {
+        panic!("scope exited while one of its ScopeTickets is entered on the same thread");
+    }
+    let timeout = TICKET_TIMEOUT.load(Ordering::Relaxed);
+    for outstanding in pending {
+        let count = outstanding.count.lock().unwrap();
+        let count = if timeout == 0 {
+            outstanding.released.wait_while(count, |c| *c > 0).unwrap()
+        } else {
+            let (count, _) = outstanding
+                .released
+                .wait_timeout_while(count, Duration::from_millis(timeout), |c| *c > 0)
+                .unwrap();
+            count
+        };
+        if *count > 0 {
+            panic!("scope exited with {} outstanding ScopeTicket(s)", *count);
+        }
+    }
+}
+
+/// A handle on the current scope that can be sent to another thread and entered there.
+///
+/// The ticket carries the parameters visible when it was issued. The
+/// issuing scope can not exit while any of its tickets is alive: the exit
+/// blocks until they are all dropped, or panics once the timeout set with
+/// [`set_ticket_timeout`] elapses. Exiting while a ticket is entered on the
+/// issuing thread itself panics right away; a ticket that is still held,
+/// unentered, by the issuing thread blocks the exit until the timeout.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set worker.batch = 32;
+///
+///     let ticket = ScopeTicket::issue();
+///     std::thread::spawn(move || {
+///         let _guard = ticket.enter();
+///         assert_eq!(32, get_param!(worker.batch, 0));
+///     })
+///     .join()
+///     .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ScopeTicket {
+    scope: ParamScope,
+    outstanding: Arc<Outstanding>,
+}
+
+impl ScopeTicket {
+    /// Issue a ticket for the scope currently entered on this thread.
+    pub fn issue() -> ScopeTicket {
+        let depth = THREAD_STORAGE.with(|ts| ts.borrow().depth());
+        let outstanding = ISSUED.with(|issued| {
+            let mut issued = issued.borrow_mut();
+            match issued.last() {
+                Some((d, o)) if *d == depth => o.clone(),
+                _ => {
+                    let o = Arc::new(Outstanding::default());
+                    issued.push((depth, o.clone()));
+                    o
+                }
+            }
+        });
+        *outstanding.count.lock().unwrap() += 1;
+        ScopeTicket {
+            scope: ParamScope::capture(),
+            outstanding,
+        }
+    }
+
+    /// Redeem the ticket on the current thread.
+    ///
+    /// The parameters are visible until the returned guard is dropped, which
+    /// also releases the ticket.
+    pub fn enter(self) -> TicketGuard {
+        let mut scope = self.scope.clone();
+        scope.enter();
+        let thread = std::thread::current().id();
+        self.outstanding.entered.lock().unwrap().push(thread);
+        TicketGuard {
+            scope,
+            thread,
+            ticket: self,
+        }
+    }
+}
+
+impl Drop for ScopeTicket {
+    fn drop(&mut self) {
+        let mut count = self.outstanding.count.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.outstanding.released.notify_all();
+        }
+    }
+}
+
+/// A redeemed [`ScopeTicket`], exiting its scope when dropped.
+#[must_use = "the scope is exited as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct TicketGuard {
+    scope: ParamScope,
+    thread: ThreadId,
+    ticket: ScopeTicket,
+}
+
+impl Drop for TicketGuard {
+    fn drop(&mut self) {
+        self.scope.exit();
+        let mut entered = self.ticket.outstanding.entered.lock().unwrap();
+        if let Some(pos) = entered.iter().position(|t| *t == self.thread) {
+            entered.swap_remove(pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::api::{ParamScope, ParamScopeOps};
+
+    use super::ScopeTicket;
+
+    #[test]
+    fn test_exit_waits_for_tickets() {
+        let released = Arc::new(AtomicBool::new(false));
+        let mut ps = ParamScope::default();
+        ps.put("ticket.a", 1);
+        ps.enter();
+        let ticket = ScopeTicket::issue();
+        let worker = {
+            let released = released.clone();
+            std::thread::spawn(move || {
+                let _guard = ticket.enter();
+                let a: i64 = ParamScope::Nothing.get_or_else("ticket.a", 0);
+                std::thread::sleep(Duration::from_millis(50));
+                released.store(true, Ordering::SeqCst);
+                a
+            })
+        };
+        ps.exit();
+        assert!(released.load(Ordering::SeqCst));
+        assert_eq!(1, worker.join().unwrap());
+    }
+
+    #[test]
+    fn test_exit_with_ticket_entered_on_same_thread() {
+        let exited = std::thread::spawn(|| {
+            let mut ps = ParamScope::default();
+            ps.put("ticket.b", 1);
+            ps.enter();
+            let _guard = ScopeTicket::issue().enter();
+            ps.exit();
+        })
+        .join();
+        assert!(exited.is_err());
+    }
+}
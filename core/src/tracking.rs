@@ -0,0 +1,269 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::storage::Storage;
+use crate::value::Value;
+
+/// Read and write counters of a single parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyStats {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+/// Most keys read while unset that one thread counts, so that probing many
+/// distinct missing keys can not grow the counters without bound.
+pub const MAX_MISSING_KEYS: usize = 1024;
+
+#[derive(Default)]
+struct Tracker {
+    stats: HashMap<u64, KeyStats>,
+    names: HashMap<u64, String>,
+    /// Keys counted while no name was known for them.
+    missing: usize,
+}
+
+impl Tracker {
+    fn merge(&mut self, other: &Tracker) {
+        for (hkey, s) in other.stats.iter() {
+            let stats = self.stats.entry(*hkey).or_default();
+            stats.reads += s.reads;
+            stats.writes += s.writes;
+        }
+        for (hkey, name) in other.names.iter() {
+            self.names.entry(*hkey).or_insert_with(|| name.clone());
+        }
+    }
+
+    fn clear(&mut self) {
+        self.stats.clear();
+        self.missing = 0;
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+lazy_static! {
+    /// The counters of running threads, each locked by its own thread only
+    /// while recording, and by readers of the counters.
+    static ref SHARDS: Mutex<Vec<Arc<Mutex<Tracker>>>> = Mutex::new(Vec::new());
+    /// The counters of threads that exited.
+    static ref RETIRED: Mutex<Tracker> = Mutex::new(Tracker::default());
+}
+
+/// The counters of the current thread, retired when it exits.
+struct Shard(Arc<Mutex<Tracker>>);
+
+impl Shard {
+    fn new() -> Shard {
+        let shard = Arc::new(Mutex::new(Tracker::default()));
+        SHARDS.lock().unwrap().push(shard.clone());
+        Shard(shard)
+    }
+}
+
+impl Drop for Shard {
+    fn drop(&mut self) {
+        SHARDS.lock().unwrap().retain(|s| !Arc::ptr_eq(s, &self.0));
+        RETIRED.lock().unwrap().merge(&self.0.lock().unwrap());
+    }
+}
+
+thread_local! {
+    static SHARD: Shard = Shard::new();
+}
+
+/// Turn the read and write counters on or off, on by default.
+///
+/// Counting costs a hash map update per access, so programs that only look
+/// at the counters now and then can turn them on just for that.
+pub fn set_key_stats(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn record<F: FnOnce(&mut Tracker)>(f: F) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    // a thread that is exiting no longer records
+    let _ = SHARD.try_with(|shard| f(&mut shard.0.lock().unwrap()));
+}
+
+pub(crate) fn record_read(hkey: u64, key: Option<&str>) {
+    record(|t| {
+        if !t.stats.contains_key(&hkey) && key.is_none() {
+            if t.missing >= MAX_MISSING_KEYS {
+                return;
+            }
+            t.missing += 1;
+        }
+        t.stats.entry(hkey).or_default().reads += 1;
+        if let Some(key) = key {
+            t.names.entry(hkey).or_insert_with(|| key.to_string());
+        }
+    });
+}
+
+pub(crate) fn record_write(hkey: u64, key: &str) {
+    record(|t| {
+        t.stats.entry(hkey).or_default().writes += 1;
+        t.names.entry(hkey).or_insert_with(|| key.to_string());
+    });
+}
+
+/// The counters of every thread, running or not.
+fn merged() -> Tracker {
+    let mut t = Tracker::default();
+    t.merge(&RETIRED.lock().unwrap());
+    for shard in SHARDS.lock().unwrap().iter() {
+        t.merge(&shard.lock().unwrap());
+    }
+    t
+}
+
+/// Read and write counters of every parameter accessed by any thread so far.
+///
+/// Keys that were only ever looked up by hash and never stored are reported
+/// by their hash in hex, e.g. `0x9c1f3d27a2b4e6f1`, unless their name is
+/// known, see [`Storage::key_name`](crate::Storage::key_name). Each thread
+/// counts at most [`MAX_MISSING_KEYS`] such keys.
+pub fn key_stats() -> HashMap<String, KeyStats> {
+    let t = merged();
+    t.stats
+        .iter()
+        .map(|(hkey, s)| {
+            let name = t
+                .names
+                .get(hkey)
+                .cloned()
+                .unwrap_or_else(|| crate::xxh::describe_key(*hkey));
+            (name, *s)
+        })
+        .collect()
+}
+
+/// Reset all read and write counters.
+pub fn reset_key_stats() {
+    RETIRED.lock().unwrap().clear();
+    for shard in SHARDS.lock().unwrap().iter() {
+        shard.lock().unwrap().clear();
+    }
+}
+
+/// Which parameters a program used, see [`Storage::usage_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageReport {
+    /// Parameters read at least once by any thread, with their read counts.
+    /// Reads of unset parameters count too.
+    pub read: BTreeMap<String, u64>,
+    /// Parameters set in the storage that were never read, ordered by key.
+    pub unused: Vec<String>,
+}
+
+impl Storage {
+    /// Report which parameters were read so far, and which of the values set
+    /// in this storage were never read and can likely be dropped from the
+    /// configuration.
+    ///
+    /// Library defaults are not reported as unused. Counters are shared by
+    /// all threads and cleared by [`reset_key_stats`].
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// with_params! {
+    ///     set doc.usage.lr = 0.1;
+    ///     set doc.usage.legacy_flag = true;
+    ///
+    ///     let _ = get_param!(doc.usage.lr, 0.01);
+    ///
+    ///     let report = THREAD_STORAGE.with(|ts| ts.borrow().usage_report());
+    ///     assert!(report.read.contains_key("doc.usage.lr"));
+    ///     assert!(report.unused.contains(&"doc.usage.legacy_flag".to_string()));
+    /// }
+    /// ```
+    pub fn usage_report(&self) -> UsageReport {
+        let t = merged();
+        let name = |hkey: &u64| {
+            t.names
+                .get(hkey)
+                .cloned()
+                .unwrap_or_else(|| crate::xxh::describe_key(*hkey))
+        };
+        let read = t
+            .stats
+            .iter()
+            .filter(|(_, s)| s.reads > 0)
+            .map(|(hkey, s)| (name(hkey), s.reads))
+            .collect();
+        let mut unused: Vec<String> = self
+            .params
+            .iter()
+            .filter(|(_, e)| !e.key.is_empty() && !matches!(e.value(), Value::Empty))
+            .filter(|(_, e)| !e.is_default())
+            .filter(|(hkey, _)| t.stats.get(hkey).copied().unwrap_or_default().reads == 0)
+            .map(|(_, e)| e.key.to_string())
+            .collect();
+        unused.sort();
+        UsageReport { read, unused }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xxh::describe_key;
+    use crate::*;
+
+    #[test]
+    fn test_key_stats() {
+        // hashing the key at runtime may intern its name, so do it before reading
+        let missing = describe_key("tracking.test.never_set".xxh());
+        with_params! {
+            set tracking.test.a = 1;
+
+            assert_eq!(1, get_param!(tracking.test.a, 0));
+            assert_eq!(1, get_param!(tracking.test.a, 0));
+            assert_eq!(0, get_param!(tracking.test.never_set, 0));
+        }
+
+        let stats = key_stats();
+        let a = stats.get("tracking.test.a").unwrap();
+        assert!(a.reads >= 2);
+        assert!(a.writes >= 1);
+        assert!(stats.get(&missing).unwrap().reads >= 1);
+    }
+
+    #[test]
+    fn test_usage_report() {
+        let mut s = Storage::default();
+        s.put("tracking.usage.read", 1);
+        s.put("tracking.usage.unused", 2);
+        assert_eq!(1, s.get_or_else("tracking.usage.read", 0));
+
+        let report = s.usage_report();
+        assert!(report.read.get("tracking.usage.read").unwrap() >= &1);
+        assert!(!report.read.contains_key("tracking.usage.unused"));
+        assert_eq!(vec!["tracking.usage.unused".to_string()], report.unused);
+    }
+
+    #[test]
+    fn test_key_stats_cap_missing_keys() {
+        std::thread::spawn(|| {
+            let mut s = Storage::default();
+            for i in 0..2 * super::MAX_MISSING_KEYS as u64 {
+                let _ = s.get_entry(i);
+            }
+            s.put("tracking.cap.a", 1);
+            let _: i64 = s.get_or_else("tracking.cap.a", 0);
+        })
+        .join()
+        .unwrap();
+        let stats = key_stats();
+        assert!(stats.get("tracking.cap.a").unwrap().reads >= 1);
+        let last = describe_key(2 * super::MAX_MISSING_KEYS as u64 - 1);
+        assert!(!stats.contains_key(&last));
+    }
+}
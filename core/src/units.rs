@@ -0,0 +1,262 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::value::{Value, ValueError};
+
+const DURATION_UNITS: [(&str, u64); 7] = [
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+const BYTE_UNITS: [(&str, u64); 9] = [
+    ("TiB", 1 << 40),
+    ("TB", 1_000_000_000_000),
+    ("GiB", 1 << 30),
+    ("GB", 1_000_000_000),
+    ("MiB", 1 << 20),
+    ("MB", 1_000_000),
+    ("KiB", 1 << 10),
+    ("KB", 1_000),
+    ("B", 1),
+];
+
+/// Split `text` into a number and a unit, and scale the number by the unit.
+///
+/// A missing unit means `default_scale`; the number may have a fraction.
+fn parse_scaled(text: &str, units: &[(&str, u64)], default_scale: u64) -> Option<u128> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '_'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number = number.replace('_', "");
+    let unit = unit.trim();
+    let scale = if unit.is_empty() {
+        default_scale
+    } else {
+        units
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+            .map(|(_, scale)| *scale)?
+    };
+    if let Ok(n) = number.parse::<u128>() {
+        return n.checked_mul(scale as u128);
+    }
+    let n = number.parse::<f64>().ok()?;
+    let scaled = (n * scale as f64).round();
+    (scaled.is_finite() && scaled <= u128::MAX as f64).then_some(scaled as u128)
+}
+
+/// Format `n` with the largest unit that divides it exactly.
+fn format_scaled(n: u128, units: &[(&str, u64)]) -> String {
+    let smallest = &units[units.len() - 1];
+    let (name, scale) = units
+        .iter()
+        .find(|(_, scale)| n != 0 && n.checked_rem(*scale as u128) == Some(0))
+        .unwrap_or(smallest);
+    format!("{}{}", n / *scale as u128, name)
+}
+
+/// Durations are stored as text like `"500ms"`, `"2s"` or `"1h"`.
+///
+/// Text accepts the units `d`, `h`, `m`, `s`, `ms`, `us` and `ns`, and plain
+/// numbers are read as seconds:
+///
+/// ```
+/// use std::time::Duration;
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set net.timeout = "500ms".to_string();
+///
+///     assert_eq!(
+///         Duration::from_millis(500),
+///         get_param!(net.timeout, Duration::from_secs(1))
+///     );
+/// }
+/// assert_eq!(Value::from("90s"), Value::from(Duration::from_secs(90)));
+/// ```
+impl From<Duration> for Value {
+    fn from(value: Duration) -> Self {
+        Value::Text(format_scaled(value.as_nanos(), &DURATION_UNITS).into())
+    }
+}
+
+impl TryFrom<&Value> for Duration {
+    type Error = ValueError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Empty => Err(ValueError::Empty),
+            Value::Int(v) => u64::try_from(*v)
+                .map(Duration::from_secs)
+                .map_err(|_| ValueError::parse_failure("Duration", &v.to_string())),
+            Value::Float(v) => Duration::try_from_secs_f64(*v)
+                .map_err(|_| ValueError::parse_failure("Duration", &v.to_string())),
+            Value::Text(s) => parse_scaled(s, &DURATION_UNITS, 1_000_000_000)
+                .and_then(|nanos| {
+                    let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+                    Some(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+                })
+                .ok_or_else(|| ValueError::parse_failure("Duration", s)),
+            _ => Err(ValueError::wrong_type("Duration", value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for Duration {
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        (&value).try_into()
+    }
+}
+
+/// A size in bytes, stored as text like `"64KiB"` or `"2GB"`.
+///
+/// Text accepts the decimal units `KB`, `MB`, `GB` and `TB`, the binary
+/// units `KiB`, `MiB`, `GiB` and `TiB`, and `B`, in any case. Plain numbers
+/// are read as bytes:
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// with_params! {
+///     set net.buffer = "64KiB".to_string();
+///
+///     assert_eq!(65536, get_param!(net.buffer, ByteSize(4096)).as_u64());
+/// }
+/// assert_eq!(Ok(ByteSize(2_000_000_000)), "2GB".parse());
+/// assert_eq!("1MiB", ByteSize(1 << 20).to_string());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_scaled(self.0 as u128, &BYTE_UNITS))
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = ValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_scaled(s, &BYTE_UNITS, 1)
+            .and_then(|n| u64::try_from(n).ok())
+            .map(ByteSize)
+            .ok_or_else(|| ValueError::parse_failure("ByteSize", s))
+    }
+}
+
+impl From<ByteSize> for Value {
+    fn from(value: ByteSize) -> Self {
+        Value::Text(value.to_string().into())
+    }
+}
+
+impl TryFrom<&Value> for ByteSize {
+    type Error = ValueError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Empty => Err(ValueError::Empty),
+            Value::Int(v) => u64::try_from(*v)
+                .map(ByteSize)
+                .map_err(|_| ValueError::parse_failure("ByteSize", &v.to_string())),
+            Value::Text(s) => s.parse(),
+            _ => Err(ValueError::wrong_type("ByteSize", value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for ByteSize {
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        (&value).try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ByteSize;
+    use crate::value::{Value, ValueError};
+
+    #[test]
+    fn test_duration_value() {
+        let cases = [
+            ("500ms", Duration::from_millis(500)),
+            ("2s", Duration::from_secs(2)),
+            ("1h", Duration::from_secs(3600)),
+            ("1.5m", Duration::from_secs(90)),
+            ("10 us", Duration::from_micros(10)),
+            ("3", Duration::from_secs(3)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(
+                Ok(expected),
+                Duration::try_from(Value::from(text)),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            Ok(Duration::from_secs(5)),
+            Duration::try_from(Value::Int(5))
+        );
+        assert_eq!(
+            Ok(Duration::from_millis(250)),
+            Duration::try_from(Value::Float(0.25))
+        );
+        assert!(matches!(
+            Duration::try_from(Value::from("2 fortnights")),
+            Err(ValueError::ParseFailure { .. })
+        ));
+        assert!(Duration::try_from(Value::Int(-1)).is_err());
+
+        for d in [
+            Duration::from_millis(1500),
+            Duration::from_nanos(7),
+            Duration::ZERO,
+        ] {
+            assert_eq!(Ok(d), Duration::try_from(Value::from(d)));
+        }
+        assert_eq!(Value::from("2h"), Value::from(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn test_byte_size_value() {
+        assert_eq!(Ok(ByteSize(65536)), "64KiB".parse());
+        assert_eq!(Ok(ByteSize(2_000_000_000)), "2gb".parse());
+        assert_eq!(Ok(ByteSize(1536)), "1.5 KiB".parse());
+        assert_eq!(Ok(ByteSize(100)), "100".parse());
+        assert!("64XB".parse::<ByteSize>().is_err());
+        assert!("99999999TiB".parse::<ByteSize>().is_err());
+
+        assert_eq!(Ok(ByteSize(512)), ByteSize::try_from(Value::Int(512)));
+        assert_eq!(Value::from("3GB"), Value::from(ByteSize(3_000_000_000)));
+        assert_eq!("1001B", ByteSize(1001).to_string());
+        assert_eq!(
+            Err(ValueError::WrongType {
+                expected: "ByteSize",
+                actual: crate::ValueKind::Boolean
+            }),
+            ByteSize::try_from(Value::Boolean(true))
+        );
+    }
+}
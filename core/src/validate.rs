@@ -0,0 +1,201 @@
+use std::fmt::{self, Debug, Display};
+
+use crate::api::ParamScope;
+use crate::value::Value;
+
+type KeyCheck = Box<dyn Fn(&Value) -> Result<(), String> + Send + Sync>;
+type ScopeCheck = Box<dyn Fn(&ParamScope) -> Result<(), String> + Send + Sync>;
+
+/// Checks run by [`ParamScope::validate_then_enter`] before a scope is entered.
+#[derive(Default)]
+pub struct Validators {
+    keys: Vec<(String, KeyCheck)>,
+    checks: Vec<ScopeCheck>,
+}
+
+impl Debug for Validators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Validators")
+            .field(
+                "keys",
+                &self.keys.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            )
+            .field("checks", &self.checks.len())
+            .finish()
+    }
+}
+
+impl Validators {
+    pub fn new() -> Self {
+        Validators::default()
+    }
+
+    /// Check the value `key` would have once the scope is entered.
+    ///
+    /// The check is skipped if the key is not set.
+    pub fn key<K, F>(mut self, key: K, check: F) -> Self
+    where
+        K: Into<String>,
+        F: Fn(&Value) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.keys.push((key.into(), Box::new(check)));
+        self
+    }
+
+    /// Add a constraint across keys.
+    ///
+    /// The check reads the pending scope, whose lookups fall back to the
+    /// thread storage as they would once the scope is entered.
+    pub fn check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&ParamScope) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.checks.push(Box::new(check));
+        self
+    }
+
+    /// Run every check against `scope`, collecting all failures.
+    pub fn validate(&self, scope: &ParamScope) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        for (key, check) in self.keys.iter() {
+            let val = scope.get(key.as_str());
+            if matches!(val, Value::Empty) {
+                continue;
+            }
+            if let Err(message) = check(&val) {
+                errors.push(ValidationError {
+                    key: Some(key.clone()),
+                    message,
+                });
+            }
+        }
+        for check in self.checks.iter() {
+            if let Err(message) = check(scope) {
+                errors.push(ValidationError { key: None, message });
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// A failed check, with the key it was registered for unless it is a cross-key constraint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{}: {}", key, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl ParamScope {
+    /// Validate the pending changes of this scope, then enter it.
+    ///
+    /// Nothing is written to the thread storage until every check has
+    /// passed; on failure the scope is left as it was and all failures are
+    /// returned.
+    ///
+    /// ```
+    /// use hyperparameter::*;
+    ///
+    /// let validators = Validators::new()
+    ///     .key("train.lr", |v| match v {
+    ///         Value::Float(lr) if *lr > 0.0 => Ok(()),
+    ///         _ => Err("must be a positive float".to_string()),
+    ///     })
+    ///     .check(|ps| {
+    ///         let warmup: i64 = ps.get_or_else("train.warmup", 0);
+    ///         let epochs: i64 = ps.get_or_else("train.epochs", 1);
+    ///         if warmup < epochs {
+    ///             Ok(())
+    ///         } else {
+    ///             Err("train.warmup must be less than train.epochs".to_string())
+    ///         }
+    ///     });
+    ///
+    /// let mut ps = ParamScope::default();
+    /// ps.put("train.lr", -0.1);
+    /// ps.put("train.warmup", 5);
+    /// let errors = ps.validate_then_enter(&validators).unwrap_err();
+    /// assert_eq!(2, errors.len());
+    /// assert_eq!(0, get_param!(train.warmup, 0));
+    ///
+    /// ps.put("train.lr", 0.1);
+    /// ps.put("train.epochs", 10);
+    /// ps.validate_then_enter(&validators).unwrap();
+    /// assert_eq!(5, get_param!(train.warmup, 0));
+    /// ps.exit();
+    /// ```
+    pub fn validate_then_enter(
+        &mut self,
+        validators: &Validators,
+    ) -> Result<(), Vec<ValidationError>> {
+        validators.validate(self)?;
+        self.enter();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::{ParamScope, ParamScopeOps};
+    use crate::value::Value;
+
+    use super::{ValidationError, Validators};
+
+    #[test]
+    fn test_validate_against_thread_storage() {
+        let validators = Validators::new()
+            .key("valid.batch", |v| match v {
+                Value::Int(b) if b % 8 == 0 => Ok(()),
+                _ => Err("must be a multiple of 8".to_string()),
+            })
+            .check(|ps| {
+                let batch: i64 = ps.get_or_else("valid.batch", 0);
+                let max: i64 = ps.get_or_else("valid.max_batch", i64::MAX);
+                if batch <= max {
+                    Ok(())
+                } else {
+                    Err("valid.batch exceeds valid.max_batch".to_string())
+                }
+            });
+
+        let mut outer = ParamScope::default();
+        outer.put("valid.max_batch", 16);
+        outer.validate_then_enter(&validators).unwrap();
+
+        let mut inner = ParamScope::default();
+        inner.put("valid.batch", 12);
+        let errors = inner.validate_then_enter(&validators).unwrap_err();
+        assert_eq!(
+            vec!["valid.batch: must be a multiple of 8".to_string()],
+            errors
+                .iter()
+                .map(ValidationError::to_string)
+                .collect::<Vec<_>>()
+        );
+
+        inner.put("valid.batch", 32);
+        let errors = inner.validate_then_enter(&validators).unwrap_err();
+        assert_eq!(
+            vec![ValidationError {
+                key: None,
+                message: "valid.batch exceeds valid.max_batch".to_string()
+            }],
+            errors
+        );
+        assert!(matches!(inner, ParamScope::Just(_)));
+
+        outer.exit();
+    }
+}
@@ -26,8 +26,9 @@ pub enum Value {
     Empty,
     Int(i64),
     Float(f64),
-    Text(String),
+    Text(Arc<str>),
     Boolean(bool),
+    List(Vec<Value>),
     UserDefined(
         u64,               //data
         i32,               //kind
@@ -69,19 +70,25 @@ impl From<f64> for Value {
 
 impl From<String> for Value {
     fn from(value: String) -> Self {
-        Value::Text(value)
+        Value::Text(value.into())
     }
 }
 
 impl From<&String> for Value {
     fn from(value: &String) -> Self {
-        Value::Text(value.to_string())
+        Value::Text(value.as_str().into())
     }
 }
 
 impl From<&str> for Value {
     fn from(value: &str) -> Self {
-        Value::Text(value.to_string())
+        Value::Text(value.into())
+    }
+}
+
+impl From<Arc<str>> for Value {
+    fn from(value: Arc<str>) -> Self {
+        Value::Text(value)
     }
 }
 
@@ -91,6 +98,12 @@ impl From<bool> for Value {
     }
 }
 
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(value: Vec<T>) -> Self {
+        Value::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
 impl From<*mut c_void> for Value {
     fn from(value: *mut c_void) -> Self {
         Value::UserDefined(value as u64, 0, None)
@@ -107,8 +120,228 @@ impl Value {
     }
 }
 
+/// The kind of data held by a [`Value`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    Empty,
+    Int,
+    Float,
+    Text,
+    Boolean,
+    List,
+    UserDefined,
+}
+
+impl ValueKind {
+    pub const ALL: [ValueKind; 7] = [
+        ValueKind::Empty,
+        ValueKind::Int,
+        ValueKind::Float,
+        ValueKind::Text,
+        ValueKind::Boolean,
+        ValueKind::List,
+        ValueKind::UserDefined,
+    ];
+}
+
+/// How a value of one kind converts into another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coercion {
+    /// The conversion always succeeds.
+    Always,
+    /// The conversion succeeds if the text parses as the target kind.
+    Parse,
+    /// The conversion always fails.
+    Never,
+}
+
+impl Coercion {
+    /// The conversion rule between two kinds, as implemented by the `TryFrom` impls.
+    pub const fn between(from: ValueKind, to: ValueKind) -> Coercion {
+        use ValueKind::*;
+        match (from, to) {
+            (Empty, _) => Coercion::Never,
+            (UserDefined, UserDefined) => Coercion::Always,
+            (UserDefined, _) | (_, UserDefined) | (_, Empty) => Coercion::Never,
+            (List, List) => Coercion::Always,
+            (List, _) | (_, List) => Coercion::Never,
+            (Text, Int) | (Text, Float) | (Text, Boolean) => Coercion::Parse,
+            (Boolean, Float) | (Float, Boolean) => Coercion::Never,
+            _ => Coercion::Always,
+        }
+    }
+}
+
+/// The full coercion matrix as `(from, to, rule)` rows.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// let table = conversion_table();
+/// assert!(table.contains(&(ValueKind::Text, ValueKind::Int, Coercion::Parse)));
+/// assert!(table.contains(&(ValueKind::Float, ValueKind::Boolean, Coercion::Never)));
+/// ```
+pub fn conversion_table() -> Vec<(ValueKind, ValueKind, Coercion)> {
+    ValueKind::ALL
+        .iter()
+        .flat_map(|from| {
+            ValueKind::ALL
+                .iter()
+                .map(move |to| (*from, *to, Coercion::between(*from, *to)))
+        })
+        .collect()
+}
+
+impl Value {
+    /// Relative tolerance used when comparing parameters for diffs and merges.
+    pub const DEFAULT_REL_TOL: f64 = 1e-9;
+    /// Absolute tolerance used when comparing parameters for diffs and merges.
+    pub const DEFAULT_ABS_TOL: f64 = 0.0;
+
+    /// Compare two values, treating floats within tolerance as equal.
+    ///
+    /// Floats are equal if they differ by at most `rel_tol` times the larger
+    /// magnitude, or by at most `abs_tol`. Two NaNs compare equal so that
+    /// they do not show up as changes. Lists compare element by element,
+    /// other kinds compare exactly.
+    ///
+    /// ```
+    /// use hyperparameter::Value;
+    ///
+    /// let parsed: f64 = "0.10000000000000001".parse().unwrap();
+    /// assert!(Value::Float(0.1 + 1e-12).approx_eq(&Value::Float(parsed), 1e-9, 0.0));
+    /// assert!(!Value::Float(0.1).approx_eq(&Value::Float(0.11), 1e-9, 0.0));
+    /// assert!(!Value::Float(1.0).approx_eq(&Value::Int(1), 1e-9, 0.0));
+    /// ```
+    pub fn approx_eq(&self, other: &Value, rel_tol: f64, abs_tol: f64) -> bool {
+        match (self, other) {
+            (Value::Float(a), Value::Float(b)) => {
+                if a == b || (a.is_nan() && b.is_nan()) {
+                    return true;
+                }
+                let diff = (a - b).abs();
+                diff <= abs_tol || diff <= rel_tol * a.abs().max(b.abs())
+            }
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|(a, b)| a.approx_eq(b, rel_tol, abs_tol))
+            }
+            _ => self == other,
+        }
+    }
+
+    /// [`Value::approx_eq`] with the default tolerances.
+    pub(crate) fn same(&self, other: &Value) -> bool {
+        self.approx_eq(other, Value::DEFAULT_REL_TOL, Value::DEFAULT_ABS_TOL)
+    }
+
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Value::Empty => ValueKind::Empty,
+            Value::Int(_) => ValueKind::Int,
+            Value::Float(_) => ValueKind::Float,
+            Value::Text(_) => ValueKind::Text,
+            Value::Boolean(_) => ValueKind::Boolean,
+            Value::List(_) => ValueKind::List,
+            Value::UserDefined(..) => ValueKind::UserDefined,
+        }
+    }
+
+    /// Check whether this value would convert into `kind` without converting it.
+    pub fn can_convert_to(&self, kind: ValueKind) -> bool {
+        match Coercion::between(self.kind(), kind) {
+            Coercion::Always => true,
+            Coercion::Never => false,
+            Coercion::Parse => match kind {
+                ValueKind::Int => i64::try_from(self).is_ok(),
+                ValueKind::Float => f64::try_from(self).is_ok(),
+                ValueKind::Boolean => bool::try_from(self).is_ok(),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Why a [`Value`] does not convert to a Rust type.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// assert_eq!(Err(ValueError::Empty), i64::try_from(&Value::Empty));
+/// assert!(matches!(
+///     i64::try_from(&Value::from("ten")),
+///     Err(ValueError::ParseFailure { .. })
+/// ));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueError {
+    /// The value is `Value::Empty`.
+    Empty,
+    /// Values of this kind never convert to the expected type.
+    WrongType {
+        expected: &'static str,
+        actual: ValueKind,
+    },
+    /// The text does not parse as the expected type.
+    ParseFailure {
+        expected: &'static str,
+        text: String,
+    },
+    /// The text is not one of the variants of a `param_enum!`.
+    UnknownVariant {
+        expected: &'static str,
+        text: String,
+        variants: &'static [&'static str],
+    },
+}
+
+impl ValueError {
+    pub(crate) fn wrong_type(expected: &'static str, value: &Value) -> ValueError {
+        ValueError::WrongType {
+            expected,
+            actual: value.kind(),
+        }
+    }
+
+    pub(crate) fn parse_failure(expected: &'static str, text: &str) -> ValueError {
+        ValueError::ParseFailure {
+            expected,
+            text: text.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueError::Empty => write!(f, "empty value error"),
+            ValueError::WrongType { expected, actual } => {
+                write!(f, "data type not matched, `{:?}` and {}", actual, expected)
+            }
+            ValueError::ParseFailure { expected, text } => {
+                write!(f, "error convert {} into {}", text, expected)
+            }
+            ValueError::UnknownVariant {
+                expected,
+                text,
+                variants,
+            } => write!(
+                f,
+                "unknown variant `{}` for {}, expected one of: {}",
+                text,
+                expected,
+                variants.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValueError {}
+
 impl TryFrom<&Value> for Value {
-    type Error = String;
+    type Error = ValueError;
 
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
         Ok(value.clone())
@@ -116,26 +349,26 @@ impl TryFrom<&Value> for Value {
 }
 
 impl TryFrom<&Value> for i64 {
-    type Error = String;
+    type Error = ValueError;
 
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
         match value {
-            Value::Empty => Err("empty value error".into()),
+            Value::Empty => Err(ValueError::Empty),
             Value::Int(v) => Ok(*v),
             Value::Float(v) => Ok(*v as i64),
             Value::Text(v) => v
                 .parse::<i64>()
-                .map_err(|_| format!("error convert {} into i64", v)),
+                .map_err(|_| ValueError::parse_failure("i64", v)),
             Value::Boolean(v) => Ok(Into::into(*v)),
-            Value::UserDefined(_, _, _) => {
-                Err("data type not matched, `UserDefined` and i64".into())
+            Value::List(_) | Value::UserDefined(_, _, _) => {
+                Err(ValueError::wrong_type("i64", value))
             }
         }
     }
 }
 
 impl TryFrom<Value> for i64 {
-    type Error = String;
+    type Error = ValueError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         (&value).try_into()
@@ -143,26 +376,25 @@ impl TryFrom<Value> for i64 {
 }
 
 impl TryFrom<&Value> for f64 {
-    type Error = String;
+    type Error = ValueError;
 
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
         match value {
-            Value::Empty => Err("empty value error".into()),
+            Value::Empty => Err(ValueError::Empty),
             Value::Int(v) => Ok(*v as f64),
             Value::Float(v) => Ok(*v),
             Value::Text(v) => v
                 .parse::<f64>()
-                .map_err(|_| format!("error convert {} into i64", v)),
-            Value::Boolean(_) => Err("data type not matched, `Boolean` and i64".into()),
-            Value::UserDefined(_, _, _) => {
-                Err("data type not matched, `UserDefined` and f64".into())
+                .map_err(|_| ValueError::parse_failure("f64", v)),
+            Value::Boolean(_) | Value::List(_) | Value::UserDefined(_, _, _) => {
+                Err(ValueError::wrong_type("f64", value))
             }
         }
     }
 }
 
 impl TryFrom<Value> for f64 {
-    type Error = String;
+    type Error = ValueError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         (&value).try_into()
@@ -170,24 +402,70 @@ impl TryFrom<Value> for f64 {
 }
 
 impl TryFrom<&Value> for String {
-    type Error = String;
+    type Error = ValueError;
 
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
         match value {
-            Value::Empty => Err("empty value error".into()),
+            Value::Empty => Err(ValueError::Empty),
             Value::Int(v) => Ok(format!("{}", v)),
             Value::Float(v) => Ok(format!("{}", v)),
-            Value::Text(v) => Ok(v.clone()),
+            Value::Text(v) => Ok(v.to_string()),
             Value::Boolean(v) => Ok(format!("{}", v)),
-            Value::UserDefined(_, _, _) => {
-                Err("data type not matched, `UserDefined` and str".into())
+            Value::List(_) | Value::UserDefined(_, _, _) => {
+                Err(ValueError::wrong_type("str", value))
             }
         }
     }
 }
 
 impl TryFrom<Value> for String {
-    type Error = String;
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        (&value).try_into()
+    }
+}
+
+/// Reads text without copying it; other values are formatted as for `String`.
+impl TryFrom<&Value> for Arc<str> {
+    type Error = ValueError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Text(v) => Ok(v.clone()),
+            _ => String::try_from(value).map(Arc::from),
+        }
+    }
+}
+
+impl TryFrom<Value> for Arc<str> {
+    type Error = ValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        (&value).try_into()
+    }
+}
+
+impl<T> TryFrom<&Value> for Vec<T>
+where
+    T: for<'a> TryFrom<&'a Value, Error = ValueError>,
+{
+    type Error = ValueError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Empty => Err(ValueError::Empty),
+            Value::List(v) => v.iter().map(T::try_from).collect(),
+            _ => Err(ValueError::wrong_type("list", value)),
+        }
+    }
+}
+
+impl<T> TryFrom<Value> for Vec<T>
+where
+    T: for<'a> TryFrom<&'a Value, Error = ValueError>,
+{
+    type Error = ValueError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         (&value).try_into()
@@ -223,33 +501,122 @@ static STR2BOOL: phf::Map<&'static str, bool> = phf_map! {
 };
 
 impl TryFrom<&Value> for bool {
-    type Error = String;
+    type Error = ValueError;
 
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
         match value {
-            Value::Empty => Err("empty value error".into()),
+            Value::Empty => Err(ValueError::Empty),
             Value::Int(v) => Ok(*v != 0),
-            Value::Float(_) => Err("data type not matched, `Float` and bool".into()),
             Value::Text(s) => match STR2BOOL.get(s) {
                 Some(v) => Ok(*v),
-                None => Err("data type not matched, `Text` and bool".into()),
+                None => Err(ValueError::parse_failure("bool", s)),
             },
             Value::Boolean(v) => Ok(*v),
-            Value::UserDefined(_, _, _) => {
-                Err("data type not matched, `UserDefined` and str".into())
+            Value::Float(_) | Value::List(_) | Value::UserDefined(_, _, _) => {
+                Err(ValueError::wrong_type("bool", value))
             }
         }
     }
 }
 
 impl TryFrom<Value> for bool {
-    type Error = String;
+    type Error = ValueError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         (&value).try_into()
     }
 }
 
+/// Define an enum that is stored as `Value::Text` holding the variant name.
+///
+/// Variants can be given another string representation with `= "repr"`.
+/// Converting an unknown string fails with an error listing the valid names.
+///
+/// ```
+/// use hyperparameter::*;
+///
+/// param_enum! {
+///     #[derive(Debug, Clone, Copy, PartialEq)]
+///     pub enum OptimizerKind {
+///         Adam,
+///         Sgd = "sgd",
+///     }
+/// }
+///
+/// with_params! {
+///     set optimizer.kind = OptimizerKind::Sgd;
+///
+///     assert_eq!(OptimizerKind::Sgd, get_param!(optimizer.kind, OptimizerKind::Adam));
+/// }
+/// ```
+#[macro_export]
+macro_rules! param_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident $(= $repr:literal)?),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant),*
+        }
+
+        impl $name {
+            /// The string representation stored in `Value::Text`.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $crate::param_enum!(@repr $variant $($repr)?)),*
+                }
+            }
+        }
+
+        impl ::std::convert::From<$name> for $crate::Value {
+            fn from(value: $name) -> Self {
+                $crate::Value::Text(value.as_str().into())
+            }
+        }
+
+        impl ::std::convert::TryFrom<&$crate::Value> for $name {
+            type Error = $crate::ValueError;
+
+            fn try_from(value: &$crate::Value) -> Result<Self, Self::Error> {
+                match value {
+                    $crate::Value::Text(s) => match &**s {
+                        $(x if x == $crate::param_enum!(@repr $variant $($repr)?) => Ok($name::$variant),)*
+                        x => Err($crate::ValueError::UnknownVariant {
+                            expected: stringify!($name),
+                            text: x.to_string(),
+                            variants: &[$($crate::param_enum!(@repr $variant $($repr)?)),*],
+                        }),
+                    },
+                    $crate::Value::Empty => Err($crate::ValueError::Empty),
+                    _ => Err($crate::ValueError::WrongType {
+                        expected: stringify!($name),
+                        actual: value.kind(),
+                    }),
+                }
+            }
+        }
+
+        impl ::std::convert::TryFrom<$crate::Value> for $name {
+            type Error = $crate::ValueError;
+
+            fn try_from(value: $crate::Value) -> Result<Self, Self::Error> {
+                (&value).try_into()
+            }
+        }
+    };
+
+    (@repr $variant:ident $repr:literal) => {
+        $repr
+    };
+
+    (@repr $variant:ident) => {
+        stringify!($variant)
+    };
+}
+
 #[derive(Debug, Clone)]
 pub struct VersionedValue(LinkedList<Value>);
 
@@ -274,6 +641,16 @@ impl VersionedValue {
         self.0.push_front(val.into());
     }
 
+    /// The versions kept for this value, newest first.
+    pub fn versions(&self) -> impl Iterator<Item = &Value> {
+        self.0.iter()
+    }
+
+    /// Number of versions kept for this value.
+    pub fn depth(&self) -> usize {
+        self.0.len()
+    }
+
     pub fn rollback(&mut self) -> bool {
         self.0.pop_front();
         !self.0.is_empty()
@@ -284,7 +661,7 @@ impl VersionedValue {
 mod test {
     use std::ffi::c_void;
 
-    use crate::value::Value;
+    use crate::value::{Value, ValueError, ValueKind};
 
     proptest! {
         #[test]
@@ -337,6 +714,109 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_conversion_table_matches_try_from() {
+        let samples = [
+            Value::Empty,
+            Value::from(1),
+            Value::from(1.5),
+            Value::from("1"),
+            Value::from("x"),
+            Value::from(true),
+            Value::from(vec![1, 2]),
+            Value::from(0x1234 as *mut c_void),
+        ];
+        for v in samples.iter() {
+            assert_eq!(v.can_convert_to(ValueKind::Int), i64::try_from(v).is_ok());
+            assert_eq!(v.can_convert_to(ValueKind::Float), f64::try_from(v).is_ok());
+            assert_eq!(
+                v.can_convert_to(ValueKind::Text),
+                String::try_from(v).is_ok()
+            );
+            assert_eq!(
+                v.can_convert_to(ValueKind::Boolean),
+                bool::try_from(v).is_ok()
+            );
+            assert_eq!(
+                v.can_convert_to(ValueKind::List),
+                Vec::<Value>::try_from(v).is_ok()
+            );
+        }
+    }
+
+    param_enum! {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Kind {
+            Adam,
+            Sgd = "sgd",
+        }
+    }
+
+    #[test]
+    fn test_param_enum() {
+        let v: Value = Kind::Sgd.into();
+        assert_eq!(v, Value::from("sgd"));
+        assert_eq!(Kind::Sgd, v.try_into().unwrap());
+        assert_eq!(Kind::Adam, Value::from("Adam").try_into().unwrap());
+
+        let err = Kind::try_from(Value::from("adagrad")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown variant `adagrad` for Kind, expected one of: Adam, sgd"
+        );
+        assert_eq!(
+            Err(ValueError::WrongType {
+                expected: "Kind",
+                actual: ValueKind::Int
+            }),
+            Kind::try_from(Value::from(1))
+        );
+    }
+
+    #[test]
+    fn test_value_errors() {
+        assert_eq!(Err(ValueError::Empty), String::try_from(&Value::Empty));
+        assert_eq!(
+            Err(ValueError::ParseFailure {
+                expected: "f64",
+                text: "fast".to_string()
+            }),
+            f64::try_from(&Value::from("fast"))
+        );
+        let err = bool::try_from(&Value::Float(1.0)).unwrap_err();
+        assert_eq!(
+            ValueError::WrongType {
+                expected: "bool",
+                actual: ValueKind::Float
+            },
+            err
+        );
+        assert_eq!("data type not matched, `Float` and bool", err.to_string());
+        assert_eq!(
+            Err(ValueError::ParseFailure {
+                expected: "i64",
+                text: "x".to_string()
+            }),
+            Vec::<i64>::try_from(&Value::from(vec!["1".to_string(), "x".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_list_value() {
+        let v: Value = vec![1i64, 2, 3].into();
+        assert_eq!(vec![1i64, 2, 3], Vec::<i64>::try_from(&v).unwrap());
+        assert_eq!(vec![1.0, 2.0, 3.0], Vec::<f64>::try_from(&v).unwrap());
+        assert_eq!(vec!["1", "2", "3"], Vec::<String>::try_from(v).unwrap());
+
+        let nested: Value = vec![vec![0.1], vec![0.2, 0.3]].into();
+        assert_eq!(
+            vec![vec![0.1], vec![0.2, 0.3]],
+            Vec::<Vec<f64>>::try_from(&nested).unwrap()
+        );
+        assert!(Vec::<i64>::try_from(Value::from(1)).is_err());
+        assert!(i64::try_from(nested).is_err());
+    }
+
     #[test]
     fn test_user_defined_value() {
         let ptr: *mut c_void = 0x00abcd as *mut c_void;
@@ -0,0 +1,139 @@
+//! wasm-bindgen bindings, to read and set parameters from JavaScript.
+
+use wasm_bindgen::prelude::*;
+
+use crate::api::ParamScope;
+use crate::value::Value;
+
+/// A [`ParamScope`] exported to JavaScript.
+///
+/// ```js
+/// const ps = new WasmParamScope();
+/// ps.put("train.lr", 0.01);
+/// ps.enter();
+/// WasmParamScope.capture().get("train.lr", 0.1); // 0.01
+/// ps.exit();
+/// ```
+///
+/// Numbers, booleans and strings are supported. Integral numbers are stored
+/// as integers, so Rust code can read them with either an integer or a float
+/// default; `null` and `undefined` remove the parameter.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct WasmParamScope {
+    inner: ParamScope,
+}
+
+#[wasm_bindgen]
+impl WasmParamScope {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmParamScope {
+        WasmParamScope::default()
+    }
+
+    /// The parameters currently visible, see [`ParamScope::capture`].
+    pub fn capture() -> WasmParamScope {
+        WasmParamScope {
+            inner: ParamScope::capture(),
+        }
+    }
+
+    /// Get a parameter, or `default` if it is not set or is not a number,
+    /// boolean or string.
+    pub fn get(&self, key: &str, default: JsValue) -> JsValue {
+        value_to_js(self.inner.get(key)).unwrap_or(default)
+    }
+
+    pub fn put(&mut self, key: &str, val: JsValue) -> Result<(), JsValue> {
+        let val = value_from_js(&val)
+            .ok_or_else(|| JsValue::from_str(&format!("{}: unsupported value", key)))?;
+        self.inner
+            .try_put(key, val)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn del(&mut self, key: &str) {
+        self.inner.del(key);
+    }
+
+    /// The keys visible from this scope, sorted.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = self.inner.keys();
+        keys.sort();
+        keys
+    }
+
+    pub fn enter(&mut self) {
+        self.inner.enter();
+    }
+
+    pub fn exit(&mut self) {
+        self.inner.exit();
+    }
+}
+
+/// The largest integer a JavaScript number holds exactly.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+fn value_from_js(val: &JsValue) -> Option<Value> {
+    if val.is_null() || val.is_undefined() {
+        Some(Value::Empty)
+    } else if let Some(v) = val.as_bool() {
+        Some(v.into())
+    } else if let Some(v) = val.as_f64() {
+        if v.fract() == 0.0 && v.abs() <= MAX_SAFE_INTEGER {
+            Some((v as i64).into())
+        } else {
+            Some(v.into())
+        }
+    } else {
+        val.as_string().map(Value::from)
+    }
+}
+
+fn value_to_js(val: Value) -> Option<JsValue> {
+    match val {
+        Value::Int(v) => Some(JsValue::from_f64(v as f64)),
+        Value::Float(v) => Some(JsValue::from_f64(v)),
+        Value::Text(v) => Some(JsValue::from_str(&v)),
+        Value::Boolean(v) => Some(JsValue::from_bool(v)),
+        Value::Empty | Value::List(_) | Value::UserDefined(..) => None,
+    }
+}
+
+// `JsValue` calls into JavaScript, run with `wasm-pack test --node --features wasm`
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::WasmParamScope;
+    use crate::*;
+
+    #[wasm_bindgen_test]
+    fn test_wasm_param_scope() {
+        let mut ps = WasmParamScope::new();
+        ps.put("wasm.lr", JsValue::from_f64(0.01)).unwrap();
+        ps.put("wasm.epochs", JsValue::from_f64(10.0)).unwrap();
+        ps.put("wasm.name", JsValue::from_str("adam")).unwrap();
+        assert_eq!(JsValue::from_f64(0.01), ps.get("wasm.lr", JsValue::NULL));
+        let keys: Vec<_> = ps
+            .keys()
+            .into_iter()
+            .filter(|k| k.starts_with("wasm."))
+            .collect();
+        assert_eq!(vec!["wasm.epochs", "wasm.lr", "wasm.name"], keys);
+
+        ps.enter();
+        assert_eq!(10, get_param!(wasm.epochs, 0));
+        assert_eq!(10.0, get_param!(wasm.epochs, 0.0));
+        assert_eq!(
+            JsValue::from_str("adam"),
+            WasmParamScope::capture().get("wasm.name", JsValue::NULL)
+        );
+        ps.exit();
+
+        ps.put("wasm.lr", JsValue::UNDEFINED).unwrap();
+        assert_eq!(JsValue::NULL, ps.get("wasm.lr", JsValue::NULL));
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use crate::api::ParamScope;
+use crate::cfg::AsParamScope;
+use crate::provenance::{with_source, Source};
+use crate::storage::{update_global_params, Entry, Params};
+use crate::value::Value;
+
+/// Interval between two checks of the watched file, see [`ConfigWatcher::interval`].
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A parameter changed by a reload of the watched file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    pub key: String,
+    /// The value from the previous load, `Value::Empty` if the key is new.
+    pub old: Value,
+    /// The value from this load, `Value::Empty` if the key was removed.
+    ///
+    /// A removed key gets back the global value it had before the file set it.
+    pub new: Value,
+}
+
+type Subscriber = (String, Arc<dyn Fn(&[ConfigChange]) + Send + Sync>);
+
+#[derive(Default)]
+struct Loaded {
+    params: Params,
+    modified: Option<SystemTime>,
+    /// The global entries replaced when the file first set their key, put
+    /// back once the file no longer sets it.
+    shadowed: HashMap<u64, Option<Entry>>,
+}
+
+/// Republishes the parameters of a config file to the frozen global storage when it changes.
+///
+/// Each reload swaps the changed keys into the global storage at once, so a
+/// thread created during a reload sees either all of the old values or all
+/// of the new ones. Like [`frozen`](crate::frozen), it does not reach into
+/// threads that already exist; subscribe to the changes to act on them.
+///
+/// ```no_run
+/// use hyperparameter::*;
+///
+/// let watcher = ConfigWatcher::new("config.toml");
+/// watcher.subscribe("train.", |changes| {
+///     for c in changes {
+///         println!("{}: {:?} -> {:?}", c.key, c.old, c.new);
+///     }
+/// });
+/// watcher.reload().unwrap();
+/// let handle = watcher.spawn();
+/// // ...
+/// handle.stop();
+/// ```
+pub struct ConfigWatcher {
+    path: PathBuf,
+    interval: Duration,
+    loaded: Mutex<Loaded>,
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl ConfigWatcher {
+    /// Watch the TOML, YAML or JSON file at `path`, the format following its extension.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        ConfigWatcher {
+            path: path.into(),
+            interval: DEFAULT_WATCH_INTERVAL,
+            loaded: Mutex::new(Loaded::default()),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Check the file for changes every `interval` once spawned.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Call `callback` with the changed keys starting with `prefix` after each reload.
+    ///
+    /// The callback is not called for reloads that change nothing under `prefix`.
+    /// It runs once the reload is published, with no lock held, so it may
+    /// subscribe or reload itself.
+    pub fn subscribe<P, F>(&self, prefix: P, callback: F)
+    where
+        P: Into<String>,
+        F: Fn(&[ConfigChange]) + Send + Sync + 'static,
+    {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .push((prefix.into(), Arc::new(callback)));
+    }
+
+    /// Load the file and publish what changed since the previous load.
+    pub fn reload(&self) -> Result<Vec<ConfigChange>, String> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        let path = self
+            .path
+            .to_str()
+            .ok_or_else(|| format!("{}: path is not valid UTF-8", self.path.display()))?;
+        let config = config::Config::builder()
+            .add_source(config::File::with_name(path))
+            .build()
+            .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        let ps = with_source(Source::File(path.into()), || config.param_scope());
+        self.loaded.lock().unwrap().modified = Some(modified);
+        Ok(self.apply(&ps))
+    }
+
+    /// Reload the file if it was modified since the previous load.
+    pub fn poll(&self) -> Result<Vec<ConfigChange>, String> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        if self.loaded.lock().unwrap().modified == Some(modified) {
+            return Ok(Vec::new());
+        }
+        self.reload()
+    }
+
+    /// Publish the parameters of `ps` as the new contents of the file.
+    ///
+    /// Keys of the previous load missing from `ps` get back the global value
+    /// they had before the file set them, or are removed if they had none.
+    /// Returns the changes, sorted by key.
+    pub fn apply(&self, ps: &ParamScope) -> Vec<ConfigChange> {
+        let params: Params = match ps {
+            ParamScope::Just(params) => params
+                .iter()
+                .filter(|(_, e)| !matches!(e.value(), Value::Empty))
+                .map(|(k, e)| (*k, e.shallow()))
+                .collect(),
+            ParamScope::Nothing => Params::default(),
+        };
+        let mut guard = self.loaded.lock().unwrap();
+        let loaded = &mut *guard;
+        let mut changes: Vec<(u64, ConfigChange)> = Vec::new();
+        for (hkey, e) in params.iter() {
+            let old = loaded.params.get(hkey).map(|e| e.clone_value());
+            if old.as_ref().is_some_and(|old| old.same(e.value())) {
+                continue;
+            }
+            changes.push((
+                *hkey,
+                ConfigChange {
+                    key: e.key.to_string(),
+                    old: old.unwrap_or(Value::Empty),
+                    new: e.clone_value(),
+                },
+            ));
+        }
+        for (hkey, e) in loaded.params.iter() {
+            if !params.contains_key(hkey) {
+                changes.push((
+                    *hkey,
+                    ConfigChange {
+                        key: e.key.to_string(),
+                        old: e.clone_value(),
+                        new: Value::Empty,
+                    },
+                ));
+            }
+        }
+        changes.sort_by(|a, b| a.1.key.cmp(&b.1.key));
+        if !changes.is_empty() {
+            let mut shadowed = HashMap::new();
+            update_global_params(|global| {
+                shadowed.clear();
+                for (hkey, c) in changes.iter() {
+                    if matches!(c.old, Value::Empty) {
+                        shadowed.insert(*hkey, global.get(hkey).map(Entry::shallow));
+                    }
+                    match (&c.new, loaded.shadowed.get(hkey)) {
+                        (Value::Empty, Some(Some(e))) => global.insert(*hkey, e.shallow()),
+                        (Value::Empty, _) => global.remove(hkey),
+                        _ => global.insert(*hkey, params[hkey].shallow()),
+                    };
+                }
+            });
+            for (hkey, c) in changes.iter() {
+                if matches!(c.new, Value::Empty) {
+                    loaded.shadowed.remove(hkey);
+                }
+            }
+            loaded.shadowed.extend(shadowed);
+        }
+        loaded.params = params;
+        drop(guard);
+
+        let changes: Vec<ConfigChange> = changes.into_iter().map(|(_, c)| c).collect();
+        let subscribers = self.subscribers.lock().unwrap().clone();
+        for (prefix, callback) in subscribers.iter() {
+            let matched: Vec<ConfigChange> = changes
+                .iter()
+                .filter(|c| c.key.starts_with(prefix.as_str()))
+                .cloned()
+                .collect();
+            if !matched.is_empty() {
+                callback(&matched);
+            }
+        }
+        changes
+    }
+
+    /// Poll the file on a background thread until the returned handle is stopped or dropped.
+    ///
+    /// Errors, such as a file caught halfway through being written, are
+    /// reported as warnings and retried on the next poll.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn(self) -> WatcherHandle {
+        let watcher = Arc::new(self);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let watcher = watcher.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    if let Err(e) = watcher.poll() {
+                        println!("hyperparameter warning: {}", e);
+                    }
+                    std::thread::park_timeout(watcher.interval);
+                }
+            })
+        };
+        WatcherHandle {
+            watcher,
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// The background thread started by [`ConfigWatcher::spawn`].
+#[cfg(not(target_arch = "wasm32"))]
+pub struct WatcherHandle {
+    watcher: Arc<ConfigWatcher>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WatcherHandle {
+    pub fn watcher(&self) -> &ConfigWatcher {
+        &self.watcher
+    }
+
+    /// Stop polling and wait for the background thread to exit.
+    pub fn stop(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for WatcherHandle {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::storage::Entry;
+    use crate::xxh::XXHashable;
+    use crate::*;
+
+    #[test]
+    fn test_watcher_apply() {
+        let watcher = ConfigWatcher::new("watch.toml");
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        {
+            let seen = seen.clone();
+            watcher.subscribe("watch.train.", move |changes| {
+                seen.lock()
+                    .unwrap()
+                    .extend(changes.iter().map(|c| c.key.clone()));
+            });
+        }
+
+        let mut ps = ParamScope::default();
+        ps.put("watch.train.lr", 0.1);
+        ps.put("watch.data.path", "/data".to_string());
+        assert_eq!(2, watcher.apply(&ps).len());
+
+        let mut ps = ParamScope::default();
+        ps.put("watch.train.lr", 0.01);
+        let changes = watcher.apply(&ps);
+        assert_eq!(
+            vec![
+                ConfigChange {
+                    key: "watch.data.path".to_string(),
+                    old: Value::from("/data"),
+                    new: Value::Empty,
+                },
+                ConfigChange {
+                    key: "watch.train.lr".to_string(),
+                    old: Value::from(0.1),
+                    new: Value::from(0.01),
+                },
+            ],
+            changes
+        );
+        assert!(watcher.apply(&ps).is_empty());
+        assert_eq!(
+            vec!["watch.train.lr", "watch.train.lr"],
+            *seen.lock().unwrap()
+        );
+
+        let lr = std::thread::spawn(|| get_param!(watch.train.lr, 0.0))
+            .join()
+            .unwrap();
+        assert_eq!(0.01, lr);
+        watcher.apply(&ParamScope::default());
+    }
+
+    #[test]
+    fn test_watcher_restores_shadowed_value() {
+        crate::storage::update_global_params(|global| {
+            global.insert(
+                "watch.restore.lr".xxh(),
+                Entry::new("watch.restore.lr", 0.5),
+            );
+        });
+        let global_lr = || {
+            std::thread::spawn(|| get_param!(watch.restore.lr, 0.0))
+                .join()
+                .unwrap()
+        };
+
+        let watcher = ConfigWatcher::new("restore.toml");
+        let mut ps = ParamScope::default();
+        ps.put("watch.restore.lr", 0.1);
+        ps.put("watch.restore.epochs", 10);
+        watcher.apply(&ps);
+        assert_eq!(0.1, global_lr());
+        ps.put("watch.restore.lr", 0.2);
+        watcher.apply(&ps);
+        assert_eq!(0.2, global_lr());
+
+        watcher.apply(&ParamScope::default());
+        assert_eq!(0.5, global_lr());
+        let epochs = std::thread::spawn(|| get_param!(watch.restore.epochs, 0))
+            .join()
+            .unwrap();
+        assert_eq!(0, epochs);
+
+        crate::storage::update_global_params(|global| {
+            global.remove(&"watch.restore.lr".xxh());
+        });
+    }
+
+    #[test]
+    fn test_watcher_subscriber_reenters() {
+        let watcher = Arc::new(ConfigWatcher::new("reenter.toml"));
+        let calls = Arc::new(Mutex::new(0));
+        {
+            let weak = Arc::downgrade(&watcher);
+            let calls = calls.clone();
+            watcher.subscribe("watch.reenter.", move |_| {
+                *calls.lock().unwrap() += 1;
+                if let Some(watcher) = weak.upgrade() {
+                    watcher.subscribe("watch.reenter.", |_| {});
+                }
+            });
+        }
+        let mut ps = ParamScope::default();
+        ps.put("watch.reenter.a", 1);
+        watcher.apply(&ps);
+        watcher.apply(&ParamScope::default());
+        assert_eq!(2, *calls.lock().unwrap());
+    }
+}
@@ -1,29 +1,139 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
 use xxhash_rust::const_xxh64;
 
+/// Seed of the xxh64 hash used for parameter keys.
+pub const HASH_SEED: u64 = 42;
+
 pub const fn xxhash(u: &[u8]) -> u64 {
-    const_xxh64::xxh64(u, 42)
+    const_xxh64::xxh64(u, HASH_SEED)
+}
+
+/// The number of segments of a dotted key, `3` for `a.b.c`.
+pub const fn key_depth(key: &str) -> usize {
+    let bytes = key.as_bytes();
+    let mut depth = 1;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'.' {
+            depth += 1;
+        }
+        i += 1;
+    }
+    depth
+}
+
+/// The hashes of a dotted key and of its parents, most specific first:
+/// `a.b.c`, `a.b`, then `a`.
+///
+/// `N` is the [`key_depth`] of `key`, so `get_param_hier!` computes these
+/// at compile time.
+pub const fn key_hierarchy_hashes<const N: usize>(key: &str) -> [u64; N] {
+    let bytes = key.as_bytes();
+    let mut hashes = [0; N];
+    let mut end = bytes.len();
+    let mut n = 0;
+    while n < N {
+        hashes[n] = xxhash(bytes.split_at(end).0);
+        n += 1;
+        while n < N && end > 0 {
+            end -= 1;
+            if bytes[end] == b'.' {
+                break;
+            }
+        }
+    }
+    hashes
 }
 
 pub trait XXHashable {
     fn xxh(&self) -> u64;
 }
 
+lazy_static! {
+    static ref KEY_NAMES: RwLock<HashMap<u64, &'static str>> = RwLock::new(HashMap::new());
+}
+
+/// Hash a key given at runtime, interning its name with the `interning`
+/// feature or in strict mode.
+fn hash_key(key: &str) -> u64 {
+    let hkey = xxhash(key.as_bytes());
+    if cfg!(feature = "interning") || crate::strict::is_strict() {
+        intern(hkey, key);
+    }
+    hkey
+}
+
+/// Remember `key` as the name of `hkey`, for [`key_name`].
+///
+/// Names are never freed, there is one per distinct key.
+pub(crate) fn intern(hkey: u64, key: &str) {
+    if KEY_NAMES.read().unwrap().contains_key(&hkey) {
+        return;
+    }
+    KEY_NAMES
+        .write()
+        .unwrap()
+        .entry(hkey)
+        .or_insert_with(|| Box::leak(key.into()));
+}
+
+/// The name of a hashed key, if it was interned or registered by `get_param!`.
+pub(crate) fn key_name(hkey: u64) -> Option<&'static str> {
+    if let Some(name) = KEY_NAMES.read().unwrap().get(&hkey) {
+        return Some(name);
+    }
+    registered_key(hkey)
+}
+
+#[cfg(feature = "registry")]
+pub(crate) fn registered_key(hkey: u64) -> Option<&'static str> {
+    crate::registry::registered_key(hkey)
+}
+
+#[cfg(not(feature = "registry"))]
+pub(crate) fn registered_key(_hkey: u64) -> Option<&'static str> {
+    None
+}
+
+/// Format a hashed key for diagnostics, by name if it is known.
+pub(crate) fn describe_key(hkey: u64) -> String {
+    key_name(hkey)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{:#018x}", hkey))
+}
+
 impl XXHashable for String {
     fn xxh(&self) -> u64 {
-        xxhash(self.as_bytes())
+        hash_key(self)
     }
 }
 
 impl XXHashable for &String {
     fn xxh(&self) -> u64 {
-        xxhash(self.as_bytes())
+        hash_key(self)
     }
 }
 
 impl XXHashable for &str {
     fn xxh(&self) -> u64 {
-        xxhash(self.as_bytes())
+        hash_key(self)
+    }
+}
+
+impl XXHashable for Cow<'_, str> {
+    fn xxh(&self) -> u64 {
+        hash_key(self)
+    }
+}
+
+impl XXHashable for Box<str> {
+    fn xxh(&self) -> u64 {
+        hash_key(self)
     }
 }
 
@@ -39,10 +149,75 @@ impl XXHashable for CString {
     }
 }
 
+/// Hash a constant key at compile time with [`HASH_SEED`], for the macros.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __key_hash {
+    ($key:expr) => {
+        $crate::xxhash_rust::const_xxh64::xxh64($key.as_bytes(), $crate::HASH_SEED)
+    };
+}
+
+/// Hash a key the way `get_param!` and `with_params!` do at compile time.
+macro_rules! const_key_hash {
+    ($($key:ident).+) => {{
+        const KEY: &str = const_str::replace!(stringify!($($key).+), ";", "");
+        (KEY, crate::__key_hash!(KEY))
+    }};
+}
+
+/// Keys hashed at compile time, covering the short, medium and long input paths of xxh64.
+const CANARIES: [(&str, u64); 3] = [
+    const_key_hash!(a),
+    const_key_hash!(model.layers),
+    const_key_hash!(canary.a.key.long.enough.to.use.the.striped.path),
+];
+
+/// Check that keys hashed at compile time by the macros match the runtime hasher.
+///
+/// A mismatch, for instance after changing [`HASH_SEED`] on one side only,
+/// would make every compile-time hashed lookup silently miss. Debug builds
+/// run this check when the first thread storage is created.
+pub fn verify_hash_consistency() -> Result<(), String> {
+    let mismatches: Vec<String> = CANARIES
+        .iter()
+        .filter_map(|(key, hash)| {
+            let runtime = key.xxh();
+            let streaming = xxhash_rust::xxh64::xxh64(key.as_bytes(), HASH_SEED);
+            if runtime == *hash && streaming == *hash {
+                None
+            } else {
+                Some(format!(
+                    "{}: compile time {:#018x}, runtime {:#018x}, streaming {:#018x}",
+                    key, hash, runtime, streaming
+                ))
+            }
+        })
+        .collect();
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "key hashes diverge between macros and runtime: {}",
+            mismatches.join("; ")
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::xxh::xxhash;
     use crate::xxh::XXHashable;
+    use crate::xxh::{key_depth, key_hierarchy_hashes};
+    #[test]
+    fn test_key_hierarchy_hashes() {
+        assert_eq!(3, key_depth("a.b.c"));
+        assert_eq!(1, key_depth("a"));
+        const HASHES: [u64; 3] = key_hierarchy_hashes("a.bc.d");
+        assert_eq!([xxhash(b"a.bc.d"), xxhash(b"a.bc"), xxhash(b"a")], HASHES);
+        assert_eq!([xxhash(b"a")], key_hierarchy_hashes::<1>("a"));
+    }
+
     #[test]
     fn test_xxhstr() {
         assert_eq!("12345".xxh(), 13461425039964245335u64);
@@ -56,6 +231,11 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_verify_hash_consistency() {
+        assert_eq!(Ok(()), super::verify_hash_consistency());
+    }
+
     #[test]
     fn test_xxhash() {
         assert_eq!(xxhash("12345".as_bytes()), 13461425039964245335u64);
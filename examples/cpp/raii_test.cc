@@ -0,0 +1,40 @@
+#include "hyperparameter.hpp"
+#include <cassert>
+#include <cstdio>
+#include <type_traits>
+#include <utility>
+
+using namespace hyperparameter::literals;
+
+template <typename T, typename = void> struct can_enter : std::false_type {};
+template <typename T>
+struct can_enter<T, decltype(void(std::declval<T>().enter()))>
+    : std::true_type {};
+
+static_assert(can_enter<hyperparameter::ParamScope &>::value, "");
+// the guard would outlive the temporary scope it exits
+static_assert(!can_enter<hyperparameter::ParamScope>::value, "");
+
+int main() {
+  static_assert("a"_key == hyperparameter::xxhash("a", 1), "");
+  assert("hpp.lr"_key == param_scope_hash("hpp.lr"));
+  assert(0.1 == hyperparameter::get("hpp.lr"_key, 0.1));
+  {
+    hyperparameter::ParamScope ps;
+    ps.put("hpp.lr", 0.01)
+        .put("hpp.name", "adam")
+        .put("hpp.layers", std::vector<int64_t>{1, 2});
+    auto guard = ps.enter();
+    assert(0.01 == hyperparameter::get("hpp.lr"_key, 0.1));
+    assert("adam" == hyperparameter::get("hpp.name"_key, "sgd"));
+    assert((std::vector<int64_t>{1, 2}) ==
+           hyperparameter::get("hpp.layers"_key, std::vector<int64_t>{}));
+    assert(hyperparameter::ParamType::Float == ps.type("hpp.lr"_key));
+    auto keys = ps.keys();
+    assert(keys.size() >= 3);
+    auto captured = hyperparameter::ParamScope::capture();
+    assert(0.01 == captured.get("hpp.lr"_key, 0.0));
+  }
+  assert(0.1 == hyperparameter::get("hpp.lr"_key, 0.1));
+  printf("ok\n");
+}
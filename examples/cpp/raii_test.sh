@@ -0,0 +1,3 @@
+header_path="../../core/include"
+lib_path="../../target/debug/libhyperparameter.a"
+clang++ -O2 -std=c++17 -I ${header_path} raii_test.cc ${lib_path} -lpthread -ldl -lm -o raii_test && ./raii_test
@@ -468,6 +468,20 @@ class param_scope(_HyperParameter):
         with param_scope():
             TLSKVStorage.frozen()
 
+    @staticmethod
+    def freeze_prefix(prefix: str):
+        """like `frozen`, but only publish the parameters whose name starts with `prefix`
+
+        Frozen parameters under other prefixes are left untouched.
+        """
+        with param_scope():
+            TLSKVStorage.freeze_prefix(prefix)
+
+    @staticmethod
+    def unfreeze_prefix(prefix: str):
+        """drop the frozen parameters whose name starts with `prefix`"""
+        TLSKVStorage.unfreeze_prefix(prefix)
+
 
 _param_scope = param_scope._func
 
@@ -68,10 +68,26 @@ class BackTrace(DebugCommand):
         return self()
 
 
+def _key_tags(key: str):
+    try:
+        from hyperparameter.librbackend import KVStorage
+    except Exception:
+        return []
+    return KVStorage.tags(key)
+
+
 @register_debug_command("params")
 class ParamsCommand(DebugCommand):
     def help(self):
-        return "list of parameters"
+        return (
+            "list of parameters\n"
+            "params.get(pattern, ...) lists parameters matching glob patterns, "
+            "add '--json' for json output and '--tag=NAME' to filter by tag\n"
+            "params.resolve(key) shows which layer the value of a key comes from\n"
+            "params.layers() lists the active layers by precedence\n"
+            "params.mode() shows the read modes, e.g. defaults_only\n"
+            "params.simulate(key=value, ...) previews which values an override would change"
+        )
 
     def __call__(self) -> Any:
         import json
@@ -81,6 +97,74 @@ class ParamsCommand(DebugCommand):
         params = param_scope().storage().storage()
         return json.dumps(params)
 
+    def get(self, *args: str) -> str:
+        import json
+        from fnmatch import fnmatchcase
+
+        from hyperparameter import param_scope
+
+        as_json = "--json" in args
+        tags = {a[len("--tag=") :] for a in args if a.startswith("--tag=")}
+        patterns = [a for a in args if not a.startswith("--")] or ["*"]
+        params = param_scope().storage().storage()
+        rows = [
+            (k, type(v).__name__, v)
+            for k, v in sorted(params.items())
+            if any(fnmatchcase(k, p) for p in patterns)
+            and (not tags or tags.intersection(_key_tags(k)))
+        ]
+        if as_json:
+            return json.dumps(
+                [{"key": k, "type": t, "value": v} for k, t, v in rows], default=repr
+            )
+        table = [("key", "type", "value")] + [(k, t, repr(v)) for k, t, v in rows]
+        widths = [max(len(row[i]) for row in table) for i in range(3)]
+        return "\n".join(
+            "  ".join(col.ljust(w) for col, w in zip(row, widths)).rstrip()
+            for row in table
+        )
+
+    def resolve(self, key: str) -> str:
+        try:
+            from hyperparameter.librbackend import KVStorage
+        except Exception:
+            return "layer information requires the rust backend"
+        r = KVStorage.resolve(key)
+        if r is None:
+            return f"{key} is not set"
+        return f"{key} = {r['value']!r} from {r['layer']} layer (depth {r['depth']})"
+
+    def layers(self) -> str:
+        try:
+            from hyperparameter.librbackend import KVStorage
+        except Exception:
+            return "layer information requires the rust backend"
+        return "\n".join(f"{name} (depth {depth})" for name, depth in KVStorage.layers())
+
+    def mode(self) -> str:
+        try:
+            from hyperparameter.librbackend import KVStorage
+        except Exception:
+            return "read modes require the rust backend"
+        return "\n".join(
+            f"{prefix or '*'}: {mode}" for prefix, mode in KVStorage.modes().items()
+        )
+
+    def simulate(self, **overrides: Any) -> str:
+        try:
+            from hyperparameter.librbackend import KVStorage
+        except Exception:
+            return "simulation requires the rust backend"
+        proposed = KVStorage()
+        proposed.update(overrides)
+        changes = proposed.simulate()
+        if not changes:
+            return "no changes"
+        return "\n".join(
+            f"{c['key']}: {c['old']!r} -> {c['new']!r} ({c['layer'] or 'unset'})"
+            for c in changes
+        )
+
     def __str__(self) -> str:
         return self()
 
@@ -3,6 +3,9 @@
 #include <cstdint>
 #include <memory>
 #include <string>
+#include <vector>
+
+#include "hyperparameter_xxh64.hpp"
 
 struct Storage;
 
@@ -11,101 +14,41 @@ extern Storage *param_scope_create();
 extern void param_scope_destroy(Storage *);
 extern void param_scope_enter(Storage *);
 extern void param_scope_exit(Storage *);
+extern Storage *param_scope_capture_token();
+extern Storage *param_scope_install_token(const Storage *);
+extern int64_t param_scope_hget_or_i64(Storage *, uint64_t, int64_t);
 extern int64_t param_scope_hget_i64(Storage *, uint64_t, int64_t);
 extern double param_scope_hget_or_f64(Storage *, uint64_t, double);
 extern bool param_scope_hget_or_bool(Storage *, uint64_t, bool);
 extern char *param_scope_hget_or_str(Storage *, uint64_t, const char *);
+extern void param_scope_free_str(char *);
 
 extern void param_scope_put_i64(Storage *, const char *, int64_t);
 extern void param_scope_put_f64(Storage *, const char *, double);
 extern void param_scope_put_bool(Storage *, const char *, bool);
 extern void param_scope_put_str(Storage *, const char *, const char *);
+
+extern int64_t param_scope_hget_i64_list(Storage *, uint64_t, int64_t *,
+                                         size_t);
+extern int64_t param_scope_hget_f64_list(Storage *, uint64_t, double *,
+                                         size_t);
+extern void param_scope_put_i64_list(Storage *, const char *, const int64_t *,
+                                     size_t);
+extern void param_scope_put_f64_list(Storage *, const char *, const double *,
+                                     size_t);
+
+extern void param_scope_del(Storage *, const char *);
+extern char **param_scope_keys(Storage *, size_t *);
+extern void param_scope_free_keys(char **, size_t);
+extern int32_t param_scope_hget_type(Storage *, uint64_t);
 }
 
 namespace hyperparameter {
 
-struct xxh64 {
-  static constexpr uint64_t hash(const char *p, uint64_t len,
-                                 uint64_t seed = 42) {
-    return finalize((len >= 32 ? h32bytes(p, len, seed) : seed + PRIME5) + len,
-                    p + (len & ~0x1F), len & 0x1F);
-  }
-
-private:
-  static constexpr uint64_t PRIME1 = 11400714785074694791ULL;
-  static constexpr uint64_t PRIME2 = 14029467366897019727ULL;
-  static constexpr uint64_t PRIME3 = 1609587929392839161ULL;
-  static constexpr uint64_t PRIME4 = 9650029242287828579ULL;
-  static constexpr uint64_t PRIME5 = 2870177450012600261ULL;
-
-  static constexpr uint64_t rotl(uint64_t x, int r) {
-    return ((x << r) | (x >> (64 - r)));
-  }
-  static constexpr uint64_t mix1(const uint64_t h, const uint64_t prime,
-                                 int rshift) {
-    return (h ^ (h >> rshift)) * prime;
-  }
-  static constexpr uint64_t mix2(const uint64_t p, const uint64_t v = 0) {
-    return rotl(v + p * PRIME2, 31) * PRIME1;
-  }
-  static constexpr uint64_t mix3(const uint64_t h, const uint64_t v) {
-    return (h ^ mix2(v)) * PRIME1 + PRIME4;
-  }
-  static constexpr uint32_t endian32(const char *v) {
-    return uint32_t(uint8_t(v[0])) | (uint32_t(uint8_t(v[1])) << 8) |
-           (uint32_t(uint8_t(v[2])) << 16) | (uint32_t(uint8_t(v[3])) << 24);
-  }
-  static constexpr uint64_t endian64(const char *v) {
-    return uint64_t(uint8_t(v[0])) | (uint64_t(uint8_t(v[1])) << 8) |
-           (uint64_t(uint8_t(v[2])) << 16) | (uint64_t(uint8_t(v[3])) << 24) |
-           (uint64_t(uint8_t(v[4])) << 32) | (uint64_t(uint8_t(v[5])) << 40) |
-           (uint64_t(uint8_t(v[6])) << 48) | (uint64_t(uint8_t(v[7])) << 56);
-  }
-  static constexpr uint64_t fetch64(const char *p, const uint64_t v = 0) {
-    return mix2(endian64(p), v);
-  }
-  static constexpr uint64_t fetch32(const char *p) {
-    return uint64_t(endian32(p)) * PRIME1;
-  }
-  static constexpr uint64_t fetch8(const char *p) {
-    return uint8_t(*p) * PRIME5;
-  }
-  static constexpr uint64_t finalize(const uint64_t h, const char *p,
-                                     uint64_t len) {
-    return (len >= 8)
-               ? (finalize(rotl(h ^ fetch64(p), 27) * PRIME1 + PRIME4, p + 8,
-                           len - 8))
-               : ((len >= 4)
-                      ? (finalize(rotl(h ^ fetch32(p), 23) * PRIME2 + PRIME3,
-                                  p + 4, len - 4))
-                      : ((len > 0)
-                             ? (finalize(rotl(h ^ fetch8(p), 11) * PRIME1,
-                                         p + 1, len - 1))
-                             : (mix1(mix1(mix1(h, PRIME2, 33), PRIME3, 29), 1,
-                                     32))));
-  }
-  static constexpr uint64_t h32bytes(const char *p, uint64_t len,
-                                     const uint64_t v1, const uint64_t v2,
-                                     const uint64_t v3, const uint64_t v4) {
-    return (len >= 32)
-               ? h32bytes(p + 32, len - 32, fetch64(p, v1), fetch64(p + 8, v2),
-                          fetch64(p + 16, v3), fetch64(p + 24, v4))
-               : mix3(mix3(mix3(mix3(rotl(v1, 1) + rotl(v2, 7) + rotl(v3, 12) +
-                                         rotl(v4, 18),
-                                     v1),
-                                v2),
-                           v3),
-                      v4);
-  }
-  static constexpr uint64_t h32bytes(const char *p, uint64_t len,
-                                     const uint64_t seed) {
-    return h32bytes(p, len, seed + PRIME1 + PRIME2, seed + PRIME2, seed,
-                    seed - PRIME1);
-  }
-};
+using detail::xxh64;
 
 constexpr uint64_t xxhash(const char *p, int len) {
-  return xxh64::hash(p, len, 42);
+  return xxh64::hash(p, len, HASH_SEED);
 }
 
 struct Hyperparameter {
@@ -147,6 +90,19 @@ struct Hyperparameter {
   }
 
   template <typename T> inline Hyperparameter *put(const char *key, T val);
+
+  inline Hyperparameter *del(const char *key) {
+    param_scope_del(_storage, key);
+    return this;
+  }
+
+  inline std::vector<std::string> keys() {
+    size_t len = 0;
+    char **raw = param_scope_keys(_storage, &len);
+    std::vector<std::string> keys(raw, raw + len);
+    param_scope_free_keys(raw, len);
+    return keys;
+  }
 };
 
 inline std::shared_ptr<Hyperparameter> create_shared() {
@@ -156,12 +112,12 @@ inline Hyperparameter *create() { return new Hyperparameter(); }
 
 template <>
 inline int64_t Hyperparameter::get<int64_t>(uint64_t key, int64_t def) {
-  return param_scope_hget_i64(_storage, key, def);
+  return param_scope_hget_or_i64(_storage, key, def);
 }
 
 template <>
 inline int32_t Hyperparameter::get<int32_t>(uint64_t key, int32_t def) {
-  return param_scope_hget_i64(_storage, key, def);
+  return param_scope_hget_or_i64(_storage, key, def);
 }
 
 template <>
@@ -176,15 +132,45 @@ template <> inline bool Hyperparameter::get<bool>(uint64_t key, bool def) {
 template <>
 inline std::string Hyperparameter::get<std::string>(uint64_t key,
                                                     std::string def) {
-  return std::string(param_scope_hget_or_str(_storage, key, def.c_str()));
+  char *raw = param_scope_hget_or_str(_storage, key, def.c_str());
+  std::string val(raw);
+  param_scope_free_str(raw);
+  return val;
 }
 
+// The returned string is a copy owned by the caller, who must release it
+// with param_scope_free_str(const_cast<char *>(s)); get<std::string> does
+// this for you.
 template <>
 inline const char *Hyperparameter::get<const char *>(uint64_t key,
                                                      const char *def) {
   return param_scope_hget_or_str(_storage, key, def);
 }
 
+template <>
+inline std::vector<int64_t>
+Hyperparameter::get<std::vector<int64_t>>(uint64_t key,
+                                          std::vector<int64_t> def) {
+  int64_t len = param_scope_hget_i64_list(_storage, key, nullptr, 0);
+  if (len < 0)
+    return def;
+  std::vector<int64_t> vals(len);
+  param_scope_hget_i64_list(_storage, key, vals.data(), vals.size());
+  return vals;
+}
+
+template <>
+inline std::vector<double>
+Hyperparameter::get<std::vector<double>>(uint64_t key,
+                                         std::vector<double> def) {
+  int64_t len = param_scope_hget_f64_list(_storage, key, nullptr, 0);
+  if (len < 0)
+    return def;
+  std::vector<double> vals(len);
+  param_scope_hget_f64_list(_storage, key, vals.data(), vals.size());
+  return vals;
+}
+
 template <>
 inline Hyperparameter *Hyperparameter::put<int64_t>(const char *key,
                                                     int64_t val) {
@@ -226,6 +212,22 @@ inline Hyperparameter *Hyperparameter::put<const char *>(const char *key,
   param_scope_put_str(_storage, key, val);
   return this;
 }
+
+template <>
+inline Hyperparameter *
+Hyperparameter::put<std::vector<int64_t>>(const char *key,
+                                          std::vector<int64_t> val) {
+  param_scope_put_i64_list(_storage, key, val.data(), val.size());
+  return this;
+}
+
+template <>
+inline Hyperparameter *
+Hyperparameter::put<std::vector<double>>(const char *key,
+                                         std::vector<double> val) {
+  param_scope_put_f64_list(_storage, key, val.data(), val.size());
+  return this;
+}
 } // namespace hyperparameter
 
 // Implicit create hyperparameter object
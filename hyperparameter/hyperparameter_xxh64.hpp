@@ -0,0 +1 @@
+../core/include/hyperparameter_xxh64.hpp
\ No newline at end of file
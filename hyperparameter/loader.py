@@ -1,7 +1,182 @@
+import re
 import warnings
+from typing import Any, Dict, List, Optional, Tuple
 
 
-def load(path: str):
+class DuplicateKeyError(ValueError):
+    """Raised when config keys differ only in case or surrounding whitespace.
+
+    Examples
+    --------
+    >>> err = DuplicateKeyError([("train.lr", 2), ("train.LR ", 3)], "a.toml")
+    >>> print(err)
+    duplicate keys in a.toml: 'train.lr' (line 2), 'train.LR ' (line 3)
+    """
+
+    def __init__(self, keys: List[Tuple[str, Optional[int]]], path: str = None):
+        self.keys = keys
+        self.path = path
+        where = f" in {path}" if path is not None else ""
+        desc = ", ".join(
+            f"{k!r} (line {line})" if line is not None else repr(k) for k, line in keys
+        )
+        super().__init__(f"duplicate keys{where}: {desc}")
+
+
+def canonical_key(key: str) -> str:
+    """Canonical form of a dotted key, used to detect duplicates.
+
+    Examples
+    --------
+    >>> canonical_key(" Train.LR ")
+    'train.lr'
+    """
+    return ".".join(part.strip().lower() for part in key.split("."))
+
+
+def _flatten(config: Dict[str, Any], prefix: str = None):
+    for k, v in config.items():
+        key = f"{prefix}.{k}" if prefix is not None else k
+        if isinstance(v, dict):
+            yield from _flatten(v, key)
+        else:
+            yield key
+
+
+_KEY_PART = r"""\s*(?:"((?:[^"\\]|\\.)*)"|'([^']*)'|([A-Za-z0-9_\-]+))\s*"""
+_DOTTED_KEY = re.compile(rf"{_KEY_PART}(?:\.{_KEY_PART})*")
+_TOML_TABLE = re.compile(r"^\s*\[\[?([^\]]*)\]\]?\s*(?:#.*)?$")
+_YAML_KEY = re.compile(r"""^(\s*)(?:"((?:[^"\\]|\\.)*)"|'([^']*)'|([^\s"'#:=\-][^:#=]*?))\s*:(?:\s|$)""")
+
+
+def _key_parts(text: str) -> Optional[List[str]]:
+    """Split a TOML dotted key into its parts, None if it is not a key."""
+    m = _DOTTED_KEY.match(text)
+    if m is None:
+        return None
+    parts = []
+    for part in re.finditer(_KEY_PART, m.group(0)):
+        parts.append(next(g for g in part.groups() if g is not None))
+    return parts
+
+
+def _key_lines(text: str) -> Dict[str, int]:
+    """The line where each key of a TOML or YAML document is defined.
+
+    Keys are tracked with the table, or the indentation, they are nested in,
+    so that keys with the same name in different tables are told apart.
+    """
+    lines: Dict[str, int] = {}
+    table: List[str] = []
+    nested: List[Tuple[int, str]] = []
+    for lineno, line in enumerate(text.splitlines(), 1):
+        m = _TOML_TABLE.match(line)
+        if m:
+            table = _key_parts(m.group(1)) or []
+            continue
+        m = _YAML_KEY.match(line)
+        if m:
+            indent = len(m.group(1))
+            while nested and nested[-1][0] >= indent:
+                nested.pop()
+            name = next(g for g in m.groups()[1:] if g is not None)
+            nested.append((indent, name))
+            lines.setdefault(".".join(n for _, n in nested), lineno)
+            continue
+        key, eq, _ = line.partition("=")
+        parts = _key_parts(key) if eq else None
+        if parts and _DOTTED_KEY.fullmatch(key):
+            lines.setdefault(".".join(table + parts), lineno)
+    return lines
+
+
+def check_duplicate_keys(config: Dict[str, Any], text: str = None, path: str = None):
+    """Raise DuplicateKeyError if two keys of `config` share a canonical form.
+
+    Line numbers are looked up in `text`, the source of `config`, if given.
+    """
+    groups: Dict[str, List[str]] = {}
+    for key in _flatten(config):
+        groups.setdefault(canonical_key(key), []).append(key)
+    dups = [k for keys in groups.values() if len(keys) > 1 for k in keys]
+    if dups:
+        found = _key_lines(text) if text is not None else {}
+        lines = [(k, found.get(k)) for k in dups]
+        raise DuplicateKeyError(lines, path)
+
+
+class TemplateError(ValueError):
+    """Raised in strict mode when a `${...}` placeholder can not be rendered."""
+
+
+_PLACEHOLDER = re.compile(r"\$\$\{|\$\{([^}]*)\}")
+
+
+def render_template(value: str, strict: bool = False, now: float = None) -> str:
+    """Render `${...}` placeholders in a config string.
+
+    Supported placeholders are `${env:NAME}`, `${now:FORMAT}` (a strftime
+    format) and `${hostname}`. Write `$${` for a literal `${`. Placeholders
+    that can not be rendered are kept as is, or raise TemplateError in
+    strict mode.
+
+    Examples
+    --------
+    >>> import os
+    >>> os.environ["HP_DOC_USER"] = "alice"
+    >>> render_template("/data/${env:HP_DOC_USER}/$${literal}")
+    '/data/alice/${literal}'
+    >>> render_template("${now:%Y}", now=0.0)[:2]
+    '19'
+    >>> render_template("${unknown}")
+    '${unknown}'
+    """
+    import os
+    import socket
+    import time
+
+    now = time.time() if now is None else now
+
+    def _render(m: re.Match) -> str:
+        if m.group(0) == "$${":
+            return "${"
+        name, _, arg = m.group(1).partition(":")
+        if name == "env" and arg in os.environ:
+            return os.environ[arg]
+        if name == "now" and arg:
+            return time.strftime(arg, time.localtime(now))
+        if name == "hostname" and not arg:
+            return socket.gethostname()
+        if strict:
+            raise TemplateError(f"can not render placeholder {m.group(0)!r}")
+        return m.group(0)
+
+    return _PLACEHOLDER.sub(_render, value)
+
+
+def interpolate(config: Any, strict: bool = False, now: float = None) -> Any:
+    """Render `${...}` placeholders in all strings of a loaded config.
+
+    All `${now:...}` placeholders use the same timestamp.
+    """
+    import time
+
+    now = time.time() if now is None else now
+    if isinstance(config, str):
+        return render_template(config, strict, now)
+    if isinstance(config, dict):
+        return {k: interpolate(v, strict, now) for k, v in config.items()}
+    if isinstance(config, list):
+        return [interpolate(v, strict, now) for v in config]
+    return config
+
+
+def load(path: str, templates: bool = False, strict: bool = False):
+    """Load a TOML config file.
+
+    `${...}` placeholders in strings are only rendered with `templates=True`,
+    see `render_template`.
+    """
     try:
         import toml
     except Exception as e:
@@ -10,10 +185,14 @@ def load(path: str):
         )
         raise e
     with open(path) as f:
-        return toml.load(f)
+        text = f.read()
+    config = toml.loads(text)
+    check_duplicate_keys(config, text, path)
+    return interpolate(config, strict) if templates else config
 
 
-def loads(config: str):
+def loads(config: str, templates: bool = False, strict: bool = False):
+    """Load a TOML config string, like `load`."""
     try:
         import toml
     except Exception as e:
@@ -21,7 +200,9 @@ def loads(config: str):
             "package toml is required by hyperparameter, please install toml with `pip install toml`"
         )
         raise e
-    return toml.loads(config)
+    parsed = toml.loads(config)
+    check_duplicate_keys(parsed, config)
+    return interpolate(parsed, strict) if templates else parsed
 
 
 def dumps(config) -> str:
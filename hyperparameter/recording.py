@@ -0,0 +1,98 @@
+"""Record the calls made to the rust backend and replay them.
+
+A recording is a JSONL file with one KVStorage call per line, written by
+`start_recording`. Replaying it runs the same calls, on the same storages
+and with the calls of each recorded thread on a thread of its own, so a
+scope desync seen in an application can be reproduced without it.
+
+Recording requires the rust backend.
+"""
+
+import json
+import queue
+import threading
+from contextlib import contextmanager
+
+__all__ = ["recording", "replay"]
+
+
+@contextmanager
+def recording(path):
+    """Record the KVStorage calls made inside the block to `path`."""
+    from hyperparameter.librbackend import start_recording, stop_recording
+
+    start_recording(path)
+    try:
+        yield
+    finally:
+        stop_recording()
+
+
+class _Worker(threading.Thread):
+    """Replays the calls of one recorded thread, one at a time."""
+
+    def __init__(self):
+        super().__init__(daemon=True)
+        self.calls = queue.Queue()
+        self.results = queue.Queue()
+
+    def run(self):
+        while True:
+            call = self.calls.get()
+            if call is None:
+                return
+            try:
+                self.results.put((call(), None))
+            except Exception as e:
+                self.results.put((None, e))
+
+    def __call__(self, call):
+        self.calls.put(call)
+        return self.results.get()
+
+
+def replay(path, on_call=None):
+    """Replay the recording at `path`, one call at a time in the recorded order.
+
+    `on_call(record, result, error)` is invoked after each call, where
+    `record` is the decoded line. Errors raised by the calls are passed to
+    `on_call` rather than raised, as the recorded application may have
+    caught them too. Arguments that were recorded as their repr are passed
+    as strings. Storages created before the recording started, such as a
+    scope the recording was started in, are replaced by empty ones.
+    """
+    from hyperparameter.librbackend import KVStorage
+
+    storages = {}
+    workers = {}
+    try:
+        with open(path) as f:
+            for line in f:
+                record = json.loads(line)
+                method, args = record["method"], record["args"]
+                if method in ("new", "current"):
+                    factory = KVStorage if method == "new" else KVStorage.current
+
+                    def call(factory=factory, sid=record["storage"]):
+                        storages[sid] = factory()
+                        return storages[sid]
+
+                elif record["storage"] is None:
+                    call = lambda m=method, a=args: getattr(KVStorage, m)(*a)
+                else:
+
+                    def call(sid=record["storage"], method=method, args=args):
+                        if sid not in storages:
+                            storages[sid] = KVStorage()
+                        return getattr(storages[sid], method)(*args)
+
+                if record["thread"] not in workers:
+                    workers[record["thread"]] = _Worker()
+                    workers[record["thread"]].start()
+                result, error = workers[record["thread"]](call)
+                if on_call is not None:
+                    on_call(record, result, error)
+    finally:
+        for worker in workers.values():
+            worker.calls.put(None)
+            worker.join()
@@ -1,6 +1,10 @@
 use std::ffi::c_void;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
 use hyperparameter::*;
+use pyo3::exceptions::PyKeyError;
+use pyo3::exceptions::PyTypeError;
 use pyo3::exceptions::PyValueError;
 use pyo3::ffi::Py_XDECREF;
 use pyo3::prelude::*;
@@ -10,8 +14,12 @@ use pyo3::types::PyFloat;
 use pyo3::types::PyInt;
 use pyo3::types::PyList;
 use pyo3::types::PyString;
+use pyo3::types::PyType;
 use pyo3::FromPyPointer;
 
+use crate::record::record;
+use crate::record::{start_recording, stop_recording};
+
 #[repr(C)]
 enum UserDefinedType {
     PyObjectType = 1,
@@ -39,29 +47,144 @@ fn make_value_from_pyobject(obj: *mut pyo3::ffi::PyObject) -> Value {
     )
 }
 
+unsafe fn make_value(val: &PyAny) -> Value {
+    if val.is_none() {
+        Value::Empty
+    } else if val.is_instance_of::<PyBool>() {
+        val.extract::<bool>().unwrap().into()
+    } else if val.is_instance_of::<PyFloat>() {
+        val.extract::<f64>().unwrap().into()
+    } else if val.is_instance_of::<PyString>() {
+        val.extract::<&str>().unwrap().into()
+    } else if val.is_instance_of::<PyInt>() {
+        val.extract::<i64>().unwrap().into()
+    } else if val.is_instance_of::<PyList>() {
+        Value::List(
+            val.downcast::<PyList>()
+                .unwrap()
+                .iter()
+                .map(|v| make_value(v))
+                .collect(),
+        )
+    } else if let Some(v) = make_scientific_value(val) {
+        v
+    } else {
+        // Py_XINCREF(val.into_ptr());
+        make_value_from_pyobject(val.into_ptr())
+    }
+}
+
+/// Convert numpy scalars, decimals, fractions and datetime types to native
+/// values, so that Rust readers can use them. `None` for other objects.
+///
+/// Datetimes become ISO 8601 text, and timedeltas durations like `"90s"`.
+unsafe fn make_scientific_value(val: &PyAny) -> Option<Value> {
+    let module: String = val.get_type().getattr("__module__").ok()?.extract().ok()?;
+    match module.as_str() {
+        "numpy" => {
+            if val.getattr("ndim").ok()?.extract::<usize>().ok()? != 0 {
+                return None;
+            }
+            let item = val.call_method0("item").ok()?;
+            let item_module: String = item.get_type().getattr("__module__").ok()?.extract().ok()?;
+            if item_module == "numpy" {
+                return None;
+            }
+            Some(make_value(item))
+        }
+        "decimal" | "fractions" => val.extract::<f64>().ok().map(Value::from),
+        "datetime" => {
+            let timedelta = val
+                .py()
+                .import("datetime")
+                .ok()?
+                .getattr("timedelta")
+                .ok()?;
+            if val.is_instance(timedelta).ok()? {
+                let secs: f64 = val.call_method0("total_seconds").ok()?.extract().ok()?;
+                return Duration::try_from_secs_f64(secs).ok().map(Value::from);
+            }
+            let iso: String = val.call_method0("isoformat").ok()?.extract().ok()?;
+            Some(iso.into())
+        }
+        _ => None,
+    }
+}
+
+unsafe fn list_into_py(py: Python<'_>, vals: &[Value]) -> PyObject {
+    let res = PyList::empty(py);
+    for val in vals {
+        let item = match val {
+            Value::Empty => py.None(),
+            Value::Int(v) => v.into_py(py),
+            Value::Float(v) => v.into_py(py),
+            Value::Text(v) => v.as_ref().into_py(py),
+            Value::Boolean(v) => v.into_py(py),
+            Value::List(v) => list_into_py(py, v),
+            Value::UserDefined(v, k, _) => {
+                if *k == UserDefinedType::PyObjectType as i32 {
+                    PyAny::from_borrowed_ptr(py, *v as *mut pyo3::ffi::PyObject).into()
+                } else {
+                    (*v).into_py(py)
+                }
+            }
+        };
+        res.append(item).unwrap();
+    }
+    res.into()
+}
+
+/// Convert a parameter value for Python, `None` for an empty value.
+unsafe fn value_into_py(py: Python<'_>, val: Value) -> Option<PyObject> {
+    match val {
+        Value::Empty => None,
+        Value::Int(v) => Some(v.into_py(py)),
+        Value::Float(v) => Some(v.into_py(py)),
+        Value::Text(v) => Some(v.as_ref().into_py(py)),
+        Value::Boolean(v) => Some(v.into_py(py)),
+        Value::List(v) => Some(list_into_py(py, &v)),
+        Value::UserDefined(v, k, _) => {
+            if k == UserDefinedType::PyObjectType as i32 {
+                Some(PyAny::from_borrowed_ptr(py, v as *mut pyo3::ffi::PyObject).into())
+            } else {
+                Some((v as u64).into_py(py))
+            }
+        }
+    }
+}
+
+static NEXT_STORAGE_ID: AtomicU64 = AtomicU64::new(1);
+
 #[pyclass]
 pub struct KVStorage {
     storage: ParamScope,
+    /// Identifies the storage in recordings.
+    id: u64,
 }
 
 #[pymethods]
 impl KVStorage {
     #[new]
     pub fn new() -> KVStorage {
+        let id = NEXT_STORAGE_ID.fetch_add(1, Ordering::Relaxed);
+        let _call = record("new", Some(id), || ());
         KVStorage {
             storage: ParamScope::default(),
+            id,
         }
     }
 
     pub unsafe fn storage(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+        let _call = record("storage", Some(self.id), || ());
         let res = PyDict::new(py);
         for k in self.storage.keys().iter() {
             match self.storage.get(k) {
                 Value::Empty => Ok(()),
                 Value::Int(v) => res.set_item(k, v),
                 Value::Float(v) => res.set_item(k, v),
-                Value::Text(v) => res.set_item(k, v.as_str()),
+                Value::Text(v) => res.set_item(k, v.as_ref()),
                 Value::Boolean(v) => res.set_item(k, v),
+                Value::List(v) => res.set_item(k, list_into_py(py, &v)),
                 Value::UserDefined(v, k, _) => {
                     if k == UserDefinedType::PyObjectType as i32 {
                         res.set_item(k, PyAny::from_owned_ptr(py, v as *mut pyo3::ffi::PyObject))
@@ -76,11 +199,67 @@ impl KVStorage {
     }
 
     pub unsafe fn keys(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+        let _call = record("keys", Some(self.id), || ());
         let res = PyList::new(py, self.storage.keys());
         Ok(res.into())
     }
 
+    /// The values of the parameters, ordered by key.
+    pub unsafe fn values(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+        let _call = record("values", Some(self.id), || ());
+        let values: Vec<PyObject> = self
+            .storage
+            .namespace("")
+            .into_values()
+            .filter_map(|v| value_into_py(py, v))
+            .collect();
+        Ok(PyList::new(py, values).into())
+    }
+
+    /// The `(key, value)` pairs of the parameters, ordered by key.
+    pub unsafe fn items(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+        let _call = record("items", Some(self.id), || ());
+        let items: Vec<(String, PyObject)> = self
+            .storage
+            .namespace("")
+            .into_iter()
+            .filter_map(|(k, v)| Some((k, value_into_py(py, v)?)))
+            .collect();
+        Ok(PyList::new(py, items).into())
+    }
+
+    pub unsafe fn __getitem__(&mut self, py: Python<'_>, key: String) -> PyResult<PyObject> {
+        let _call = record("__getitem__", Some(self.id), || (key.clone(),));
+        self.storage
+            .try_get(&key)
+            .and_then(|v| value_into_py(py, v))
+            .ok_or_else(|| PyKeyError::new_err(key))
+    }
+
+    pub unsafe fn __setitem__(&mut self, key: String, val: &PyAny) -> PyResult<()> {
+        self.put(key, val)
+    }
+
+    pub fn __contains__(&mut self, key: String) -> bool {
+        let _call = record("__contains__", Some(self.id), || (key.clone(),));
+        self.storage.try_get(&key).is_some()
+    }
+
+    pub fn __len__(&mut self) -> usize {
+        let _call = record("__len__", Some(self.id), || ());
+        // the same key set as iteration, items() and values()
+        self.storage.namespace("").len()
+    }
+
+    /// Iterate over the keys, ordered, like a `dict`.
+    pub fn __iter__(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+        let _call = record("__iter__", Some(self.id), || ());
+        let keys = PyList::new(py, self.storage.namespace("").into_keys());
+        Ok(keys.call_method0("__iter__")?.into())
+    }
+
     pub unsafe fn _update(&mut self, kws: &PyDict, prefix: Option<String>) {
+        let _call = record("_update", Some(self.id), || (kws, prefix.clone()));
         kws.iter()
             .map(|(k, v)| {
                 let key = match &prefix {
@@ -97,22 +276,28 @@ impl KVStorage {
     }
 
     pub unsafe fn update(&mut self, kws: &PyDict) {
+        let _call = record("update", Some(self.id), || (kws,));
         self._update(kws, None);
     }
 
     pub unsafe fn clear(&mut self) {
-        for k in self.storage.keys().iter() {
-            self.storage.put(k, Value::Empty);
-        }
+        let _call = record("clear", Some(self.id), || ());
+        self.storage.clear();
     }
 
     pub unsafe fn get(&mut self, py: Python<'_>, key: String) -> PyResult<Option<PyObject>> {
-        match self.storage.get(key) {
-            Value::Empty => Err(PyValueError::new_err("not found")),
+        let _call = record("get", Some(self.id), || (key.clone(),));
+        let val = match self.storage.try_get(&key) {
+            Some(val) => val,
+            None => return Err(PyValueError::new_err(format!("not found: {}", key))),
+        };
+        match val {
+            Value::Empty => Err(PyValueError::new_err(format!("not found: {}", key))),
             Value::Int(v) => Ok(Some(v.into_py(py))),
             Value::Float(v) => Ok(Some(v.into_py(py))),
-            Value::Text(v) => Ok(Some(v.into_py(py))),
+            Value::Text(v) => Ok(Some(v.as_ref().into_py(py))),
             Value::Boolean(v) => Ok(Some(v.into_py(py))),
+            Value::List(v) => Ok(Some(list_into_py(py, &v))),
             Value::UserDefined(v, k, _) => {
                 if k == UserDefinedType::PyObjectType as i32 {
                     Ok(Some(
@@ -126,12 +311,14 @@ impl KVStorage {
     }
 
     pub unsafe fn get_entry(&mut self, py: Python<'_>, hkey: u64) -> PyResult<Option<PyObject>> {
+        let _call = record("get_entry", Some(self.id), || (hkey,));
         match self.storage.get_with_hash(hkey) {
             Value::Empty => Err(PyValueError::new_err("not found")),
             Value::Int(v) => Ok(Some(v.into_py(py))),
             Value::Float(v) => Ok(Some(v.into_py(py))),
-            Value::Text(v) => Ok(Some(v.into_py(py))),
+            Value::Text(v) => Ok(Some(v.as_ref().into_py(py))),
             Value::Boolean(v) => Ok(Some(v.into_py(py))),
+            Value::List(v) => Ok(Some(list_into_py(py, &v))),
             Value::UserDefined(v, k, _) => {
                 if k == UserDefinedType::PyObjectType as i32 {
                     Ok(Some(
@@ -145,44 +332,278 @@ impl KVStorage {
     }
 
     pub unsafe fn put(&mut self, key: String, val: &PyAny) -> PyResult<()> {
-        if val.is_none() {
-            self.storage.put(key, Value::Empty);
-        } else if val.is_instance_of::<PyBool>() {
-            self.storage.put(key, val.extract::<bool>().unwrap());
-        } else if val.is_instance_of::<PyFloat>() {
-            self.storage.put(key, val.extract::<f64>().unwrap());
-        } else if val.is_instance_of::<PyString>() {
-            self.storage
-                .put(key, val.extract::<&str>().unwrap().to_string());
-        } else if val.is_instance_of::<PyInt>() {
-            self.storage.put(key, val.extract::<i64>().unwrap());
-        } else {
-            // Py_XINCREF(val.into_ptr());
-            self.storage
-                .put(key, make_value_from_pyobject(val.into_ptr()));
+        let _call = record("put", Some(self.id), || (key.clone(), val));
+        let val = make_value(val);
+        with_source(Source::Python, || self.storage.try_put(key, val))
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Put a parameter and attach `tags` such as "tunable" or "infra" to its key.
+    pub unsafe fn put_tagged(
+        &mut self,
+        key: String,
+        val: &PyAny,
+        tags: Vec<String>,
+    ) -> PyResult<()> {
+        let _call = record("put_tagged", Some(self.id), || {
+            (key.clone(), val, tags.clone())
+        });
+        tag_key(&key, &tags.iter().map(|t| t.as_str()).collect::<Vec<_>>());
+        self.put(key, val)
+    }
+
+    /// The tags attached to `key`.
+    #[staticmethod]
+    pub fn tags(key: String) -> Vec<String> {
+        let _call = record("tags", None, || (key.clone(),));
+        key_tags(&key)
+    }
+
+    /// Build an instance of the dataclass `cls` from the keys under `prefix`.
+    ///
+    /// Nested dataclass fields are read from `prefix.field.*`. Fields without a
+    /// stored value are left to the dataclass defaults.
+    pub unsafe fn extract(
+        &mut self,
+        py: Python<'_>,
+        prefix: String,
+        cls: &PyAny,
+    ) -> PyResult<PyObject> {
+        let _call = record("extract", Some(self.id), || (prefix.clone(), cls));
+        let dataclasses = py.import("dataclasses")?;
+        if !dataclasses
+            .call_method1("is_dataclass", (cls,))?
+            .is_true()?
+        {
+            return Err(PyTypeError::new_err(format!(
+                "{} is not a dataclass",
+                cls.repr()?
+            )));
+        }
+        let hints = py
+            .import("typing")?
+            .call_method1("get_type_hints", (cls,))?
+            .downcast::<PyDict>()?;
+        let kwargs = PyDict::new(py);
+        for field in dataclasses.call_method1("fields", (cls,))?.iter()? {
+            let name: String = field?.getattr("name")?.extract()?;
+            let key = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", prefix, name)
+            };
+            let hint = hints.get_item(&name)?;
+            if let Some(hint) = hint {
+                if dataclasses
+                    .call_method1("is_dataclass", (hint,))?
+                    .is_true()?
+                {
+                    kwargs.set_item(&name, self.extract(py, key, hint)?)?;
+                    continue;
+                }
+            }
+            let val = match self.get(py, key.clone()) {
+                Ok(Some(val)) => val.into_ref(py),
+                _ => continue,
+            };
+            match hint {
+                Some(hint) if hint.is_instance_of::<PyType>() && !val.is_instance(hint)? => {
+                    if hint.is(py.get_type::<PyFloat>()) && val.is_instance_of::<PyInt>() {
+                        kwargs.set_item(&name, val.extract::<f64>()?)?;
+                    } else {
+                        return Err(PyTypeError::new_err(format!(
+                            "{}: expected {}, got {}",
+                            key,
+                            hint.getattr("__name__")?,
+                            val.get_type().name()?
+                        )));
+                    }
+                }
+                _ => kwargs.set_item(&name, val)?,
+            }
+        }
+        Ok(cls.call((), Some(kwargs))?.into())
+    }
+
+    /// Store the fields of the dataclass instance `obj` under `prefix`.
+    pub unsafe fn update_from(
+        &mut self,
+        py: Python<'_>,
+        obj: &PyAny,
+        prefix: String,
+    ) -> PyResult<()> {
+        let _call = record("update_from", Some(self.id), || (obj, prefix.clone()));
+        let dataclasses = py.import("dataclasses")?;
+        if !dataclasses
+            .call_method1("is_dataclass", (obj,))?
+            .is_true()?
+            || obj.is_instance_of::<PyType>()
+        {
+            return Err(PyTypeError::new_err(format!(
+                "{} is not a dataclass instance",
+                obj.repr()?
+            )));
+        }
+        for field in dataclasses.call_method1("fields", (obj,))?.iter()? {
+            let name: String = field?.getattr("name")?.extract()?;
+            let key = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", prefix, name)
+            };
+            let val = obj.getattr(name.as_str())?;
+            if dataclasses
+                .call_method1("is_dataclass", (val,))?
+                .is_true()?
+            {
+                self.update_from(py, val, key)?;
+            } else {
+                self.put(key, val)?;
+            }
         }
         Ok(())
     }
 
     pub fn enter(&mut self) {
+        let _call = record("enter", Some(self.id), || ());
         self.storage.enter();
     }
 
     pub fn exit(&mut self) {
+        let _call = record("exit", Some(self.id), || ());
         self.storage.exit();
     }
 
     #[staticmethod]
     pub fn current() -> KVStorage {
+        let id = NEXT_STORAGE_ID.fetch_add(1, Ordering::Relaxed);
+        let _call = record("current", Some(id), || ());
         KVStorage {
             storage: ParamScope::Nothing,
+            id,
         }
     }
 
     #[staticmethod]
     pub fn frozen() {
+        let _call = record("frozen", None, || ());
         frozen();
     }
+
+    #[staticmethod]
+    pub fn freeze_prefix(prefix: &str) {
+        let _call = record("freeze_prefix", None, || (prefix,));
+        freeze_prefix(prefix);
+    }
+
+    #[staticmethod]
+    pub fn unfreeze_prefix(prefix: &str) {
+        let _call = record("unfreeze_prefix", None, || (prefix,));
+        unfreeze_prefix(prefix);
+    }
+
+    /// Describe where the current value of `key` comes from, or None if it is not set.
+    #[staticmethod]
+    pub unsafe fn resolve(py: Python<'_>, key: String) -> PyResult<Option<PyObject>> {
+        let _call = record("resolve", None, || (key.clone(),));
+        let r = match hyperparameter::resolve(&key) {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+        let res = PyDict::new(py);
+        res.set_item("value", KVStorage::current().get(py, key)?)?;
+        res.set_item("layer", r.layer.name())?;
+        res.set_item("depth", r.depth)?;
+        Ok(Some(res.into()))
+    }
+
+    /// Preview which keys would change if this storage were entered, without entering it.
+    ///
+    /// Returns a list of dicts with the key, its old and new values and the
+    /// layer the old value comes from (None if the key is unset).
+    pub unsafe fn simulate(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+        let _call = record("simulate", Some(self.id), || ());
+        let res = PyList::empty(py);
+        for change in hyperparameter::simulate(&self.storage) {
+            let item = PyDict::new(py);
+            item.set_item("key", &change.key)?;
+            item.set_item("new", self.get(py, change.key.clone())?)?;
+            match change.old {
+                Some(old) => {
+                    item.set_item("old", KVStorage::current().get(py, change.key)?)?;
+                    item.set_item("layer", old.layer.name())?;
+                }
+                None => {
+                    item.set_item("old", py.None())?;
+                    item.set_item("layer", py.None())?;
+                }
+            }
+            res.append(item)?;
+        }
+        Ok(res.into())
+    }
+
+    /// List the active layers from highest to lowest precedence.
+    #[staticmethod]
+    pub fn layers(py: Python<'_>) -> PyResult<PyObject> {
+        let _call = record("layers", None, || ());
+        let res = PyList::empty(py);
+        for (layer, depth) in hyperparameter::layers() {
+            res.append((layer.name(), depth))?;
+        }
+        Ok(res.into())
+    }
+
+    /// Set the read mode ("normal" or "defaults_only"), globally or for keys under `prefix`.
+    ///
+    /// Passing None as the mode removes the mode of `prefix`.
+    #[staticmethod]
+    pub fn set_mode(mode: Option<&str>, prefix: Option<String>) -> PyResult<()> {
+        let _call = record("set_mode", None, || (mode, prefix.clone()));
+        let mode = mode
+            .map(Mode::try_from)
+            .transpose()
+            .map_err(PyValueError::new_err)?;
+        match (prefix, mode) {
+            (Some(prefix), mode) => set_prefix_mode(prefix, mode),
+            (None, Some(mode)) => hyperparameter::set_mode(mode),
+            (None, None) => hyperparameter::set_mode(Mode::Normal),
+        }
+        Ok(())
+    }
+
+    /// The active read modes by prefix, the global mode being under "".
+    #[staticmethod]
+    pub fn modes(py: Python<'_>) -> PyResult<PyObject> {
+        let _call = record("modes", None, || ());
+        let res = PyDict::new(py);
+        for (prefix, mode) in hyperparameter::modes() {
+            res.set_item(prefix, mode.name())?;
+        }
+        Ok(res.into())
+    }
+
+    /// Turn the read and write counters reported by `stats` on or off.
+    ///
+    /// They are off when the module is loaded, as counting slows every access.
+    #[staticmethod]
+    pub fn track_stats(enabled: bool) {
+        let _call = record("track_stats", None, || (enabled,));
+        set_key_stats(enabled);
+    }
+
+    #[staticmethod]
+    pub fn stats(py: Python<'_>) -> PyResult<PyObject> {
+        let _call = record("stats", None, || ());
+        let res = PyDict::new(py);
+        for (k, s) in key_stats() {
+            let item = PyDict::new(py);
+            item.set_item("reads", s.reads)?;
+            item.set_item("writes", s.writes)?;
+            res.set_item(k, item)?;
+        }
+        Ok(res.into())
+    }
 }
 
 #[pyfunction]
@@ -190,9 +611,24 @@ pub fn xxh64(s: &str) -> u64 {
     s.xxh()
 }
 
+/// Version, enabled features and hash seed of the compiled backend.
+#[pyfunction]
+pub fn build_info(py: Python<'_>) -> PyResult<PyObject> {
+    let info = hyperparameter::build_info();
+    let res = PyDict::new(py);
+    res.set_item("version", info.version)?;
+    res.set_item("features", info.features)?;
+    res.set_item("hash_seed", info.hash_seed)?;
+    Ok(res.into())
+}
+
 #[pymodule]
 fn librbackend(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    set_key_stats(false);
     m.add_class::<KVStorage>()?;
     m.add_function(wrap_pyfunction!(xxh64, m)?)?;
+    m.add_function(wrap_pyfunction!(self::build_info, m)?)?;
+    m.add_function(wrap_pyfunction!(start_recording, m)?)?;
+    m.add_function(wrap_pyfunction!(stop_recording, m)?)?;
     Ok(())
 }
@@ -1 +1,2 @@
 pub mod ext;
+pub mod record;
@@ -0,0 +1,113 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use lazy_static::lazy_static;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::types::PyTuple;
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref RECORDER: Mutex<Option<File>> = Mutex::new(None);
+}
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Marks a KVStorage call in progress; calls made while it is alive are not recorded.
+pub struct Call(());
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        DEPTH.with(|d| d.set(d.get() - 1));
+    }
+}
+
+/// Record a call to `method` on the storage `storage`, None for static methods.
+///
+/// Only the outermost call is written, so `update` is recorded once rather
+/// than once per key it puts. `args` is only evaluated while recording.
+pub fn record<A, F>(method: &str, storage: Option<u64>, args: F) -> Call
+where
+    A: IntoPy<Py<PyTuple>>,
+    F: FnOnce() -> A,
+{
+    let depth = DEPTH.with(|d| d.replace(d.get() + 1));
+    if depth == 0 && RECORDING.load(Ordering::Relaxed) {
+        Python::with_gil(|py| {
+            let args = args().into_py(py);
+            if let Err(e) = write_call(py, method, storage, args) {
+                e.print(py);
+            }
+        });
+    }
+    Call(())
+}
+
+fn write_call(
+    py: Python<'_>,
+    method: &str,
+    storage: Option<u64>,
+    args: Py<PyTuple>,
+) -> PyResult<()> {
+    let time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let call = PyDict::new(py);
+    call.set_item("time", time.as_secs_f64())?;
+    call.set_item("thread", py.import("threading")?.call_method0("get_ident")?)?;
+    call.set_item("task", current_task_name(py))?;
+    call.set_item("storage", storage)?;
+    call.set_item("method", method)?;
+    call.set_item("args", args)?;
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("default", py.import("builtins")?.getattr("repr")?)?;
+    let mut line: String = py
+        .import("json")?
+        .call_method("dumps", (call,), Some(kwargs))?
+        .extract()?;
+    line.push('\n');
+    if let Some(out) = RECORDER.lock().unwrap().as_mut() {
+        out.write_all(line.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Name of the running asyncio task, None outside of a task.
+fn current_task_name(py: Python<'_>) -> Option<String> {
+    let task = py
+        .import("asyncio")
+        .ok()?
+        .call_method0("current_task")
+        .ok()?;
+    if task.is_none() {
+        return None;
+    }
+    task.call_method0("get_name").ok()?.extract().ok()
+}
+
+/// Start writing every KVStorage call to the JSONL file at `path`.
+///
+/// Each line holds the method, its arguments, the id of the storage it was
+/// called on, the thread id and the asyncio task name. Arguments that are
+/// not JSON serializable are written as their repr. Replay a recording with
+/// `hyperparameter.recording.replay`.
+#[pyfunction]
+pub fn start_recording(path: String) -> PyResult<()> {
+    *RECORDER.lock().unwrap() = Some(File::create(path)?);
+    RECORDING.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Stop recording and close the recording file.
+#[pyfunction]
+pub fn stop_recording() {
+    RECORDING.store(false, Ordering::Relaxed);
+    RECORDER.lock().unwrap().take();
+}
@@ -140,6 +140,17 @@ class TLSKVStorage(Storage):
     def frozen():
         GLOBAL_STORAGE.update(TLSKVStorage.tls.his[-1].storage())
 
+    @staticmethod
+    def freeze_prefix(prefix: str):
+        params = TLSKVStorage.tls.his[-1].storage()
+        TLSKVStorage.unfreeze_prefix(prefix)
+        GLOBAL_STORAGE.update({k: v for k, v in params.items() if k.startswith(prefix)})
+
+    @staticmethod
+    def unfreeze_prefix(prefix: str):
+        for k in [k for k in GLOBAL_STORAGE if k.startswith(prefix)]:
+            del GLOBAL_STORAGE[k]
+
 
 has_rust_backend = False
 
@@ -0,0 +1,30 @@
+import json
+from unittest import TestCase
+
+from hyperparameter import param_scope
+from hyperparameter.debug import ParamsCommand
+
+
+class TestParamsCommand(TestCase):
+    def test_params_get_glob(self):
+        with param_scope(**{"model.l1.dropout": 0.1, "model.l2.dropout": 0.2, "lr": 1}):
+            table = ParamsCommand().get("model.*.dropout")
+            self.assertEqual(
+                table.splitlines(),
+                [
+                    "key               type   value",
+                    "model.l1.dropout  float  0.1",
+                    "model.l2.dropout  float  0.2",
+                ],
+            )
+
+    def test_params_get_json(self):
+        with param_scope(**{"a.b": 1, "a.c": "x", "b": True}):
+            rows = json.loads(ParamsCommand().get("a.*", "--json"))
+            self.assertEqual(
+                rows,
+                [
+                    {"key": "a.b", "type": "int", "value": 1},
+                    {"key": "a.c", "type": "str", "value": "x"},
+                ],
+            )
@@ -0,0 +1,102 @@
+import sys
+import types
+from unittest import TestCase, SkipTest
+
+from hyperparameter.loader import DuplicateKeyError, check_duplicate_keys, loads
+
+try:
+    import tomllib
+except ImportError:
+    try:
+        import tomli as tomllib
+    except ImportError:
+        tomllib = None
+
+
+class TestLoader(TestCase):
+    def setUp(self):
+        if "toml" not in sys.modules:
+            try:
+                import toml  # noqa: F401
+            except ImportError:
+                if tomllib is None:
+                    raise SkipTest("toml, tomllib or tomli is required")
+                sys.modules["toml"] = types.SimpleNamespace(loads=tomllib.loads)
+
+    def test_loads(self):
+        self.assertDictEqual(loads("[train]\nlr = 0.1\n"), {"train": {"lr": 0.1}})
+
+    def test_duplicate_keys_case(self):
+        with self.assertRaises(DuplicateKeyError) as ctx:
+            loads("[train]\nlr = 0.1\nLR = 0.2\n")
+        self.assertListEqual(ctx.exception.keys, [("train.lr", 2), ("train.LR", 3)])
+
+    def test_duplicate_keys_whitespace(self):
+        with self.assertRaises(DuplicateKeyError) as ctx:
+            loads('[model]\nname = "a"\n"name " = "b"\n')
+        self.assertListEqual(
+            ctx.exception.keys, [("model.name", 2), ("model.name ", 3)]
+        )
+
+    def test_duplicate_keys_in_later_table(self):
+        with self.assertRaises(DuplicateKeyError) as ctx:
+            loads("[train]\nlr = 0.1\n\n[eval]\nlr = 0.2\nLR = 0.3\n")
+        self.assertListEqual(ctx.exception.keys, [("eval.lr", 5), ("eval.LR", 6)])
+
+    def test_duplicate_keys_dotted(self):
+        with self.assertRaises(DuplicateKeyError) as ctx:
+            loads('[model]\noptim.name = "a"\n\n[model.OPTIM]\nname = "b"\n')
+        self.assertListEqual(
+            ctx.exception.keys, [("model.optim.name", 2), ("model.OPTIM.name", 5)]
+        )
+
+    def test_duplicate_keys_yaml(self):
+        try:
+            import yaml
+        except ImportError:
+            self.skipTest("yaml is required")
+        text = (
+            "train:\n"
+            "  lr: 0.1\n"
+            "eval:\n"
+            "  lr: 0.2\n"
+            "  batch: 8\n"
+            "  'LR ': 0.3\n"
+        )
+        with self.assertRaises(DuplicateKeyError) as ctx:
+            check_duplicate_keys(yaml.safe_load(text), text, "eval.yaml")
+        self.assertListEqual(ctx.exception.keys, [("eval.lr", 4), ("eval.LR ", 6)])
+
+    def test_check_duplicate_keys_without_text(self):
+        with self.assertRaises(DuplicateKeyError) as ctx:
+            check_duplicate_keys({"a": {"B": 1}, "A": {"b": 2}})
+        self.assertEqual(str(ctx.exception), "duplicate keys: 'a.B', 'A.b'")
+
+    def test_loads_templates(self):
+        import os
+        import socket
+
+        os.environ["HP_TEST_LOADER_ROOT"] = "/data"
+        cfg = loads(
+            'out = "${env:HP_TEST_LOADER_ROOT}/${hostname}/$${x}"\n'
+            'tags = ["${env:HP_TEST_LOADER_ROOT}"]\n',
+            templates=True,
+        )
+        self.assertEqual(cfg["out"], f"/data/{socket.gethostname()}/${{x}}")
+        self.assertListEqual(cfg["tags"], ["/data"])
+
+    def test_loads_templates_off_by_default(self):
+        import os
+
+        os.environ["HP_TEST_LOADER_ROOT"] = "/data"
+        cfg = loads('out = "${env:HP_TEST_LOADER_ROOT}/$${x}"')
+        self.assertEqual(cfg["out"], "${env:HP_TEST_LOADER_ROOT}/$${x}")
+
+    def test_loads_templates_strict(self):
+        from hyperparameter.loader import TemplateError
+
+        self.assertEqual(loads('a = "${nope:x}"', templates=True)["a"], "${nope:x}")
+        with self.assertRaises(TemplateError):
+            loads('a = "${nope:x}"', templates=True, strict=True)
+        with self.assertRaises(TemplateError):
+            loads('a = "${env:HP_TEST_LOADER_UNSET}"', templates=True, strict=True)
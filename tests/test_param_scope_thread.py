@@ -14,6 +14,22 @@ class TestParamScopeThread(TestCase):
         t.start()
         t.join
         
+    def test_freeze_prefix(self):
+        result = {}
+
+        def read():
+            ps = param_scope()
+            result["model"] = ps.freeze_test.model.layers(None)
+            result["data"] = ps.freeze_test.data.path(None)
+
+        with param_scope(**{"freeze_test.model.layers": 12, "freeze_test.data.path": "/tmp"}):
+            param_scope.freeze_prefix("freeze_test.model.")
+        t = Thread(target=read)
+        t.start()
+        t.join()
+        param_scope.unfreeze_prefix("freeze_test.model.")
+        self.assertEqual(result, {"model": 12, "data": None})
+
     # def test_new_thread_init(self):
     #     param_scope.A.B = 1
     #     param_scope.frozen()
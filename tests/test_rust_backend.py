@@ -1,6 +1,6 @@
 from unittest import TestCase
 
-from hyperparameter.librbackend import KVStorage
+from hyperparameter.librbackend import KVStorage, build_info
 
 
 class TestRBackend(TestCase):
@@ -91,6 +91,47 @@ class TestRBackend(TestCase):
         except Exception as exc:
             self.assertIsInstance(exc, ValueError)
 
+    def test_kvstorage_mapping(self):
+        s = KVStorage()
+        s["mapping.a"] = 1
+        s["mapping.b"] = "2"
+        self.assertIn("mapping.a", s)
+        self.assertNotIn("mapping.c", s)
+        self.assertEqual(s["mapping.b"], "2")
+        with self.assertRaises(KeyError):
+            s["mapping.c"]
+
+        d = dict(s)
+        self.assertEqual(d["mapping.a"], 1)
+        self.assertEqual(len(s), len(d))
+        self.assertEqual(list(s), sorted(d))
+        self.assertEqual(dict(s.items()), d)
+        self.assertEqual(s.values(), [d[k] for k in sorted(d)])
+        self.assertEqual((lambda **kws: kws)(**s), d)
+
+    def test_kvstorage_put_scientific_types(self):
+        import datetime
+        from decimal import Decimal
+
+        s = KVStorage()
+        s.put("sci.decimal", Decimal("0.5"))
+        s.put("sci.date", datetime.date(2024, 1, 2))
+        s.put("sci.delta", datetime.timedelta(milliseconds=1500))
+        self.assertEqual(s.get("sci.decimal"), 0.5)
+        self.assertEqual(s.get("sci.date"), "2024-01-02")
+        self.assertEqual(s.get("sci.delta"), "1500ms")
+
+        try:
+            import numpy as np
+        except ImportError:
+            return
+        s.put("sci.int", np.int32(3))
+        s.put("sci.float", np.float32(0.25))
+        s.put("sci.bool", np.bool_(True))
+        self.assertEqual(s.get("sci.int"), 3)
+        self.assertEqual(s.get("sci.float"), 0.25)
+        self.assertIs(s.get("sci.bool"), True)
+
     def test_kvstorage_current(self):
         s1 = KVStorage()
         s1.update(
@@ -119,3 +160,184 @@ class TestRBackend(TestCase):
         self.assertEqual(s2.get("d"), True)
 
         s1.exit()
+
+    def test_kvstorage_stats(self):
+        s = KVStorage()
+        s.put("stats.off", 1)
+        self.assertNotIn("stats.off", KVStorage.stats())
+
+        KVStorage.track_stats(True)
+        try:
+            s.put("stats.a", 1)
+            s.enter()
+            KVStorage.current().get("stats.a")
+            KVStorage.current().get("stats.a")
+            s.exit()
+        finally:
+            KVStorage.track_stats(False)
+
+        stats = KVStorage.stats()
+        self.assertGreaterEqual(stats["stats.a"]["reads"], 2)
+        self.assertGreaterEqual(stats["stats.a"]["writes"], 1)
+
+    def test_kvstorage_dataclass(self):
+        from dataclasses import dataclass, field
+
+        @dataclass
+        class Optim:
+            kind: str = "adam"
+            lr: float = 0.1
+
+        @dataclass
+        class Train:
+            epochs: int
+            optim: Optim = field(default_factory=Optim)
+
+        s = KVStorage()
+        s.update({"train": {"epochs": 10, "optim": {"lr": 1}}})
+        cfg = s.extract("train", Train)
+        self.assertEqual(cfg, Train(epochs=10, optim=Optim(kind="adam", lr=1.0)))
+        self.assertIsInstance(cfg.optim.lr, float)
+
+        s.put("train.epochs", "ten")
+        with self.assertRaises(TypeError):
+            s.extract("train", Train)
+
+        s2 = KVStorage()
+        s2.update_from(Train(epochs=3, optim=Optim(kind="sgd")), "train")
+        self.assertDictEqual(
+            s2.storage(),
+            {"train.epochs": 3, "train.optim.kind": "sgd", "train.optim.lr": 0.1},
+        )
+
+    def test_kvstorage_clear(self):
+        s = KVStorage()
+        s.update({"a": 1, "b": 2})
+        s.put("b", None)
+        self.assertListEqual(s.keys(), ["a"])
+        with self.assertRaises(ValueError):
+            s.get("b")
+
+        s.clear()
+        self.assertDictEqual(s.storage(), {})
+        with self.assertRaises(ValueError):
+            s.get("a")
+
+        outer = KVStorage()
+        outer.put("c", 3)
+        outer.enter()
+        inner = KVStorage()
+        inner.enter()
+        KVStorage.current().clear()
+        with self.assertRaises(ValueError):
+            KVStorage.current().get("c")
+        inner.exit()
+        self.assertEqual(KVStorage.current().get("c"), 3)
+        outer.exit()
+
+    def test_kvstorage_resolve(self):
+        self.assertIsNone(KVStorage.resolve("resolve.a"))
+        s = KVStorage()
+        s.put("resolve.a", 1)
+        s.enter()
+        self.assertDictEqual(
+            KVStorage.resolve("resolve.a"), {"value": 1, "layer": "thread", "depth": 1}
+        )
+        self.assertEqual(KVStorage.layers()[0], ("thread", 1))
+        s.exit()
+        self.assertIsNone(KVStorage.resolve("resolve.a"))
+
+    def test_kvstorage_put_tagged(self):
+        s = KVStorage()
+        s.put_tagged("tagged.host", "db", ["infra"])
+        self.assertEqual(s.get("tagged.host"), "db")
+        self.assertListEqual(KVStorage.tags("tagged.host"), ["infra"])
+        self.assertListEqual(KVStorage.tags("tagged.other"), [])
+
+    def test_kvstorage_mode(self):
+        s = KVStorage()
+        s.put("mode.a", 1)
+        s.enter()
+        KVStorage.set_mode("defaults_only", "mode.")
+        with self.assertRaises(ValueError):
+            KVStorage.current().get("mode.a")
+        self.assertEqual(KVStorage.modes()["mode."], "defaults_only")
+        self.assertNotIn("mode.a", list(KVStorage.current()))
+        self.assertEqual(len(KVStorage.current()), len(list(KVStorage.current())))
+        KVStorage.set_mode(None, "mode.")
+        self.assertEqual(KVStorage.current().get("mode.a"), 1)
+        s.exit()
+
+    def test_kvstorage_list(self):
+        s = KVStorage()
+        s.put("list.a", [1, 2.5, "x", [True]])
+        self.assertEqual(s.get("list.a"), [1, 2.5, "x", [True]])
+        self.assertEqual(s.storage(), {"list.a": [1, 2.5, "x", [True]]})
+
+    def test_build_info(self):
+        info = build_info()
+        self.assertEqual(info["hash_seed"], 42)
+        self.assertIn("tracking", info["features"])
+
+    def test_kvstorage_simulate(self):
+        s = KVStorage()
+        s.put("simulate.a", 1)
+        s.put("simulate.b", 2)
+        s.enter()
+        proposed = KVStorage()
+        proposed.update({"simulate": {"a": 1, "b": 3, "c": 4}})
+        self.assertEqual(
+            proposed.simulate(),
+            [
+                {"key": "simulate.b", "new": 3, "old": 2, "layer": "thread"},
+                {"key": "simulate.c", "new": 4, "old": None, "layer": None},
+            ],
+        )
+        self.assertEqual(KVStorage.current().get("simulate.b"), 2)
+        s.exit()
+
+    def test_record_and_replay(self):
+        import json
+        import os
+        import tempfile
+
+        from hyperparameter.recording import recording, replay
+
+        path = os.path.join(tempfile.mkdtemp(), "session.jsonl")
+        with recording(path):
+            s = KVStorage()
+            s.update({"record": {"a": 1}})
+            s.enter()
+            KVStorage.current().get("record.a")
+            s.exit()
+        with open(path) as f:
+            calls = [json.loads(line) for line in f]
+        self.assertEqual(
+            [c["method"] for c in calls],
+            ["new", "update", "enter", "current", "get", "exit"],
+        )
+        self.assertEqual(calls[1]["args"], [{"record": {"a": 1}}])
+
+        results = []
+        replay(path, lambda call, result, error: results.append((call["method"], result)))
+        self.assertIn(("get", 1), results)
+
+    def test_replay_storage_created_before_recording(self):
+        import os
+        import tempfile
+
+        from hyperparameter.recording import recording, replay
+
+        path = os.path.join(tempfile.mkdtemp(), "session.jsonl")
+        s = KVStorage()
+        s.enter()
+        try:
+            with recording(path):
+                s.put("record.before", 1)
+                s.get("record.before")
+        finally:
+            s.exit()
+
+        results = []
+        replay(path, lambda call, result, error: results.append((call["method"], result, error)))
+        self.assertEqual([("put", None, None), ("get", 1, None)], results)